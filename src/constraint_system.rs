@@ -3,6 +3,8 @@ use std::marker::PhantomData;
 
 use ff::PrimeField;
 
+use crate::multicore::THREAD_POOL;
+use crate::util_cs::shape_cs::CircuitDigest;
 use crate::{gpu, Index, LinearCombination, Variable};
 
 /// Computations are expressed in terms of arithmetic circuits, in particular
@@ -56,6 +58,25 @@ pub enum SynthesisError {
     IncompatibleLengthVector(String),
     #[error("invalid pairing")]
     InvalidPairing,
+    /// During MPC verification, a contribution to the parameters was invalid.
+    #[error("invalid MPC contribution: {0}")]
+    InvalidContribution(String),
+    /// During proof generation, the circuit didn't match the circuit digest recorded with
+    /// the parameters, i.e. the parameters were generated for a different circuit.
+    #[error("circuit digest {actual} doesn't match the parameters, expected {expected}")]
+    CircuitDigestMismatch {
+        expected: CircuitDigest,
+        actual: CircuitDigest,
+    },
+    /// During proof generation with the satisfiability check enabled, some constraints
+    /// weren't satisfied by the assignment of the circuit.
+    #[error("{count} constraints are unsatisfied, the first one is `{first}`")]
+    UnsatisfiedConstraints { count: usize, first: String },
+    /// During parameter or proof generation, the circuit digest couldn't be computed, as the
+    /// circuit was extended with a constraint system which wasn't created by
+    /// [`ConstraintSystem::new_for_extend`].
+    #[error("circuit digest can't be computed, the circuit was extended with a constraint system not created by new_for_extend")]
+    CircuitDigestUnavailable,
 }
 
 /// Represents a constraint system which can have new variables
@@ -148,6 +169,70 @@ pub trait ConstraintSystem<Scalar: PrimeField>: Sized + Send {
             "ConstraintSystem::extend must be implemented for types implementing ConstraintSystem"
         );
     }
+
+    /// Creates a constraint system to synthesize an independent part of a circuit into, which
+    /// this constraint system can then be [`extend`](ConstraintSystem::extend)ed with. Its
+    /// first input is the constant one, which `extend` skips. Constraint systems which need
+    /// more than the assignments and constraints of the part to extend themselves with it,
+    /// like the constraints for the circuit digest, can set that up here.
+    ///
+    /// By default, the input is allocated in a constraint system returned by `new`, so
+    /// constraint systems whose `new` already allocates it need to override this.
+    fn new_for_extend(&self) -> Self {
+        let mut cs = Self::new();
+        cs.alloc_input(|| "ONE", || Ok(Scalar::one()))
+            .expect("allocating the input for the constant one failed");
+
+        cs
+    }
+}
+
+/// Synthesizes independent parts of a circuit in parallel, each into its own constraint
+/// system, and extends `cs` with them in order. The result is the same as synthesizing the
+/// parts one after the other, each in a namespace `part <i>`.
+///
+/// The parts can't share any variables, except the constant one, as none of them is
+/// allocated in `cs` while the parts are synthesized. Circuits without independent parts
+/// can't gain anything from this.
+///
+/// The parts are synthesized on the [`THREAD_POOL`], so they must not call this function
+/// themselves. If the root of `cs` isn't [extensible](ConstraintSystem::is_extensible), the
+/// parts are synthesized sequentially instead.
+pub fn synthesize_parallel<Scalar, CS, C>(cs: &mut CS, parts: Vec<C>) -> Result<(), SynthesisError>
+where
+    Scalar: PrimeField,
+    CS: ConstraintSystem<Scalar>,
+    C: Circuit<Scalar> + Send,
+{
+    if !CS::Root::is_extensible() {
+        for (i, part) in parts.into_iter().enumerate() {
+            part.synthesize(&mut cs.namespace(|| format!("part {}", i)))?;
+        }
+        return Ok(());
+    }
+
+    let root = cs.get_root();
+    let mut part_css = parts
+        .iter()
+        .map(|_| CS::Root::new_for_extend(root))
+        .collect::<Vec<_>>();
+    let mut results = Vec::with_capacity(parts.len());
+    results.resize_with(parts.len(), || Ok(()));
+    THREAD_POOL.scoped(|s| {
+        let part_css = part_css.iter_mut().zip(results.iter_mut());
+        for (i, (part, (part_cs, result))) in parts.into_iter().zip(part_css).enumerate() {
+            s.execute(move || {
+                *result = part.synthesize(&mut part_cs.namespace(|| format!("part {}", i)));
+            });
+        }
+    });
+
+    for (part_cs, result) in part_css.into_iter().zip(results) {
+        result?;
+        root.extend(part_cs);
+    }
+
+    Ok(())
 }
 
 /// This is a "namespaced" constraint system which borrows a constraint system (pushing
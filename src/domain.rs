@@ -12,6 +12,7 @@
 //! [Groth16]: https://eprint.iacr.org/2016/260
 
 use ff::{Field, PrimeField};
+use group::Group;
 use pairing::Engine;
 
 use super::multicore::Worker;
@@ -197,6 +198,31 @@ impl<E: Engine + gpu::GpuEngine> EvaluationDomain<E> {
         Ok(())
     }
 
+    /// Performs an inverse FFT over the group elements `a` in the exponent, using the size of
+    /// this domain. Applied to the powers of tau in a group, this yields the Lagrange
+    /// coefficients evaluated at tau in that group.
+    pub fn ifft_group<G: Group<Scalar = E::Fr>>(&self, worker: &Worker, a: &mut [G]) {
+        assert_eq!(a.len(), self.coeffs.len());
+
+        let log_cpus = worker.log_num_cpus();
+        if self.exp <= log_cpus {
+            serial_group_fft(a, &self.omegainv, self.exp);
+        } else {
+            parallel_group_fft(a, worker, &self.omegainv, self.exp, log_cpus);
+        }
+
+        let minv = self.minv;
+        worker.scope(a.len(), |scope, chunk| {
+            for v in a.chunks_mut(chunk) {
+                scope.execute(move || {
+                    for v in v {
+                        *v *= minv;
+                    }
+                });
+            }
+        });
+    }
+
     /// This evaluates t(tau) for this domain, which is
     /// tau^m - 1 for these radix-2 domains.
     pub fn z(&self, tau: &E::Fr) -> E::Fr {
@@ -398,6 +424,106 @@ fn parallel_fft<E: Engine>(
     });
 }
 
+#[allow(clippy::many_single_char_names)]
+fn serial_group_fft<G: Group>(a: &mut [G], omega: &G::Scalar, log_n: u32) {
+    fn bitreverse(mut n: u32, l: u32) -> u32 {
+        let mut r = 0;
+        for _ in 0..l {
+            r = (r << 1) | (n & 1);
+            n >>= 1;
+        }
+        r
+    }
+
+    let n = a.len() as u32;
+    assert_eq!(n, 1 << log_n);
+
+    for k in 0..n {
+        let rk = bitreverse(k, log_n);
+        if k < rk {
+            a.swap(rk as usize, k as usize);
+        }
+    }
+
+    let mut m = 1;
+    for _ in 0..log_n {
+        let w_m = omega.pow_vartime([u64::from(n / (2 * m))]);
+
+        let mut k = 0;
+        while k < n {
+            let mut w = G::Scalar::one();
+            for j in 0..m {
+                let mut t = a[(k + j + m) as usize];
+                t *= w;
+                let mut tmp = a[(k + j) as usize];
+                tmp -= t;
+                a[(k + j + m) as usize] = tmp;
+                a[(k + j) as usize] += t;
+                w *= w_m;
+            }
+
+            k += 2 * m;
+        }
+
+        m *= 2;
+    }
+}
+
+fn parallel_group_fft<G: Group>(
+    a: &mut [G],
+    worker: &Worker,
+    omega: &G::Scalar,
+    log_n: u32,
+    log_cpus: u32,
+) {
+    assert!(log_n >= log_cpus);
+
+    let num_cpus = 1 << log_cpus;
+    let log_new_n = log_n - log_cpus;
+    let mut tmp = vec![vec![G::identity(); 1 << log_new_n]; num_cpus];
+    let new_omega = omega.pow_vartime([num_cpus as u64]);
+
+    worker.scope(0, |scope, _| {
+        let a = &*a;
+
+        for (j, tmp) in tmp.iter_mut().enumerate() {
+            scope.execute(move || {
+                // Shuffle into a sub-FFT
+                let omega_j = omega.pow_vartime([j as u64]);
+                let omega_step = omega.pow_vartime([(j as u64) << log_new_n]);
+
+                let mut elt = G::Scalar::one();
+                for (i, tmp) in tmp.iter_mut().enumerate() {
+                    for s in 0..num_cpus {
+                        let idx = (i + (s << log_new_n)) % (1 << log_n);
+                        let mut t = a[idx];
+                        t *= elt;
+                        *tmp += t;
+                        elt *= omega_step;
+                    }
+                    elt *= omega_j;
+                }
+
+                // Perform sub-FFT
+                serial_group_fft(tmp, &new_omega, log_new_n);
+            });
+        }
+    });
+
+    worker.scope(a.len(), |scope, chunk| {
+        let tmp = &tmp;
+
+        for (idx, a) in a.chunks_mut(chunk).enumerate() {
+            scope.execute(move || {
+                let mask = (1 << log_cpus) - 1;
+                for (idx, a) in (idx * chunk..).zip(a) {
+                    *a = tmp[idx & mask][idx >> log_cpus];
+                }
+            });
+        }
+    });
+}
+
 // Test multiplying various (low degree) polynomials together and
 // comparing with naive evaluations.
 #[test]
@@ -514,6 +640,46 @@ fn parallel_fft_consistency() {
     test_consistency::<Bls12, _>(rng);
 }
 
+#[test]
+fn group_ifft_consistency() {
+    use blstrs::{Bls12, G1Projective};
+
+    let rng = &mut rand::thread_rng();
+    let worker = Worker::new();
+
+    for log_d in 0..7 {
+        let d = 1 << log_d;
+
+        let scalars = (0..d)
+            .map(|_| <Bls12 as Engine>::Fr::random(&mut *rng))
+            .collect::<Vec<_>>();
+        let mut domain = EvaluationDomain::<Bls12>::from_coeffs(scalars.clone()).unwrap();
+        let mut points = scalars
+            .iter()
+            .map(|s| G1Projective::generator() * s)
+            .collect::<Vec<_>>();
+        let mut points_parallel = points.clone();
+
+        domain.ifft_group(&worker, &mut points);
+        domain.ifft(&worker, &mut None).unwrap();
+
+        let expected = domain
+            .as_ref()
+            .iter()
+            .map(|s| G1Projective::generator() * s)
+            .collect::<Vec<_>>();
+        assert!(points == expected);
+
+        if log_d >= 2 {
+            parallel_group_fft(&mut points_parallel, &worker, &domain.omegainv, log_d, 2);
+            for p in points_parallel.iter_mut() {
+                *p *= domain.minv;
+            }
+            assert!(points_parallel == expected);
+        }
+    }
+}
+
 pub fn create_fft_kernel<E>(_log_d: usize, priority: bool) -> Option<gpu::FFTKernel<E>>
 where
     E: Engine + gpu::GpuEngine,
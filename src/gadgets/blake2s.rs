@@ -415,6 +415,7 @@ mod test {
     use super::blake2s;
     use crate::gadgets::boolean::{AllocatedBit, Boolean};
     use crate::gadgets::test::TestConstraintSystem;
+    use crate::util_cs::snapshot::assert_snapshot;
     use crate::ConstraintSystem;
 
     #[test]
@@ -443,16 +444,26 @@ mod test {
     #[test]
     fn test_blake2s_constraints() {
         let mut cs = TestConstraintSystem::<Fr>::new();
-        let input_bits: Vec<_> = (0..512)
-            .map(|i| {
-                AllocatedBit::alloc(cs.namespace(|| format!("input bit {}", i)), Some(true))
-                    .unwrap()
-                    .into()
-            })
-            .collect();
-        blake2s(&mut cs, &input_bits, b"12345678").unwrap();
+        let input_bits: Vec<_> = {
+            let mut cs = cs.namespace(|| "input");
+            (0..512)
+                .map(|i| {
+                    AllocatedBit::alloc(cs.namespace(|| format!("input bit {}", i)), Some(true))
+                        .unwrap()
+                        .into()
+                })
+                .collect()
+        };
+        blake2s(cs.namespace(|| "blake2s"), &input_bits, b"12345678").unwrap();
         assert!(cs.is_satisfied());
         assert_eq!(cs.num_constraints(), 21518);
+        assert_snapshot(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/gadgets/test/snapshots/blake2s.snap"
+            ),
+            &cs.snapshot(1),
+        );
     }
 
     #[test]
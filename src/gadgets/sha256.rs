@@ -276,6 +276,7 @@ mod test {
     use super::*;
     use crate::gadgets::boolean::AllocatedBit;
     use crate::gadgets::test::TestConstraintSystem;
+    use crate::util_cs::snapshot::assert_snapshot;
     use blstrs::Scalar as Fr;
     use rand_core::{RngCore, SeedableRng};
     use rand_xorshift::XorShiftRng;
@@ -341,22 +342,32 @@ mod test {
         ]);
 
         let mut cs = TestConstraintSystem::<Fr>::new();
-        let input_bits: Vec<_> = (0..512)
-            .map(|i| {
-                Boolean::from(
-                    AllocatedBit::alloc(
-                        cs.namespace(|| format!("input bit {}", i)),
-                        Some(rng.next_u32() % 2 != 0),
+        let input_bits: Vec<_> = {
+            let mut cs = cs.namespace(|| "input");
+            (0..512)
+                .map(|i| {
+                    Boolean::from(
+                        AllocatedBit::alloc(
+                            cs.namespace(|| format!("input bit {}", i)),
+                            Some(rng.next_u32() % 2 != 0),
+                        )
+                        .unwrap(),
                     )
-                    .unwrap(),
-                )
-            })
-            .collect();
+                })
+                .collect()
+        };
 
         sha256(cs.namespace(|| "sha256"), &input_bits).unwrap();
 
         assert!(cs.is_satisfied());
         assert_eq!(cs.num_constraints() - 512, 44874);
+        assert_snapshot(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/src/gadgets/test/snapshots/sha256_full_hash.snap"
+            ),
+            &cs.snapshot(1),
+        );
     }
 
     #[test]
@@ -9,6 +9,7 @@ use blake2s_simd::{Params as Blake2sParams, State as Blake2sState};
 use byteorder::{BigEndian, ByteOrder};
 use ff::PrimeField;
 
+use crate::util_cs::snapshot::CircuitSnapshot;
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 
 #[derive(Debug)]
@@ -224,6 +225,19 @@ impl<Scalar: PrimeField> TestConstraintSystem<Scalar> {
         s
     }
 
+    /// A snapshot of the size of the circuit, with namespaces nested deeper than `depth`
+    /// counted in their ancestor at that depth, see
+    /// [`assert_snapshot`](crate::util_cs::snapshot::assert_snapshot).
+    pub fn snapshot(&self, depth: usize) -> CircuitSnapshot {
+        CircuitSnapshot::new(
+            self.hash(),
+            self.inputs.len(),
+            self.aux.len(),
+            self.constraints.iter().map(|c| c.3.as_str()),
+            depth,
+        )
+    }
+
     pub fn which_is_unsatisfied(&self) -> Option<&str> {
         for &(ref a, ref b, ref c, ref path) in &self.constraints {
             let mut a = eval_lc::<Scalar>(a, &self.inputs, &self.aux);
@@ -415,6 +429,70 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for TestConstraintSystem<Scala
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
+
+    // `new` already allocates the input for the constant one.
+    fn new_for_extend(&self) -> Self {
+        Self::new()
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        let input_offset = self.inputs.len() - 1;
+        let aux_offset = self.aux.len();
+        let constraint_offset = self.constraints.len();
+        let prefix = |path: String| {
+            if self.current_namespace.is_empty() {
+                path
+            } else {
+                format!("{}/{}", self.current_namespace.join("/"), path)
+            }
+        };
+
+        let mut named_objects = vec![];
+        for (path, object) in other.named_objects {
+            let object = match object {
+                // The constant one is shared.
+                NamedObject::Var(var) if var == Self::one() => continue,
+                NamedObject::Var(var) => NamedObject::Var(var.shift(input_offset, aux_offset)),
+                NamedObject::Constraint(i) => NamedObject::Constraint(i + constraint_offset),
+                NamedObject::Namespace => NamedObject::Namespace,
+            };
+            named_objects.push((prefix(path), object));
+        }
+        let inputs = other
+            .inputs
+            .into_iter()
+            .skip(1)
+            .map(|(value, path)| (value, prefix(path)))
+            .collect::<Vec<_>>();
+        let aux = other
+            .aux
+            .into_iter()
+            .map(|(value, path)| (value, prefix(path)))
+            .collect::<Vec<_>>();
+        let constraints = other
+            .constraints
+            .into_iter()
+            .map(|(a, b, c, path)| {
+                (
+                    a.shift(input_offset, aux_offset),
+                    b.shift(input_offset, aux_offset),
+                    c.shift(input_offset, aux_offset),
+                    prefix(path),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (path, object) in named_objects {
+            self.set_named_obj(path, object);
+        }
+        self.inputs.extend(inputs);
+        self.aux.extend(aux);
+        self.constraints.extend(constraints);
+    }
 }
 
 #[test]
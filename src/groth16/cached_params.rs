@@ -0,0 +1,639 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+use std::slice;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+use log::{info, warn};
+use memmap::MmapOptions;
+use pairing::MultiMillerLoop;
+use rayon::prelude::*;
+
+use super::header::body_digest;
+use super::{CurveName, MappedParameters, ParameterSource, VerifyingKey};
+use crate::util_cs::shape_cs::CircuitDigest;
+use crate::SynthesisError;
+
+/// Magic bytes at the start of a raw parameter cache file.
+const RAW_CACHE_MAGIC: [u8; 8] = *b"\x89BELLRAW";
+
+/// Version of the raw parameter cache format. Caches of version 1 weren't necessarily
+/// checked, so they are replaced.
+const RAW_CACHE_VERSION: u32 = 2;
+
+/// Number of cached queries: h, l, a, b_g1 and b_g2.
+const NUM_CACHED_SECTIONS: usize = 5;
+
+/// Affine points whose in-memory representation can be written to a file and copied back
+/// into memory byte by byte, which is what the disk cache of [`CachedParameters`] does.
+///
+/// # Safety
+///
+/// Implementors must be plain data without padding or pointers, such that any bytes copied
+/// from a valid point are a valid point again in the same build.
+pub unsafe trait RawAffine: PrimeCurveAffine {}
+
+// Both are `repr(transparent)` wrappers of `repr(C)` structs of `u64` limbs.
+unsafe impl RawAffine for blstrs::G1Affine {}
+unsafe impl RawAffine for blstrs::G2Affine {}
+
+/// Statistics of the lookups of a [`CachedParameters`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Queries served from memory.
+    pub hits: u64,
+    /// Queries loaded from the disk cache.
+    pub disk_hits: u64,
+    /// Queries decoded from the parameter file.
+    pub misses: u64,
+}
+
+/// A [`ParameterSource`] which decodes each query of [`MappedParameters`] at most once and
+/// keeps it in memory for all following proofs.
+///
+/// With [`with_disk_cache`](Self::with_disk_cache), the decoded queries are also stored in
+/// a raw file which other processes load without decoding or checking any points. The file
+/// is mapped and its queries are copied into memory as they are, as multiexps need them in
+/// vectors, see [`SourceBuilder::get`](crate::multiexp::SourceBuilder::get).
+pub struct CachedParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    params: MappedParameters<E>,
+
+    h: Mutex<Option<Arc<Vec<E::G1Affine>>>>,
+    l: Mutex<Option<Arc<Vec<E::G1Affine>>>>,
+    a: Mutex<Option<Arc<Vec<E::G1Affine>>>>,
+    b_g1: Mutex<Option<Arc<Vec<E::G1Affine>>>>,
+    b_g2: Mutex<Option<Arc<Vec<E::G2Affine>>>>,
+
+    hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<E> CachedParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Caches the queries of `params` in memory. Nothing is decoded until a query is used.
+    pub fn new(params: MappedParameters<E>) -> Self {
+        CachedParameters {
+            params,
+            h: Mutex::new(None),
+            l: Mutex::new(None),
+            a: Mutex::new(None),
+            b_g1: Mutex::new(None),
+            b_g2: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            disk_hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Caches the queries of `params` in memory, backed by the raw cache file at `path`.
+    ///
+    /// If `path` holds a cache of the same parameters, all queries are copied from it.
+    /// Otherwise all queries are decoded and written to `path`, replacing any outdated or
+    /// invalid cache. They are always checked before, even if `params.checked` isn't set, so
+    /// this fails without writing the cache if any point is invalid.
+    ///
+    /// The cache file is trusted: its points aren't checked at all when loaded, so it must
+    /// only be writable by trusted processes. It is only valid for the build which wrote it,
+    /// which is detected by comparing the in-memory representation of the generators.
+    pub fn with_disk_cache<P: AsRef<Path>>(params: MappedParameters<E>, path: P) -> io::Result<Self>
+    where
+        E: CurveName,
+        E::G1Affine: RawAffine,
+        E::G2Affine: RawAffine,
+    {
+        let path = path.as_ref();
+        let mut cached = Self::new(params);
+        let key = cache_key(&cached.params);
+
+        match cached.load_raw_cache(path, &key) {
+            Ok(()) => {
+                info!("loaded cached parameters from {}", path.display());
+                return Ok(cached);
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => warn!("ignoring parameter cache {}: {}", path.display(), err),
+        }
+
+        cached.write_raw_cache(path, &key)?;
+        info!("wrote cached parameters to {}", path.display());
+
+        Ok(cached)
+    }
+
+    /// The underlying parameters.
+    pub fn params(&self) -> &MappedParameters<E> {
+        &self.params
+    }
+
+    /// The lookups of queries since the parameters were created.
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            disk_hits: self.disk_hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the cached query, decoding it from `ranges` of the parameter file on the
+    /// first use. Concurrent callers wait for a single decoding.
+    fn get<G>(
+        &self,
+        slot: &Mutex<Option<Arc<Vec<G>>>>,
+        ranges: &[Range<usize>],
+    ) -> io::Result<Arc<Vec<G>>>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+    {
+        let mut slot = slot.lock().unwrap();
+        if let Some(points) = &*slot {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(points.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let points = Arc::new(self.decode(ranges, self.params.checked)?);
+        *slot = Some(points.clone());
+
+        Ok(points)
+    }
+
+    /// Decodes the query at `ranges` of the parameter file, checking its points if `checked`
+    /// is set.
+    fn decode<G>(&self, ranges: &[Range<usize>], checked: bool) -> io::Result<Vec<G>>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+    {
+        ranges
+            .par_iter()
+            .map(|range| {
+                self.params
+                    .encoding
+                    .read_point(&self.params.params[range.clone()], checked)
+            })
+            .collect()
+    }
+
+    /// Decodes the query like [`get`](Self::get), but always checks its points, as the disk
+    /// cache is trusted by all processes loading it. Replaces what was cached in memory.
+    fn get_checked<G>(
+        &self,
+        slot: &Mutex<Option<Arc<Vec<G>>>>,
+        ranges: &[Range<usize>],
+    ) -> io::Result<Arc<Vec<G>>>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+    {
+        let mut slot = slot.lock().unwrap();
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let points = Arc::new(self.decode(ranges, true)?);
+        *slot = Some(points.clone());
+
+        Ok(points)
+    }
+
+    fn load_raw_cache(&mut self, path: &Path, key: &[u8; 32]) -> io::Result<()>
+    where
+        E: CurveName,
+        E::G1Affine: RawAffine,
+        E::G2Affine: RawAffine,
+    {
+        let file = File::open(path)?;
+        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let sections = read_raw_header::<E>(&mmap, key)?;
+
+        let expected = [
+            self.params.h.len(),
+            self.params.l.len(),
+            self.params.a.len(),
+            self.params.b_g1.len(),
+            self.params.b_g2.len(),
+        ];
+        let sizes = [
+            mem::size_of::<E::G1Affine>(),
+            mem::size_of::<E::G1Affine>(),
+            mem::size_of::<E::G1Affine>(),
+            mem::size_of::<E::G1Affine>(),
+            mem::size_of::<E::G2Affine>(),
+        ];
+        for ((section, len), size) in sections.iter().zip(&expected).zip(&sizes) {
+            if section.end > mmap.len() || section.end - section.start != len * size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "parameter cache sections don't match the parameters",
+                ));
+            }
+        }
+
+        *self.h.get_mut().unwrap() = Some(Arc::new(read_raw(&mmap[sections[0].clone()])));
+        *self.l.get_mut().unwrap() = Some(Arc::new(read_raw(&mmap[sections[1].clone()])));
+        *self.a.get_mut().unwrap() = Some(Arc::new(read_raw(&mmap[sections[2].clone()])));
+        *self.b_g1.get_mut().unwrap() = Some(Arc::new(read_raw(&mmap[sections[3].clone()])));
+        *self.b_g2.get_mut().unwrap() = Some(Arc::new(read_raw(&mmap[sections[4].clone()])));
+        self.disk_hits
+            .fetch_add(NUM_CACHED_SECTIONS as u64, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    fn write_raw_cache(&self, path: &Path, key: &[u8; 32]) -> io::Result<()>
+    where
+        E: CurveName,
+        E::G1Affine: RawAffine,
+        E::G2Affine: RawAffine,
+    {
+        let h = self.get_checked(&self.h, &self.params.h)?;
+        let l = self.get_checked(&self.l, &self.params.l)?;
+        let a = self.get_checked(&self.a, &self.params.a)?;
+        let b_g1 = self.get_checked(&self.b_g1, &self.params.b_g1)?;
+        let b_g2 = self.get_checked(&self.b_g2, &self.params.b_g2)?;
+        let sections = [
+            raw_bytes(&h[..]),
+            raw_bytes(&l[..]),
+            raw_bytes(&a[..]),
+            raw_bytes(&b_g1[..]),
+            raw_bytes(&b_g2[..]),
+        ];
+
+        let mut header = vec![];
+        header.write_all(&RAW_CACHE_MAGIC)?;
+        header.write_u32::<BigEndian>(RAW_CACHE_VERSION)?;
+        header.write_u32::<BigEndian>(E::CURVE_NAME.len() as u32)?;
+        header.write_all(E::CURVE_NAME.as_bytes())?;
+        header.write_all(key)?;
+        write_layout::<E>(&mut header)?;
+        let mut offset = (header.len() + NUM_CACHED_SECTIONS * 2 * mem::size_of::<u64>()) as u64;
+        for section in &sections {
+            header.write_u64::<BigEndian>(offset)?;
+            header.write_u64::<BigEndian>(section.len() as u64)?;
+            offset += section.len() as u64;
+        }
+
+        // Other processes may read the cache at any time, so it is written to a temporary
+        // file first, which then atomically replaces the cache.
+        let tmp_path = path.with_extension(format!("tmp.{}", process::id()));
+        let write = || -> io::Result<()> {
+            let mut file = io::BufWriter::new(File::create(&tmp_path)?);
+            file.write_all(&header)?;
+            for section in &sections {
+                file.write_all(section)?;
+            }
+            file.into_inner()?.sync_all()?;
+            fs::rename(&tmp_path, path)
+        };
+        let result = write();
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+
+        result
+    }
+}
+
+impl<E> ParameterSource<E> for &CachedParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
+    type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
+
+    fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
+        Ok(&self.params.vk)
+    }
+
+    fn get_h(&self, _num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
+        Ok((self.get(&self.h, &self.params.h)?, 0))
+    }
+
+    fn get_l(&self, _num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
+        Ok((self.get(&self.l, &self.params.l)?, 0))
+    }
+
+    fn get_a(
+        &self,
+        num_inputs: usize,
+        _num_a: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        let a = self.get(&self.a, &self.params.a)?;
+        Ok(((a.clone(), 0), (a, num_inputs)))
+    }
+
+    fn get_b_g1(
+        &self,
+        num_inputs: usize,
+        _num_b_g1: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        let b_g1 = self.get(&self.b_g1, &self.params.b_g1)?;
+        Ok(((b_g1.clone(), 0), (b_g1, num_inputs)))
+    }
+
+    fn get_b_g2(
+        &self,
+        num_inputs: usize,
+        _num_b_g2: usize,
+    ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        let b_g2 = self.get(&self.b_g2, &self.params.b_g2)?;
+        Ok(((b_g2.clone(), 0), (b_g2, num_inputs)))
+    }
+
+    fn get_circuit_digest(&self) -> Option<CircuitDigest> {
+        self.params.circuit_digest
+    }
+}
+
+/// Identifies parameters by their verifying key, which is unique to each setup, and the
+/// sizes of their queries.
+fn cache_key<E: MultiMillerLoop>(params: &MappedParameters<E>) -> [u8; 32] {
+    let mut bytes = vec![];
+    params
+        .vk
+        .write(&mut bytes)
+        .expect("writing to a vector cannot fail");
+    for len in &[
+        params.h.len(),
+        params.l.len(),
+        params.a.len(),
+        params.b_g1.len(),
+        params.b_g2.len(),
+    ] {
+        bytes.extend_from_slice(&(*len as u64).to_be_bytes());
+    }
+
+    body_digest(&bytes)
+}
+
+/// Writes the in-memory representation of the generators, which differs between builds
+/// with an incompatible representation of points.
+fn write_layout<E>(writer: &mut Vec<u8>) -> io::Result<()>
+where
+    E: MultiMillerLoop,
+    E::G1Affine: RawAffine,
+    E::G2Affine: RawAffine,
+{
+    let g1 = [E::G1Affine::generator()];
+    let g2 = [E::G2Affine::generator()];
+    for bytes in &[raw_bytes(&g1[..]), raw_bytes(&g2[..])] {
+        writer.write_u32::<BigEndian>(bytes.len() as u32)?;
+        writer.write_all(bytes)?;
+    }
+
+    Ok(())
+}
+
+/// Validates the header of a raw cache file and returns the byte ranges of its sections.
+fn read_raw_header<E>(mut bytes: &[u8], key: &[u8; 32]) -> io::Result<Vec<Range<usize>>>
+where
+    E: MultiMillerLoop + CurveName,
+    E::G1Affine: RawAffine,
+    E::G2Affine: RawAffine,
+{
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut magic = [0u8; RAW_CACHE_MAGIC.len()];
+    bytes.read_exact(&mut magic)?;
+    if magic != RAW_CACHE_MAGIC {
+        return Err(invalid("not a parameter cache"));
+    }
+    if bytes.read_u32::<BigEndian>()? != RAW_CACHE_VERSION {
+        return Err(invalid("unsupported parameter cache version"));
+    }
+
+    let curve_len = bytes.read_u32::<BigEndian>()? as usize;
+    if curve_len != E::CURVE_NAME.len() || bytes.len() < curve_len {
+        return Err(invalid("parameter cache is for a different curve"));
+    }
+    let (curve, rest) = bytes.split_at(curve_len);
+    if curve != E::CURVE_NAME.as_bytes() {
+        return Err(invalid("parameter cache is for a different curve"));
+    }
+    bytes = rest;
+
+    let mut cached_key = [0u8; 32];
+    bytes.read_exact(&mut cached_key)?;
+    if &cached_key != key {
+        return Err(invalid("parameter cache is for different parameters"));
+    }
+
+    let mut layout = vec![];
+    write_layout::<E>(&mut layout)?;
+    if bytes.len() < layout.len() || bytes[..layout.len()] != layout[..] {
+        return Err(invalid(
+            "parameter cache was written by an incompatible build",
+        ));
+    }
+    bytes = &bytes[layout.len()..];
+
+    (0..NUM_CACHED_SECTIONS)
+        .map(|_| {
+            let start = bytes.read_u64::<BigEndian>()? as usize;
+            let len = bytes.read_u64::<BigEndian>()? as usize;
+            Ok(start..start.saturating_add(len))
+        })
+        .collect()
+}
+
+fn raw_bytes<G: RawAffine>(points: &[G]) -> &[u8] {
+    // Safety: `RawAffine` guarantees that points are plain data without padding.
+    unsafe { slice::from_raw_parts(points.as_ptr() as *const u8, mem::size_of_val(points)) }
+}
+
+/// Copies points out of `bytes`, which must have been written by `raw_bytes`.
+fn read_raw<G: RawAffine>(bytes: &[u8]) -> Vec<G> {
+    let len = bytes.len() / mem::size_of::<G>();
+    let mut points = Vec::<G>::with_capacity(len);
+    // Safety: `bytes` holds exactly `len` points written by the same build, see
+    // `RawAffine`, and copying bytewise doesn't require `bytes` to be aligned.
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            points.as_mut_ptr() as *mut u8,
+            len * mem::size_of::<G>(),
+        );
+        points.set_len(len);
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+        Parameters,
+    };
+    use crate::{Circuit, ConstraintSystem};
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct CubeCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for CubeCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let x2 = cs.alloc(
+                || "x^2",
+                || {
+                    self.x
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    self.x
+                        .map(|x| x.square() * x)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = x^2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x^2 * x = y", |lc| lc + x2, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    fn map_params(params: &Parameters<Bls12>, dir: &Path, name: &str) -> MappedParameters<Bls12> {
+        let path = dir.join(name);
+        params
+            .write_compressed(File::create(&path).unwrap())
+            .unwrap();
+        Parameters::build_mapped_parameters(path, true).unwrap()
+    }
+
+    fn prove(cached: &CachedParameters<Bls12>, rng: &mut XorShiftRng) {
+        let x = Fr::random(&mut *rng);
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, cached, rng).unwrap();
+        let pvk = prepare_verifying_key(&cached.params().vk);
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+    }
+
+    #[test]
+    fn test_cached_parameters() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+
+        // In memory only
+        let cached = CachedParameters::new(map_params(&params, dir.path(), "params-1"));
+        assert_eq!(cached.stats(), CacheStats::default());
+        prove(&cached, rng);
+        let stats = cached.stats();
+        assert_eq!((stats.hits, stats.misses), (0, 5));
+        prove(&cached, rng);
+        assert_eq!(cached.stats().hits, 5);
+        assert!(*cached.h.lock().unwrap().as_ref().unwrap() == params.h);
+        assert!(*cached.b_g2.lock().unwrap().as_ref().unwrap() == params.b_g2);
+
+        // The first process writes the disk cache, following ones only load it.
+        let cache_path = dir.path().join("params.cache");
+        let cached = CachedParameters::with_disk_cache(
+            map_params(&params, dir.path(), "params-2"),
+            &cache_path,
+        )
+        .unwrap();
+        assert_eq!(cached.stats().misses, 5);
+        assert!(cache_path.exists());
+
+        let cached = CachedParameters::with_disk_cache(
+            map_params(&params, dir.path(), "params-3"),
+            &cache_path,
+        )
+        .unwrap();
+        let stats = cached.stats();
+        assert_eq!((stats.disk_hits, stats.misses), (5, 0));
+        assert!(*cached.l.lock().unwrap().as_ref().unwrap() == params.l);
+        assert!(*cached.b_g2.lock().unwrap().as_ref().unwrap() == params.b_g2);
+        prove(&cached, rng);
+        assert_eq!(cached.stats().hits, 5);
+
+        // A cache of other parameters is replaced.
+        let other =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+        let cached = CachedParameters::with_disk_cache(
+            map_params(&other, dir.path(), "other-1"),
+            &cache_path,
+        )
+        .unwrap();
+        assert_eq!(cached.stats().misses, 5);
+        prove(&cached, rng);
+
+        // So is a corrupted one.
+        let mut bytes = fs::read(&cache_path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        fs::write(&cache_path, &bytes).unwrap();
+        let cached = CachedParameters::with_disk_cache(
+            map_params(&other, dir.path(), "other-2"),
+            &cache_path,
+        )
+        .unwrap();
+        assert_eq!(cached.stats().misses, 5);
+        let cached = CachedParameters::with_disk_cache(
+            map_params(&other, dir.path(), "other-3"),
+            &cache_path,
+        )
+        .unwrap();
+        assert_eq!(cached.stats().disk_hits, 5);
+    }
+
+    #[test]
+    fn test_disk_cache_checks_points() {
+        use blstrs::G1Affine;
+        use group::GroupEncoding;
+
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let dir = tempfile::tempdir().unwrap();
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+        let path = dir.path().join("params");
+        let range = map_params(&params, dir.path(), "params").l[0].clone();
+
+        // Replace a point of L by one on the curve, but not in the subgroup, which only
+        // unchecked decoding accepts.
+        let mut bytes = fs::read(&path).unwrap();
+        let mut repr = <G1Affine as GroupEncoding>::Repr::default();
+        repr.as_mut().copy_from_slice(&bytes[range.clone()]);
+        loop {
+            repr.as_mut()[47] = repr.as_ref()[47].wrapping_add(1);
+            if bool::from(G1Affine::from_bytes_unchecked(&repr).is_some())
+                && bool::from(G1Affine::from_bytes(&repr).is_none())
+            {
+                break;
+            }
+        }
+        bytes[range].copy_from_slice(repr.as_ref());
+        fs::write(&path, &bytes).unwrap();
+
+        let unchecked = Parameters::<Bls12>::build_mapped_parameters(path, false).unwrap();
+        let cache_path = dir.path().join("params.cache");
+        assert!(CachedParameters::with_disk_cache(unchecked, &cache_path).is_err());
+        assert!(!cache_path.exists());
+    }
+}
@@ -1,6 +1,11 @@
-use super::{create_proof_batch_priority, create_random_proof_batch_priority};
-use super::{ParameterSource, Proof};
+use super::{
+    create_proof_batch_priority, create_proof_batch_with_shape_priority,
+    create_proof_from_assignments_batch_priority, create_proof_from_witnesses_batch_priority,
+    create_random_proof_batch_priority,
+};
+use super::{Assignment, ParameterSource, Proof, R1csShape, Witness};
 use crate::{gpu, Circuit, SynthesisError};
+use ff::Field;
 use pairing::MultiMillerLoop;
 use rand_core::RngCore;
 
@@ -115,3 +120,113 @@ where
 {
     create_random_proof_batch_priority::<E, C, R, P>(circuits, params, rng, true)
 }
+
+pub fn create_proof_from_witness<E, P: ParameterSource<E>>(
+    witness: Witness<E::Fr>,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    let proofs = create_proof_from_witnesses_batch_priority::<E, P>(
+        vec![witness],
+        params,
+        vec![r],
+        vec![s],
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_random_proof_from_witness<E, R, P: ParameterSource<E>>(
+    witness: Witness<E::Fr>,
+    params: P,
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    R: RngCore,
+{
+    let r = E::Fr::random(&mut *rng);
+    let s = E::Fr::random(&mut *rng);
+
+    create_proof_from_witness::<E, P>(witness, params, r, s)
+}
+
+pub fn create_proof_from_assignment<E, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    assignment: Assignment<E::Fr>,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    let proofs = create_proof_from_assignments_batch_priority::<E, P>(
+        shape,
+        vec![assignment],
+        params,
+        vec![r],
+        vec![s],
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_random_proof_from_assignment<E, R, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    assignment: Assignment<E::Fr>,
+    params: P,
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    R: RngCore,
+{
+    let r = E::Fr::random(&mut *rng);
+    let s = E::Fr::random(&mut *rng);
+
+    create_proof_from_assignment::<E, P>(shape, assignment, params, r, s)
+}
+
+pub fn create_proof_with_shape<E, C, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    circuit: C,
+    params: P,
+    r: E::Fr,
+    s: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    C: Circuit<E::Fr> + Send,
+{
+    let proofs = create_proof_batch_with_shape_priority::<E, C, P>(
+        shape,
+        vec![circuit],
+        params,
+        vec![r],
+        vec![s],
+        false,
+    )?;
+    Ok(proofs.into_iter().next().unwrap())
+}
+
+pub fn create_random_proof_with_shape<E, C, R, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    circuit: C,
+    params: P,
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    C: Circuit<E::Fr> + Send,
+    R: RngCore,
+{
+    let r = E::Fr::random(&mut *rng);
+    let s = E::Fr::random(&mut *rng);
+
+    create_proof_with_shape::<E, C, P>(shape, circuit, params, r, s)
+}
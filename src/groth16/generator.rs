@@ -1,20 +1,29 @@
-use std::ops::{AddAssign, Mul, MulAssign};
+use std::cmp::min;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::ops::{AddAssign, Mul, MulAssign, Range};
 
 use std::sync::Arc;
 
+use byteorder::{BigEndian, WriteBytesExt};
+
 use ff::{Field, PrimeField};
 use group::{
     prime::{PrimeCurve, PrimeCurveAffine},
-    Curve, Group, Wnaf, WnafGroup,
+    Curve, Group, UncompressedEncoding, Wnaf, WnafGroup,
 };
 use pairing::{Engine, MultiMillerLoop};
 use rand_core::RngCore;
+use rayon::prelude::*;
 
-use super::{Parameters, VerifyingKey};
+use super::header::HashingWriter;
+use super::{
+    CurveName, ParameterHeader, Parameters, PointEncoding, VerifyingKey, NUM_PARAMETER_SECTIONS,
+};
 
 use crate::domain::EvaluationDomain;
 use crate::gpu;
 use crate::multicore::Worker;
+use crate::util_cs::shape_cs::{CircuitDigest, ExtensibleHasher};
 use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 
 /// Generates a random common reference string for
@@ -43,16 +52,22 @@ where
 
 /// This is our assembly structure that we'll use to synthesize the
 /// circuit into a QAP.
-struct KeypairAssembly<Scalar: PrimeField> {
-    num_inputs: usize,
-    num_aux: usize,
-    num_constraints: usize,
-    at_inputs: Vec<Vec<(Scalar, usize)>>,
-    bt_inputs: Vec<Vec<(Scalar, usize)>>,
-    ct_inputs: Vec<Vec<(Scalar, usize)>>,
-    at_aux: Vec<Vec<(Scalar, usize)>>,
-    bt_aux: Vec<Vec<(Scalar, usize)>>,
-    ct_aux: Vec<Vec<(Scalar, usize)>>,
+pub(crate) struct KeypairAssembly<Scalar: PrimeField> {
+    pub(crate) num_inputs: usize,
+    pub(crate) num_aux: usize,
+    pub(crate) num_constraints: usize,
+    pub(crate) at_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(crate) bt_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(crate) ct_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(crate) at_aux: Vec<Vec<(Scalar, usize)>>,
+    pub(crate) bt_aux: Vec<Vec<(Scalar, usize)>>,
+    pub(crate) ct_aux: Vec<Vec<(Scalar, usize)>>,
+    /// Dropped when the assembly is extended with one which didn't record its constraints,
+    /// see [`ExtensibleHasher::extend`].
+    hasher: Option<ExtensibleHasher<Scalar>>,
+    /// The digest of the circuit, excluding the input constraints. Set by
+    /// [`synthesize_assembly`].
+    pub(crate) circuit_digest: Option<CircuitDigest>,
 }
 
 impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
@@ -69,17 +84,23 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
             at_aux: vec![],
             bt_aux: vec![],
             ct_aux: vec![],
+            hasher: Some(ExtensibleHasher::new()),
+            circuit_digest: None,
         }
     }
 
-    /// Explicitly declare this `ConstraintSystem` is not extensible as a reminder to future implementers.
-    /// By forbidding use of `ConstraintSystem::extend` when generating Groth parameters, we enforce
-    /// the requirement of a well-defined sequential circuit synthesis. This also means we know that any
-    /// synthesized `ProvingAssignment` is well-formed if it leads to a verifiable proof using the resulting
-    /// groth parameters and verifying key. This is true even if the `ProvingAssignment` was synthesized
-    /// in parallel components which were then joined by `ConstraintSystem::extend`.
+    /// Generating Groth parameters requires a well-defined sequential circuit synthesis, so that
+    /// any synthesized `ProvingAssignment` is well-formed if it leads to a verifiable proof using
+    /// the resulting parameters and verifying key, no matter whether either of them was
+    /// synthesized in parallel components which were then joined by `ConstraintSystem::extend`.
+    ///
+    /// Extending the assembly keeps that: the assembly only depends on the constraints, and
+    /// components created by `new_for_extend` record theirs, which are hashed with their final
+    /// indices when the assembly is extended with them. The parameters and their circuit digest
+    /// are therefore the same as for the circuit synthesized sequentially. Extending it with
+    /// any other assembly makes [`synthesize_assembly`] fail, as the digest is unknown.
     fn is_extensible() -> bool {
-        false
+        true
     }
 
     fn alloc<F, A, AR>(&mut self, _: A, _: F) -> Result<Variable, SynthesisError>
@@ -93,6 +114,9 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
 
         let index = self.num_aux;
         self.num_aux += 1;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc_aux();
+        }
 
         self.at_aux.push(vec![]);
         self.bt_aux.push(vec![]);
@@ -112,6 +136,9 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
 
         let index = self.num_inputs;
         self.num_inputs += 1;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc_input();
+        }
 
         self.at_inputs.push(vec![]);
         self.bt_inputs.push(vec![]);
@@ -142,20 +169,27 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
             }
         }
 
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+        if let Some(hasher) = &mut self.hasher {
+            hasher.enforce(&a, &b, &c);
+        }
+
         eval(
-            a(LinearCombination::zero()),
+            a,
             &mut self.at_inputs,
             &mut self.at_aux,
             self.num_constraints,
         );
         eval(
-            b(LinearCombination::zero()),
+            b,
             &mut self.bt_inputs,
             &mut self.bt_aux,
             self.num_constraints,
         );
         eval(
-            c(LinearCombination::zero()),
+            c,
             &mut self.ct_inputs,
             &mut self.ct_aux,
             self.num_constraints,
@@ -179,6 +213,80 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
+
+    fn new_for_extend(&self) -> Self {
+        let mut cs = Self::new();
+        cs.hasher = self.hasher.as_ref().map(ExtensibleHasher::for_part);
+        cs.alloc_input(|| "ONE", || Ok(Scalar::one()))
+            .expect("allocating the input for the constant one failed");
+
+        cs
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.hasher = match self.hasher.take() {
+            Some(hasher) => hasher.extend(other.hasher),
+            None => None,
+        };
+
+        let offset = self.num_constraints;
+        let shift = |columns: Vec<Vec<(Scalar, usize)>>| {
+            columns.into_iter().map(move |column| {
+                column
+                    .into_iter()
+                    .map(|(coeff, constraint)| (coeff, constraint + offset))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        // The first input of `other` is the constant one, which is shared.
+        for (columns, other) in [
+            (&mut self.at_inputs, other.at_inputs),
+            (&mut self.bt_inputs, other.bt_inputs),
+            (&mut self.ct_inputs, other.ct_inputs),
+        ]
+        .iter_mut()
+        {
+            let mut other = shift(std::mem::take(other));
+            columns[0].extend(other.next().expect("missing input for the constant one"));
+            columns.extend(other);
+        }
+        self.at_aux.extend(shift(other.at_aux));
+        self.bt_aux.extend(shift(other.bt_aux));
+        self.ct_aux.extend(shift(other.ct_aux));
+
+        self.num_inputs += other.num_inputs - 1;
+        self.num_aux += other.num_aux;
+        self.num_constraints += other.num_constraints;
+    }
+}
+
+/// Synthesizes the circuit into a `KeypairAssembly`, including the input constraints
+/// which ensure full density of the IC query.
+pub(crate) fn synthesize_assembly<Scalar, C>(
+    circuit: C,
+) -> Result<KeypairAssembly<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar>,
+{
+    let mut assembly = KeypairAssembly::new();
+
+    // Allocate the "one" input variable
+    assembly.alloc_input(|| "", || Ok(Scalar::one()))?;
+
+    // Synthesize the circuit.
+    circuit.synthesize(&mut assembly)?;
+    let circuit_digest = assembly.hasher.as_ref().and_then(ExtensibleHasher::digest);
+    assembly.circuit_digest = Some(circuit_digest.ok_or(SynthesisError::CircuitDigestUnavailable)?);
+
+    // Input constraints to ensure full density of IC query
+    // x * 0 = 0
+    for i in 0..assembly.num_inputs {
+        assembly.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+    }
+
+    Ok(assembly)
 }
 
 /// Create parameters for a circuit, given some toxic waste.
@@ -199,19 +307,7 @@ where
     <E as Engine>::G2: WnafGroup,
     C: Circuit<E::Fr>,
 {
-    let mut assembly = KeypairAssembly::new();
-
-    // Allocate the "one" input variable
-    assembly.alloc_input(|| "", || Ok(E::Fr::one()))?;
-
-    // Synthesize the circuit.
-    circuit.synthesize(&mut assembly)?;
-
-    // Input constraints to ensure full density of IC query
-    // x * 0 = 0
-    for i in 0..assembly.num_inputs {
-        assembly.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
-    }
+    let assembly = synthesize_assembly(circuit)?;
 
     // Create bases for blind evaluation of polynomials at tau
     let powers_of_tau = vec![E::Fr::zero(); assembly.num_constraints];
@@ -375,21 +471,6 @@ where
                         .zip(bt.iter())
                         .zip(ct.iter())
                     {
-                        fn eval_at_tau<Scalar: PrimeField>(
-                            powers_of_tau: &[Scalar],
-                            p: &[(Scalar, usize)],
-                        ) -> Scalar {
-                            let mut acc = Scalar::zero();
-
-                            for &(ref coeff, index) in p {
-                                let mut n = powers_of_tau[index];
-                                n.mul_assign(coeff);
-                                acc.add_assign(&n);
-                            }
-
-                            acc
-                        }
-
                         // Evaluate QAP polynomials at tau
                         let mut at = eval_at_tau::<E::Fr>(powers_of_tau, at);
                         let mut bt = eval_at_tau::<E::Fr>(powers_of_tau, bt);
@@ -486,6 +567,7 @@ where
 
     Ok(Parameters {
         vk,
+        circuit_digest: assembly.circuit_digest,
         h: Arc::new(h_affine),
         l: Arc::new(l_affine),
 
@@ -510,3 +592,614 @@ where
         ),
     })
 }
+
+/// Number of query elements which are held in memory at once by
+/// `generate_parameters_to_writer`.
+const WRITER_CHUNK_SIZE: usize = 1 << 18;
+
+/// Generates a random common reference string for a circuit and writes it to `writer`,
+/// see [`generate_parameters_to_writer`].
+pub fn generate_random_parameters_to_writer<E, C, R, W>(
+    circuit: C,
+    rng: &mut R,
+    writer: W,
+) -> Result<VerifyingKey<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop + CurveName,
+    <E as Engine>::G1: WnafGroup,
+    <E as Engine>::G2: WnafGroup,
+    C: Circuit<E::Fr>,
+    R: RngCore,
+    W: Write + Seek,
+{
+    let g1 = E::G1::random(&mut *rng);
+    let g2 = E::G2::random(&mut *rng);
+    let alpha = E::Fr::random(&mut *rng);
+    let beta = E::Fr::random(&mut *rng);
+    let gamma = E::Fr::random(&mut *rng);
+    let delta = E::Fr::random(&mut *rng);
+    let tau = E::Fr::random(&mut *rng);
+
+    generate_parameters_to_writer::<E, C, W>(
+        circuit, g1, g2, alpha, beta, gamma, delta, tau, writer,
+    )
+}
+
+/// Create parameters for a circuit, given some toxic waste, and write them to `writer`.
+///
+/// The output is byte for byte what [`Parameters::write_with_header`] produces for the
+/// parameters returned by [`generate_parameters`], including the digest of the circuit, so
+/// it can be loaded with [`Parameters::read`] or [`Parameters::build_mapped_parameters`].
+/// In contrast to `generate_parameters`, the queries are computed and written in chunks, so
+/// only the synthesized circuit and the Lagrange coefficients for tau are kept in memory.
+/// Returns the verifying key.
+///
+/// The digest of the body is only known once all queries are written, so the header is
+/// first written with placeholders, and `writer` is seeked back to fill it in at the end.
+/// Afterwards `writer` is positioned at the end of the parameters.
+///
+/// On error, `writer` may contain partially written parameters.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_parameters_to_writer<E, C, W>(
+    circuit: C,
+    g1: E::G1,
+    g2: E::G2,
+    alpha: E::Fr,
+    beta: E::Fr,
+    gamma: E::Fr,
+    delta: E::Fr,
+    tau: E::Fr,
+    writer: W,
+) -> Result<VerifyingKey<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop + CurveName,
+    <E as Engine>::G1: WnafGroup,
+    <E as Engine>::G2: WnafGroup,
+    C: Circuit<E::Fr>,
+    W: Write + Seek,
+{
+    generate_parameters_to_writer_chunked::<E, C, W>(
+        circuit,
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+        writer,
+        WRITER_CHUNK_SIZE,
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+fn generate_parameters_to_writer_chunked<E, C, W>(
+    circuit: C,
+    g1: E::G1,
+    g2: E::G2,
+    alpha: E::Fr,
+    beta: E::Fr,
+    gamma: E::Fr,
+    delta: E::Fr,
+    tau: E::Fr,
+    mut writer: W,
+    chunk_size: usize,
+) -> Result<VerifyingKey<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop + CurveName,
+    <E as Engine>::G1: WnafGroup,
+    <E as Engine>::G2: WnafGroup,
+    C: Circuit<E::Fr>,
+    W: Write + Seek,
+{
+    let assembly = synthesize_assembly(circuit)?;
+    let num_inputs = assembly.num_inputs;
+    let num_vars = assembly.num_inputs + assembly.num_aux;
+
+    let gamma_inverse: E::Fr =
+        Option::from(gamma.invert()).ok_or(SynthesisError::UnexpectedIdentity)?;
+    let delta_inverse = Option::from(delta.invert()).ok_or(SynthesisError::UnexpectedIdentity)?;
+
+    let worker = Worker::new();
+
+    // Use inverse FFT to convert powers of tau to Lagrange coefficients
+    let mut powers_of_tau =
+        EvaluationDomain::<E>::from_coeffs(vec![E::Fr::zero(); assembly.num_constraints])?;
+    let m = powers_of_tau.as_ref().len();
+    // coeff = t(x) / delta
+    let coeff = powers_of_tau.z(&tau) * delta_inverse;
+    {
+        let powers_of_tau = powers_of_tau.as_mut();
+        worker.scope(powers_of_tau.len(), |scope, chunk| {
+            for (i, powers_of_tau) in powers_of_tau.chunks_mut(chunk).enumerate() {
+                scope.execute(move || {
+                    let mut current_tau_power = tau.pow_vartime([(i * chunk) as u64]);
+
+                    for p in powers_of_tau {
+                        *p = current_tau_power;
+                        current_tau_power.mul_assign(&tau);
+                    }
+                });
+            }
+        });
+    }
+    powers_of_tau.ifft(&worker, &mut None)?;
+    let powers_of_tau = powers_of_tau.into_coeffs();
+
+    // Compute G1 window table
+    let mut g1_wnaf = Wnaf::new();
+    let g1_wnaf = g1_wnaf.base(g1, {
+        // H query
+        (m - 1)
+        // IC/L queries
+        + num_vars
+        // A query
+        + num_vars
+        // B query
+        + num_vars
+    });
+
+    // Compute G2 window table
+    let mut g2_wnaf = Wnaf::new();
+    let g2_wnaf = g2_wnaf.base(g2, {
+        // B query
+        num_vars
+    });
+
+    // (beta * u_i(tau) + alpha * v_i(tau) + w_i(tau)) / (gamma or delta)
+    let ext = |range: Range<usize>| {
+        range
+            .map(|i| {
+                let inv = if i < num_inputs {
+                    gamma_inverse
+                } else {
+                    delta_inverse
+                };
+                let at = qap_poly(&assembly.at_inputs, &assembly.at_aux, i);
+                let bt = qap_poly(&assembly.bt_inputs, &assembly.bt_aux, i);
+                let ct = qap_poly(&assembly.ct_inputs, &assembly.ct_aux, i);
+
+                let mut e = eval_at_tau(&powers_of_tau, at) * beta;
+                e += eval_at_tau(&powers_of_tau, bt) * alpha;
+                e += eval_at_tau(&powers_of_tau, ct);
+                e * inv
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let ic = eval_query(&worker, &g1_wnaf, &ext(0..num_inputs));
+
+    let g1 = g1.to_affine();
+    let g2 = g2.to_affine();
+
+    let vk = VerifyingKey::<E> {
+        alpha_g1: g1.mul(alpha).to_affine(),
+        beta_g1: g1.mul(beta).to_affine(),
+        beta_g2: g2.mul(beta).to_affine(),
+        gamma_g2: g2.mul(gamma).to_affine(),
+        delta_g1: g1.mul(delta).to_affine(),
+        delta_g2: g2.mul(delta).to_affine(),
+        ic,
+    };
+
+    // The header is rewritten with the section lengths and the digest of the body once
+    // all of it was written. Its size doesn't depend on either.
+    let placeholder = ParameterHeader::new::<E>(
+        [0; NUM_PARAMETER_SECTIONS],
+        [0; 32],
+        PointEncoding::Uncompressed,
+        assembly.circuit_digest,
+    );
+    placeholder.write(&mut writer)?;
+
+    let mut writer = HashingWriter::new(writer);
+    let mut section_ends = [0; NUM_PARAMETER_SECTIONS];
+
+    vk.write(&mut writer)?;
+    section_ends[0] = writer.count();
+
+    // Set values of the H query to g1^{(tau^i * t(tau)) / delta}
+    write_query(
+        &mut writer,
+        &worker,
+        &g1_wnaf,
+        0..m - 1,
+        chunk_size,
+        |range| {
+            let mut current_tau_power = tau.pow_vartime([range.start as u64]);
+            range
+                .map(|_| {
+                    let exp = current_tau_power * coeff;
+                    current_tau_power.mul_assign(&tau);
+                    exp
+                })
+                .collect()
+        },
+    )?;
+    section_ends[1] = writer.count();
+
+    // Don't allow any elements be unconstrained, so that
+    // the L query is always fully dense.
+    let l = num_inputs..num_vars;
+    let constrained = l
+        .clone()
+        .step_by(chunk_size)
+        .collect::<Vec<_>>()
+        .into_par_iter()
+        .all(|start| {
+            ext(start..min(start + chunk_size, num_vars))
+                .iter()
+                .all(|e| !bool::from(e.is_zero()))
+        });
+    if !constrained {
+        return Err(SynthesisError::UnconstrainedVariable);
+    }
+    write_query(&mut writer, &worker, &g1_wnaf, l, chunk_size, ext)?;
+    section_ends[2] = writer.count();
+
+    // Points at infinity are filtered away from A/B queries
+    let eval =
+        |inputs: &[Vec<(E::Fr, usize)>], aux: &[Vec<(E::Fr, usize)>], range: Range<usize>| {
+            range
+                .map(|i| eval_at_tau(&powers_of_tau, qap_poly(inputs, aux, i)))
+                .collect::<Vec<_>>()
+        };
+    write_query(
+        &mut writer,
+        &worker,
+        &g1_wnaf,
+        0..num_vars,
+        chunk_size,
+        |range| eval(&assembly.at_inputs, &assembly.at_aux, range),
+    )?;
+    section_ends[3] = writer.count();
+    write_query(
+        &mut writer,
+        &worker,
+        &g1_wnaf,
+        0..num_vars,
+        chunk_size,
+        |range| eval(&assembly.bt_inputs, &assembly.bt_aux, range),
+    )?;
+    section_ends[4] = writer.count();
+    write_query(
+        &mut writer,
+        &worker,
+        &g2_wnaf,
+        0..num_vars,
+        chunk_size,
+        |range| eval(&assembly.bt_inputs, &assembly.bt_aux, range),
+    )?;
+    section_ends[5] = writer.count();
+
+    let (mut writer, body_len, digest) = writer.finalize();
+    let mut section_lengths = section_ends;
+    for (len, previous_end) in section_lengths[1..].iter_mut().zip(&section_ends) {
+        *len -= previous_end;
+    }
+    let header = ParameterHeader::new::<E>(
+        section_lengths,
+        digest,
+        PointEncoding::Uncompressed,
+        assembly.circuit_digest,
+    );
+    writer.seek(SeekFrom::Current(
+        -((header.size() as u64 + body_len) as i64),
+    ))?;
+    header.write(&mut writer)?;
+    writer.seek(SeekFrom::Current(body_len as i64))?;
+
+    Ok(vk)
+}
+
+/// Looks up the QAP polynomial of the `i`th variable, where inputs precede auxiliary
+/// variables.
+fn qap_poly<'a, Scalar: PrimeField>(
+    inputs: &'a [Vec<(Scalar, usize)>],
+    aux: &'a [Vec<(Scalar, usize)>],
+    i: usize,
+) -> &'a [(Scalar, usize)] {
+    if i < inputs.len() {
+        &inputs[i]
+    } else {
+        &aux[i - inputs.len()]
+    }
+}
+
+/// Exponentiates the base of `wnaf` by each of the `exps`.
+fn eval_query<G>(
+    worker: &Worker,
+    wnaf: &Wnaf<usize, &[G], &mut Vec<i64>>,
+    exps: &[G::Scalar],
+) -> Vec<G::Affine>
+where
+    G: PrimeCurve + WnafGroup,
+{
+    let mut query = vec![G::identity(); exps.len()];
+    worker.scope(query.len(), |scope, chunk| {
+        for (query, exps) in query.chunks_mut(chunk).zip(exps.chunks(chunk)) {
+            let mut wnaf = wnaf.shared();
+
+            scope.execute(move || {
+                for (q, exp) in query.iter_mut().zip(exps.iter()) {
+                    if !bool::from(exp.is_zero()) {
+                        *q = wnaf.scalar(exp);
+                    }
+                }
+            });
+        }
+    });
+
+    let mut query_affine = vec![G::Affine::identity(); query.len()];
+    G::batch_normalize(&query, &mut query_affine);
+    query_affine
+}
+
+/// Writes a query in the layout of `Parameters::write`, computing the exponents for `range`
+/// in chunks via `exps`. Elements with a zero exponent are omitted.
+fn write_query<G, W, F>(
+    writer: &mut W,
+    worker: &Worker,
+    wnaf: &Wnaf<usize, &[G], &mut Vec<i64>>,
+    range: Range<usize>,
+    chunk_size: usize,
+    exps: F,
+) -> io::Result<()>
+where
+    G: PrimeCurve + WnafGroup,
+    G::Affine: UncompressedEncoding,
+    W: Write,
+    F: Fn(Range<usize>) -> Vec<G::Scalar> + Sync,
+{
+    let chunks = range
+        .clone()
+        .step_by(chunk_size)
+        .map(|start| start..min(start + chunk_size, range.end))
+        .collect::<Vec<_>>();
+
+    // The length prefix must be known upfront, so count the non-zero exponents first.
+    let len: usize = chunks
+        .par_iter()
+        .map(|chunk| {
+            exps(chunk.clone())
+                .iter()
+                .filter(|e| !bool::from(e.is_zero()))
+                .count()
+        })
+        .sum();
+    writer.write_u32::<BigEndian>(len as u32)?;
+
+    for chunk in chunks {
+        for g in eval_query(worker, wnaf, &exps(chunk)) {
+            if !bool::from(g.is_identity()) {
+                writer.write_all(g.to_uncompressed().as_ref())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Evaluates the QAP polynomial `p` at tau, given the Lagrange coefficients for tau.
+fn eval_at_tau<Scalar: PrimeField>(powers_of_tau: &[Scalar], p: &[(Scalar, usize)]) -> Scalar {
+    let mut acc = Scalar::zero();
+
+    for &(ref coeff, index) in p {
+        let mut n = powers_of_tau[index];
+        n.mul_assign(coeff);
+        acc.add_assign(&n);
+    }
+
+    acc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{create_random_proof, prepare_verifying_key, verify_proof};
+    use crate::synthesize_parallel;
+    use blstrs::{Bls12, G1Projective, G2Projective, Scalar as Fr};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct MulAddCircuit {
+        a: Option<Fr>,
+        b: Option<Fr>,
+    }
+
+    // a * b + a = out
+    impl Circuit<Fr> for MulAddCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c_val = self.a.and_then(|a| self.b.map(|b| a * b));
+            let c = cs.alloc(|| "c", || c_val.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce(|| "a * b = c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+            let out = cs.alloc_input(
+                || "out",
+                || {
+                    c_val
+                        .map(|c| c + self.a.unwrap())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(
+                || "c + a = out",
+                |lc| lc + c + a,
+                |lc| lc + CS::one(),
+                |lc| lc + out,
+            );
+
+            Ok(())
+        }
+    }
+
+    // Independent instances of `MulAddCircuit`.
+    struct ManyMulAdd {
+        parts: Vec<MulAddCircuit>,
+        parallel: bool,
+    }
+
+    impl Circuit<Fr> for ManyMulAdd {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            if self.parallel {
+                return synthesize_parallel(cs, self.parts);
+            }
+
+            for (i, part) in self.parts.into_iter().enumerate() {
+                part.synthesize(&mut cs.namespace(|| format!("part {}", i)))?;
+            }
+
+            Ok(())
+        }
+    }
+
+    // Extends the assembly with one it didn't create, so the constraints of the part aren't
+    // recorded.
+    struct UnrecordedPart(MulAddCircuit);
+
+    impl Circuit<Fr> for UnrecordedPart {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut part = CS::Root::new();
+            part.alloc_input(|| "ONE", || Ok(Fr::one()))?;
+            self.0.synthesize(&mut part)?;
+            cs.get_root().extend(part);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_generate_parameters_parallel() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let values = (0..5)
+            .map(|_| (Fr::random(&mut *rng), Fr::random(&mut *rng)))
+            .collect::<Vec<_>>();
+        let outputs = values.iter().map(|(a, b)| a * b + a).collect::<Vec<_>>();
+        let circuit = |parallel: bool, witness: bool| ManyMulAdd {
+            parts: values
+                .iter()
+                .map(|&(a, b)| MulAddCircuit {
+                    a: Some(a).filter(|_| witness),
+                    b: Some(b).filter(|_| witness),
+                })
+                .collect(),
+            parallel,
+        };
+
+        let g1 = G1Projective::random(&mut *rng);
+        let g2 = G2Projective::random(&mut *rng);
+        let alpha = Fr::random(&mut *rng);
+        let beta = Fr::random(&mut *rng);
+        let gamma = Fr::random(&mut *rng);
+        let delta = Fr::random(&mut *rng);
+        let tau = Fr::random(&mut *rng);
+        let generate = |circuit| {
+            generate_parameters::<Bls12, _>(circuit, g1, g2, alpha, beta, gamma, delta, tau)
+                .unwrap()
+        };
+
+        let params = generate(circuit(true, false));
+        let expected = generate(circuit(false, false));
+        assert!(params.vk == expected.vk);
+        assert_eq!(params.h, expected.h);
+        assert_eq!(params.l, expected.l);
+        assert_eq!(params.a, expected.a);
+        assert_eq!(params.b_g1, expected.b_g1);
+        assert_eq!(params.b_g2, expected.b_g2);
+        assert!(params.circuit_digest.is_some());
+        assert_eq!(params.circuit_digest, expected.circuit_digest);
+        assert!(matches!(
+            synthesize_assembly::<Fr, _>(UnrecordedPart(MulAddCircuit { a: None, b: None })),
+            Err(SynthesisError::CircuitDigestUnavailable)
+        ));
+
+        let pvk = prepare_verifying_key(&params.vk);
+        for parallel in &[true, false] {
+            let proof = create_random_proof(circuit(*parallel, true), &params, rng).unwrap();
+            assert!(verify_proof(&pvk, &proof, &outputs).unwrap());
+        }
+        let proof = create_random_proof(circuit(true, true), &expected, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &outputs).unwrap());
+        let mut swapped = outputs.clone();
+        swapped.swap(0, 1);
+        assert!(!verify_proof(&pvk, &proof, &swapped).unwrap());
+    }
+
+    #[test]
+    fn test_generate_parameters_to_writer() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let g1 = G1Projective::random(&mut *rng);
+        let g2 = G2Projective::random(&mut *rng);
+        let alpha = Fr::random(&mut *rng);
+        let beta = Fr::random(&mut *rng);
+        let gamma = Fr::random(&mut *rng);
+        let delta = Fr::random(&mut *rng);
+        let tau = Fr::random(&mut *rng);
+
+        let circuit = MulAddCircuit { a: None, b: None };
+        let params = generate_parameters::<Bls12, _>(
+            circuit.clone(),
+            g1,
+            g2,
+            alpha,
+            beta,
+            gamma,
+            delta,
+            tau,
+        )
+        .unwrap();
+        assert!(params.circuit_digest.is_some());
+        let mut expected = vec![];
+        params.write_with_header(&mut expected).unwrap();
+
+        // Small chunks to exercise the chunking of all queries.
+        for chunk_size in &[1, 2, 3, WRITER_CHUNK_SIZE] {
+            let mut bytes = io::Cursor::new(vec![]);
+            let vk = generate_parameters_to_writer_chunked::<Bls12, _, _>(
+                circuit.clone(),
+                g1,
+                g2,
+                alpha,
+                beta,
+                gamma,
+                delta,
+                tau,
+                &mut bytes,
+                *chunk_size,
+            )
+            .unwrap();
+            assert!(vk == params.vk);
+            assert_eq!(bytes.position(), expected.len() as u64);
+            assert_eq!(bytes.into_inner(), expected);
+        }
+
+        // The written parameters can be used for proving right away.
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let vk = generate_random_parameters_to_writer::<Bls12, _, _, _>(circuit, rng, &mut file)
+            .unwrap();
+        let mapped_params =
+            Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), true).unwrap();
+        assert!(mapped_params.vk == vk);
+        assert_eq!(mapped_params.circuit_digest, params.circuit_digest);
+
+        let pvk = prepare_verifying_key(&vk);
+        let (a, b) = (Fr::random(&mut *rng), Fr::random(&mut *rng));
+        let circuit = MulAddCircuit {
+            a: Some(a),
+            b: Some(b),
+        };
+        let proof = create_random_proof(circuit, &mapped_params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[a * b + a]).unwrap());
+    }
+}
@@ -0,0 +1,818 @@
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use blake2s_simd::State as Blake2s;
+use blstrs::Bls12;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+
+use crate::util_cs::shape_cs::CircuitDigest;
+
+/// Magic bytes at the start of a parameter file with a [`ParameterHeader`].
+///
+/// A legacy parameter file starts with an uncompressed G1 point, whose first byte never has
+/// the most significant bit set, so the two can't be confused.
+pub const PARAMETERS_MAGIC: [u8; 8] = *b"\x89BELLPRM";
+
+/// Version of the parameter file format described by [`ParameterHeader`].
+pub const PARAMETERS_VERSION: u32 = 1;
+
+/// Header flag marking parameter files whose points are compressed.
+pub const FLAG_COMPRESSED: u32 = 1;
+
+/// Header flag marking parameter files which record the [`CircuitDigest`] of their circuit.
+pub const FLAG_CIRCUIT_DIGEST: u32 = 2;
+
+/// Number of sections of a parameter file: the verifying key, followed by the h, l, a, b_g1
+/// and b_g2 queries.
+pub const NUM_PARAMETER_SECTIONS: usize = 6;
+
+/// Encoding of the curve points of a parameter file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointEncoding {
+    /// The [`UncompressedEncoding`](group::UncompressedEncoding) of points, which is what
+    /// legacy parameter files use. Fast to load, but twice as large.
+    Uncompressed,
+    /// The compressed [`GroupEncoding`](group::GroupEncoding) of points. Decompression makes
+    /// loading more expensive, and is done in parallel.
+    Compressed,
+}
+
+impl PointEncoding {
+    /// The flags of a [`ParameterHeader`] for this encoding.
+    pub fn flags(self) -> u32 {
+        match self {
+            PointEncoding::Uncompressed => 0,
+            PointEncoding::Compressed => FLAG_COMPRESSED,
+        }
+    }
+
+    /// The size of an encoded point in bytes.
+    pub fn point_size<G: PrimeCurveAffine + UncompressedEncoding>(self) -> usize {
+        match self {
+            PointEncoding::Uncompressed => G::Uncompressed::default().as_ref().len(),
+            PointEncoding::Compressed => G::Repr::default().as_ref().len(),
+        }
+    }
+
+    /// Encodes a point.
+    pub(crate) fn write_point<G, W>(self, point: &G, mut writer: W) -> io::Result<()>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+        W: Write,
+    {
+        match self {
+            PointEncoding::Uncompressed => writer.write_all(point.to_uncompressed().as_ref()),
+            PointEncoding::Compressed => writer.write_all(point.to_bytes().as_ref()),
+        }
+    }
+
+    /// Decodes a point from exactly [`point_size`](Self::point_size) bytes. Points at
+    /// infinity are rejected, as parameters never contain them.
+    ///
+    /// The subgroup check, which dominates decoding, is skipped unless `checked` is set.
+    pub(crate) fn read_point<G>(self, bytes: &[u8], checked: bool) -> io::Result<G>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+    {
+        let affine_opt: Option<G> = match self {
+            PointEncoding::Uncompressed => {
+                let mut repr = G::Uncompressed::default();
+                repr.as_mut().copy_from_slice(bytes);
+                if checked {
+                    G::from_uncompressed(&repr).into()
+                } else {
+                    G::from_uncompressed_unchecked(&repr).into()
+                }
+            }
+            PointEncoding::Compressed => {
+                let mut repr = G::Repr::default();
+                repr.as_mut().copy_from_slice(bytes);
+                if checked {
+                    G::from_bytes(&repr).into()
+                } else {
+                    G::from_bytes_unchecked(&repr).into()
+                }
+            }
+        };
+        let affine =
+            affine_opt.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))?;
+
+        if affine.is_identity().into() {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "point at infinity",
+            ))
+        } else {
+            Ok(affine)
+        }
+    }
+}
+
+/// Identifies the curve of an [`Engine`](pairing::Engine) in parameter files and in the
+/// human readable representation of [`json`](super::json).
+pub trait CurveName {
+    const CURVE_NAME: &'static str;
+}
+
+impl CurveName for Bls12 {
+    const CURVE_NAME: &'static str = "bls12-381";
+}
+
+/// Header of a self-describing parameter file, as written by
+/// [`Parameters::write_with_header`](super::Parameters::write_with_header).
+///
+/// The header is followed by the body, which is exactly what
+/// [`Parameters::write`](super::Parameters::write) produces, with points compressed if the
+/// header says so. All integers are big-endian.
+///
+/// - magic bytes [`PARAMETERS_MAGIC`]
+/// - format version as `u32`
+/// - flags as `u32`: [`FLAG_COMPRESSED`] if the points of the body are compressed, see
+///   [`PointEncoding`]
+/// - length of the curve name as `u32`, followed by the name in UTF-8
+/// - BLAKE2s-256 digest of the body
+/// - only if [`FLAG_CIRCUIT_DIGEST`] is set: the [`CircuitDigest`] of the circuit the
+///   parameters were generated for
+/// - offset and length of each section as `u64`, relative to the start of the file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParameterHeader {
+    pub version: u32,
+    pub flags: u32,
+    pub curve: String,
+    pub digest: [u8; 32],
+    pub circuit_digest: Option<CircuitDigest>,
+    pub sections: [Range<u64>; NUM_PARAMETER_SECTIONS],
+}
+
+impl ParameterHeader {
+    /// Creates the header for a body with the given section lengths and digest, for the
+    /// curve of `E`, recording `circuit_digest` if there is one.
+    pub(crate) fn new<E: CurveName>(
+        section_lengths: [u64; NUM_PARAMETER_SECTIONS],
+        digest: [u8; 32],
+        encoding: PointEncoding,
+        circuit_digest: Option<CircuitDigest>,
+    ) -> Self {
+        let mut flags = encoding.flags();
+        if circuit_digest.is_some() {
+            flags |= FLAG_CIRCUIT_DIGEST;
+        }
+
+        let mut header = ParameterHeader {
+            version: PARAMETERS_VERSION,
+            flags,
+            curve: E::CURVE_NAME.to_string(),
+            digest,
+            circuit_digest,
+            sections: Default::default(),
+        };
+
+        let mut offset = header.size() as u64;
+        for (section, len) in header.sections.iter_mut().zip(section_lengths.iter()) {
+            *section = offset..offset + len;
+            offset += len;
+        }
+
+        header
+    }
+
+    /// The size of the encoded header in bytes, which is also the offset of the body.
+    pub fn size(&self) -> usize {
+        let circuit_digest_len = if self.circuit_digest.is_some() { 32 } else { 0 };
+
+        PARAMETERS_MAGIC.len()
+            + 4
+            + 4
+            + 4
+            + self.curve.len()
+            + 32
+            + circuit_digest_len
+            + NUM_PARAMETER_SECTIONS * 16
+    }
+
+    /// The encoding of the points in the body.
+    pub fn encoding(&self) -> PointEncoding {
+        if self.flags & FLAG_COMPRESSED != 0 {
+            PointEncoding::Compressed
+        } else {
+            PointEncoding::Uncompressed
+        }
+    }
+
+    /// The range of the body within the file.
+    pub fn body(&self) -> Range<u64> {
+        self.sections[0].start..self.sections[NUM_PARAMETER_SECTIONS - 1].end
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&PARAMETERS_MAGIC)?;
+        writer.write_u32::<BigEndian>(self.version)?;
+        writer.write_u32::<BigEndian>(self.flags)?;
+        writer.write_u32::<BigEndian>(self.curve.len() as u32)?;
+        writer.write_all(self.curve.as_bytes())?;
+        writer.write_all(&self.digest)?;
+        if let Some(circuit_digest) = &self.circuit_digest {
+            writer.write_all(&circuit_digest.0)?;
+        }
+        for section in &self.sections {
+            writer.write_u64::<BigEndian>(section.start)?;
+            writer.write_u64::<BigEndian>(section.end - section.start)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a header, including the magic bytes.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != PARAMETERS_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a parameter file header",
+            ));
+        }
+
+        Self::read_after_magic(reader)
+    }
+
+    /// Reads a header whose magic bytes have already been consumed.
+    pub(crate) fn read_after_magic<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u32::<BigEndian>()?;
+        let flags = reader.read_u32::<BigEndian>()?;
+
+        let curve_len = reader.read_u32::<BigEndian>()? as usize;
+        // Curve names are short, don't allocate whatever a corrupted length says.
+        if curve_len > 64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid curve name",
+            ));
+        }
+        let mut curve = vec![0u8; curve_len];
+        reader.read_exact(&mut curve)?;
+        let curve = String::from_utf8(curve)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid curve name"))?;
+
+        let mut digest = [0u8; 32];
+        reader.read_exact(&mut digest)?;
+
+        let circuit_digest = if flags & FLAG_CIRCUIT_DIGEST != 0 {
+            let mut circuit_digest = [0u8; 32];
+            reader.read_exact(&mut circuit_digest)?;
+            Some(CircuitDigest(circuit_digest))
+        } else {
+            None
+        };
+
+        let mut sections: [Range<u64>; NUM_PARAMETER_SECTIONS] = Default::default();
+        for section in sections.iter_mut() {
+            let offset = reader.read_u64::<BigEndian>()?;
+            let len = reader.read_u64::<BigEndian>()?;
+            let end = offset.checked_add(len).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid section length")
+            })?;
+            *section = offset..end;
+        }
+
+        Ok(ParameterHeader {
+            version,
+            flags,
+            curve,
+            digest,
+            circuit_digest,
+            sections,
+        })
+    }
+
+    /// Reads the header at the start of `bytes`, if there is one.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> io::Result<Option<Self>> {
+        if !bytes.starts_with(&PARAMETERS_MAGIC) {
+            return Ok(None);
+        }
+
+        Self::read_after_magic(&bytes[PARAMETERS_MAGIC.len()..]).map(Some)
+    }
+
+    /// Checks that the header describes parameters for the curve of `E` in a version of the
+    /// format which is supported, see [`validate_format`](Self::validate_format).
+    pub fn validate<E: CurveName>(&self, file_len: Option<u64>) -> io::Result<()> {
+        if self.curve != E::CURVE_NAME {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "parameters are for curve {}, expected {}",
+                    self.curve,
+                    E::CURVE_NAME
+                ),
+            ));
+        }
+
+        self.validate_format(file_len)
+    }
+
+    /// Checks that the header is in a version of the format which is supported, and that the
+    /// sections directly follow each other. If `file_len` is given, also checks that the
+    /// body extends exactly to the end of the file.
+    ///
+    /// The curve isn't checked, so that parameters of engines which don't implement
+    /// [`CurveName`] can be read. Use [`validate`](Self::validate) where the engine is known
+    /// to implement it.
+    pub fn validate_format(&self, file_len: Option<u64>) -> io::Result<()> {
+        let invalid = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+
+        if self.version != PARAMETERS_VERSION {
+            return invalid(format!(
+                "unsupported parameter file version {}",
+                self.version
+            ));
+        }
+        if self.flags & !(FLAG_COMPRESSED | FLAG_CIRCUIT_DIGEST) != 0 {
+            return invalid(format!(
+                "unsupported parameter file flags {:#x}",
+                self.flags
+            ));
+        }
+        if (self.flags & FLAG_CIRCUIT_DIGEST != 0) != self.circuit_digest.is_some() {
+            return invalid("circuit digest doesn't match the header flags".to_string());
+        }
+
+        let mut offset = self.size() as u64;
+        for section in &self.sections {
+            if section.start != offset {
+                return invalid("invalid parameter file sections".to_string());
+            }
+            offset = section.end;
+        }
+
+        match file_len {
+            Some(len) if len < offset => invalid("truncated parameter file".to_string()),
+            Some(len) if len > offset => invalid("trailing data in parameter file".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks that `offset` is the end of the section with the given index.
+    pub(crate) fn check_section_end(&self, section: usize, offset: u64) -> io::Result<()> {
+        if self.sections[section].end != offset {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "parameter file section doesn't match the header",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the digest of the body against the header.
+    pub(crate) fn check_digest(&self, digest: &[u8; 32]) -> io::Result<()> {
+        if &self.digest != digest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "parameter file digest mismatch",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Hashes the body of a parameter file.
+pub(crate) fn body_digest(body: &[u8]) -> [u8; 32] {
+    let mut h = Blake2s::new();
+    h.update(body);
+    finalize(h)
+}
+
+fn finalize(h: Blake2s) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(h.finalize().as_bytes());
+    out
+}
+
+/// Adapter which hashes and counts everything read through it.
+pub(crate) struct HashReader<R> {
+    reader: R,
+    hasher: Blake2s,
+    count: u64,
+}
+
+impl<R: Read> HashReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        HashReader {
+            reader,
+            hasher: Blake2s::new(),
+            count: 0,
+        }
+    }
+
+    /// Returns the number of bytes read and their digest.
+    pub(crate) fn finalize(self) -> (u64, [u8; 32]) {
+        (self.count, finalize(self.hasher))
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.reader.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Adapter which hashes everything written to it.
+pub(crate) struct HashWriter(Blake2s);
+
+impl HashWriter {
+    pub(crate) fn new() -> Self {
+        HashWriter(Blake2s::new())
+    }
+
+    pub(crate) fn finalize(self) -> [u8; 32] {
+        finalize(self.0)
+    }
+}
+
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Adapter which hashes and counts everything written through it to the inner writer.
+pub(crate) struct HashingWriter<W> {
+    writer: W,
+    hasher: Blake2s,
+    count: u64,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        HashingWriter {
+            writer,
+            hasher: Blake2s::new(),
+            count: 0,
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the inner writer, the number of bytes written and their digest.
+    pub(crate) fn finalize(self) -> (W, u64, [u8; 32]) {
+        (self.writer, self.count, finalize(self.hasher))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.writer.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        self.count += len as u64;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use crate::groth16::{
+        convert_parameters, create_random_proof, generate_random_parameters, prepare_verifying_key,
+        verify_proof, Parameters,
+    };
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+    use memmap::MmapOptions;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct CubeCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for CubeCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let x2 = cs.alloc(
+                || "x^2",
+                || {
+                    self.x
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    self.x
+                        .map(|x| x.square() * x)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = x^2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x^2 * x = y", |lc| lc + x2, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    // Not the circuit of the parameters generated for `CubeCircuit`.
+    struct SquareCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for SquareCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    self.x
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    fn write_file(bytes: &[u8]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(bytes).unwrap();
+        file.flush().unwrap();
+        file
+    }
+
+    fn read_mmap(bytes: &[u8]) -> io::Result<Parameters<Bls12>> {
+        let file = write_file(bytes);
+        let mmap = unsafe { MmapOptions::new().map(file.as_file()).unwrap() };
+        Parameters::read_mmap(&mmap, true)
+    }
+
+    fn build_mapped(bytes: &[u8], checked: bool) -> io::Result<()> {
+        let file = write_file(bytes);
+        Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), checked).map(|_| ())
+    }
+
+    #[test]
+    fn test_parameters_with_header() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+
+        let mut legacy = vec![];
+        params.write(&mut legacy).unwrap();
+        let mut bytes = vec![];
+        params.write_with_header(&mut bytes).unwrap();
+
+        let header = ParameterHeader::read(&bytes[..]).unwrap();
+        header.validate::<Bls12>(Some(bytes.len() as u64)).unwrap();
+        assert_eq!(header.curve, "bls12-381");
+        assert_eq!(header.digest, body_digest(&legacy));
+        assert_eq!(&bytes[header.size()..], &legacy[..]);
+
+        // Both formats are understood by all readers.
+        for bytes in &[&bytes, &legacy] {
+            assert!(Parameters::<Bls12>::read(&bytes[..], true).unwrap() == params);
+            assert!(read_mmap(bytes).unwrap() == params);
+        }
+
+        let file = write_file(&bytes);
+        let mapped =
+            Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), true).unwrap();
+        let x = Fr::random(&mut *rng);
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, &mapped, rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+    }
+
+    #[test]
+    fn test_compressed_parameters() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+
+        let mut uncompressed = vec![];
+        params.write_with_header(&mut uncompressed).unwrap();
+        let mut compressed = vec![];
+        params.write_compressed(&mut compressed).unwrap();
+
+        let header = ParameterHeader::read(&compressed[..]).unwrap();
+        header
+            .validate::<Bls12>(Some(compressed.len() as u64))
+            .unwrap();
+        assert_eq!(header.flags, FLAG_COMPRESSED | FLAG_CIRCUIT_DIGEST);
+        assert_eq!(header.encoding(), PointEncoding::Compressed);
+        let body = header.body();
+        let uncompressed_body = ParameterHeader::read(&uncompressed[..]).unwrap().body();
+        // Everything but the length prefixes of the sections is halved.
+        let prefixes = 4 * NUM_PARAMETER_SECTIONS as u64;
+        assert_eq!(
+            2 * (body.end - body.start - prefixes),
+            uncompressed_body.end - uncompressed_body.start - prefixes
+        );
+
+        for &checked in &[true, false] {
+            assert!(Parameters::<Bls12>::read(&compressed[..], checked).unwrap() == params);
+        }
+        assert!(read_mmap(&compressed).unwrap() == params);
+
+        let file = write_file(&compressed);
+        let mapped =
+            Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), true).unwrap();
+        assert_eq!(mapped.encoding, PointEncoding::Compressed);
+        let x = Fr::random(&mut *rng);
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, &mapped, rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+
+        // Conversion works in both directions, and from legacy files.
+        let mut converted = vec![];
+        convert_parameters::<Bls12, _, _>(
+            &compressed[..],
+            &mut converted,
+            PointEncoding::Uncompressed,
+            true,
+        )
+        .unwrap();
+        assert_eq!(converted, uncompressed);
+
+        // Legacy files don't record the circuit digest.
+        let mut legacy = vec![];
+        params.write(&mut legacy).unwrap();
+        let mut converted = vec![];
+        convert_parameters::<Bls12, _, _>(
+            &legacy[..],
+            &mut converted,
+            PointEncoding::Compressed,
+            false,
+        )
+        .unwrap();
+        let mut without_digest = params.clone();
+        without_digest.circuit_digest = None;
+        let mut compressed_without_digest = vec![];
+        without_digest
+            .write_compressed(&mut compressed_without_digest)
+            .unwrap();
+        assert_eq!(converted, compressed_without_digest);
+
+        // Corrupted points are detected when reading.
+        let mut corrupted = compressed.clone();
+        let h_start = header.sections[1].start as usize + 4;
+        corrupted[h_start + 10] ^= 0xff;
+        assert!(Parameters::<Bls12>::read(&corrupted[..], true).is_err());
+        assert!(read_mmap(&corrupted).is_err());
+    }
+
+    #[test]
+    fn test_parameters_header_invalid() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+        let mut bytes = vec![];
+        params.write_with_header(&mut bytes).unwrap();
+        let header = ParameterHeader::read(&bytes[..]).unwrap();
+
+        // Corrupted body: only detected by the digest, which the lazy loader checks only
+        // if asked to.
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(Parameters::<Bls12>::read(&corrupted[..], false).is_err());
+        assert!(read_mmap(&corrupted).is_err());
+        assert!(build_mapped(&corrupted, true).is_err());
+        assert!(build_mapped(&corrupted, false).is_ok());
+
+        // Truncated or extended files
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(Parameters::<Bls12>::read(truncated, false).is_err());
+        assert!(read_mmap(truncated).is_err());
+        assert!(build_mapped(truncated, false).is_err());
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(read_mmap(&extended).is_err());
+        assert!(build_mapped(&extended, false).is_err());
+
+        // Other curve
+        let mut other_curve = header.clone();
+        other_curve.curve = "bn254".to_string();
+        assert!(other_curve.validate::<Bls12>(None).is_err());
+        let mut other = vec![];
+        other_curve.write(&mut other).unwrap();
+        other.extend_from_slice(&bytes[header.size()..]);
+        assert!(Parameters::<Bls12>::read(&other[..], false).is_err());
+        assert!(build_mapped(&other, false).is_err());
+
+        // Unsupported version
+        let mut other_version = bytes.clone();
+        other_version[11] = 2;
+        assert!(Parameters::<Bls12>::read(&other_version[..], false).is_err());
+        assert!(read_mmap(&other_version).is_err());
+
+        // Sections which don't match the body
+        let mut sections = header;
+        sections.sections[1].end -= 1;
+        sections.sections[2].start -= 1;
+        let mut other = vec![];
+        sections.write(&mut other).unwrap();
+        other.extend_from_slice(&bytes[sections.size()..]);
+        assert!(build_mapped(&other, false).is_err());
+    }
+
+    #[test]
+    fn test_parameters_circuit_digest() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(CubeCircuit { x: None }, rng).unwrap();
+        let digest = CircuitDigest::of_circuit(CubeCircuit { x: None }).unwrap();
+        assert_eq!(params.circuit_digest, Some(digest));
+
+        let mut bytes = vec![];
+        params.write_with_header(&mut bytes).unwrap();
+        let header = ParameterHeader::read(&bytes[..]).unwrap();
+        header.validate::<Bls12>(Some(bytes.len() as u64)).unwrap();
+        assert_eq!(header.circuit_digest, Some(digest));
+
+        let read = Parameters::<Bls12>::read(&bytes[..], false).unwrap();
+        assert_eq!(read.circuit_digest, Some(digest));
+        assert_eq!(read_mmap(&bytes).unwrap().circuit_digest, Some(digest));
+        let file = write_file(&bytes);
+        let mapped =
+            Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), false).unwrap();
+        assert_eq!(mapped.circuit_digest, Some(digest));
+
+        let mut legacy = vec![];
+        params.write(&mut legacy).unwrap();
+        assert_eq!(
+            Parameters::<Bls12>::read(&legacy[..], false)
+                .unwrap()
+                .circuit_digest,
+            None
+        );
+
+        // The digest must match its flag.
+        let mut without_flag = header.clone();
+        without_flag.flags &= !FLAG_CIRCUIT_DIGEST;
+        assert!(without_flag.validate::<Bls12>(None).is_err());
+
+        // The circuit of the parameters can be proven, any other circuit is rejected before
+        // the proof is computed.
+        let x = Fr::random(&mut *rng);
+        let pvk = prepare_verifying_key(&params.vk);
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, &read, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, &mapped, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+
+        let expected = digest;
+        let actual = CircuitDigest::of_circuit(SquareCircuit { x: None }).unwrap();
+        assert_ne!(actual, expected);
+        for result in &[
+            create_random_proof(SquareCircuit { x: Some(x) }, &read, rng),
+            create_random_proof(SquareCircuit { x: Some(x) }, &mapped, rng),
+        ] {
+            match result {
+                Err(SynthesisError::CircuitDigestMismatch {
+                    expected: e,
+                    actual: a,
+                }) => assert_eq!((*e, *a), (expected, actual)),
+                _ => panic!("mismatching circuit wasn't rejected"),
+            }
+        }
+    }
+}
@@ -0,0 +1,489 @@
+//! A human readable representation of verifying keys, proofs and aggregate proofs.
+//!
+//! The types in this module mirror [`VerifyingKey`], [`Proof`] and [`AggregateProof`] with
+//! named fields, and can be serialized with any serde format, usually JSON. Group elements
+//! are hex encoded in the same compressed form as in the binary formats of this crate, i.e.
+//! the [`GroupEncoding`] of affine points and the [`Compress`] encoding of target group
+//! elements. Every object carries the name of its curve and the version of the
+//! representation, which are checked on conversion back.
+//!
+//! A proof over BLS12-381 looks like
+//!
+//! ```json
+//! {
+//!   "curve": "bls12-381",
+//!   "version": 1,
+//!   "a": "<48 bytes of hex>",
+//!   "b": "<96 bytes of hex>",
+//!   "c": "<48 bytes of hex>"
+//! }
+//! ```
+//!
+//! A verifying key has the fields `alpha_g1`, `beta_g1`, `beta_g2`, `gamma_g2`, `delta_g1`,
+//! `delta_g2` and `ic`, a list of G1 points. Aggregate proofs follow the structure of
+//! [`AggregateProof`], with tuples represented as lists.
+
+use std::convert::TryFrom;
+use std::io;
+
+use blstrs::Compress;
+use group::{prime::PrimeCurveAffine, Curve, GroupEncoding};
+use pairing::{Engine, MultiMillerLoop};
+use serde::{Deserialize, Serialize};
+
+use super::aggregate::{AggregateProof, GipaProof, TippMippProof};
+use super::{CurveName, Proof, VerifyingKey};
+
+/// Version of the representation written by this module.
+pub const JSON_VERSION: u32 = 1;
+
+/// The human readable representation of a [`Proof`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofJson {
+    pub curve: String,
+    pub version: u32,
+    pub a: String,
+    pub b: String,
+    pub c: String,
+}
+
+impl<E: Engine + CurveName> From<&Proof<E>> for ProofJson {
+    fn from(proof: &Proof<E>) -> Self {
+        ProofJson {
+            curve: E::CURVE_NAME.to_string(),
+            version: JSON_VERSION,
+            a: encode_point(&proof.a),
+            b: encode_point(&proof.b),
+            c: encode_point(&proof.c),
+        }
+    }
+}
+
+impl<E: Engine + CurveName> TryFrom<ProofJson> for Proof<E> {
+    type Error = io::Error;
+
+    fn try_from(json: ProofJson) -> io::Result<Self> {
+        check_header::<E>(&json.curve, json.version)?;
+
+        Ok(Proof {
+            a: decode_point(&json.a)?,
+            b: decode_point(&json.b)?,
+            c: decode_point(&json.c)?,
+        })
+    }
+}
+
+/// The human readable representation of a [`VerifyingKey`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyingKeyJson {
+    pub curve: String,
+    pub version: u32,
+    pub alpha_g1: String,
+    pub beta_g1: String,
+    pub beta_g2: String,
+    pub gamma_g2: String,
+    pub delta_g1: String,
+    pub delta_g2: String,
+    pub ic: Vec<String>,
+}
+
+impl<E: MultiMillerLoop + CurveName> From<&VerifyingKey<E>> for VerifyingKeyJson {
+    fn from(vk: &VerifyingKey<E>) -> Self {
+        VerifyingKeyJson {
+            curve: E::CURVE_NAME.to_string(),
+            version: JSON_VERSION,
+            alpha_g1: encode_point(&vk.alpha_g1),
+            beta_g1: encode_point(&vk.beta_g1),
+            beta_g2: encode_point(&vk.beta_g2),
+            gamma_g2: encode_point(&vk.gamma_g2),
+            delta_g1: encode_point(&vk.delta_g1),
+            delta_g2: encode_point(&vk.delta_g2),
+            ic: vk.ic.iter().map(encode_point).collect(),
+        }
+    }
+}
+
+impl<E: MultiMillerLoop + CurveName> TryFrom<VerifyingKeyJson> for VerifyingKey<E> {
+    type Error = io::Error;
+
+    fn try_from(json: VerifyingKeyJson) -> io::Result<Self> {
+        check_header::<E>(&json.curve, json.version)?;
+
+        let vk: VerifyingKey<E> = VerifyingKey {
+            alpha_g1: decode_point(&json.alpha_g1)?,
+            beta_g1: decode_point(&json.beta_g1)?,
+            beta_g2: decode_point(&json.beta_g2)?,
+            gamma_g2: decode_point(&json.gamma_g2)?,
+            delta_g1: decode_point(&json.delta_g1)?,
+            delta_g2: decode_point(&json.delta_g2)?,
+            ic: json
+                .ic
+                .iter()
+                .map(|p| decode_point(p))
+                .collect::<io::Result<_>>()?,
+        };
+
+        // None of the points of a verifying key may be the identity, which would make the
+        // corresponding term of the verification equation vanish.
+        let g1_identity = [vk.alpha_g1, vk.beta_g1, vk.delta_g1]
+            .iter()
+            .chain(vk.ic.iter())
+            .any(|p| bool::from(p.is_identity()));
+        let g2_identity = [vk.beta_g2, vk.gamma_g2, vk.delta_g2]
+            .iter()
+            .any(|p| bool::from(p.is_identity()));
+        if g1_identity || g2_identity {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "point at infinity",
+            ));
+        }
+
+        Ok(vk)
+    }
+}
+
+/// The human readable representation of an [`AggregateProof`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AggregateProofJson {
+    pub curve: String,
+    pub version: u32,
+    pub com_ab: [String; 2],
+    pub com_c: [String; 2],
+    pub ip_ab: String,
+    pub agg_c: String,
+    pub tmipp: TippMippProofJson,
+}
+
+/// The human readable representation of a [`TippMippProof`], part of an
+/// [`AggregateProofJson`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TippMippProofJson {
+    pub gipa: GipaProofJson,
+    pub vkey_opening: [String; 2],
+    pub wkey_opening: [String; 2],
+}
+
+/// The human readable representation of a [`GipaProof`], part of a [`TippMippProofJson`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GipaProofJson {
+    pub nproofs: u32,
+    pub comms_ab: Vec<[[String; 2]; 2]>,
+    pub comms_c: Vec<[[String; 2]; 2]>,
+    pub z_ab: Vec<[String; 2]>,
+    pub z_c: Vec<[String; 2]>,
+    pub final_a: String,
+    pub final_b: String,
+    pub final_c: String,
+    pub final_vkey: [String; 2],
+    pub final_wkey: [String; 2],
+}
+
+impl<E> From<&AggregateProof<E>> for AggregateProofJson
+where
+    E: MultiMillerLoop + CurveName,
+    <E as Engine>::Gt: Compress,
+{
+    fn from(proof: &AggregateProof<E>) -> Self {
+        let gipa = &proof.tmipp.gipa;
+        let encode_pair = |(x, y): &(E::Gt, E::Gt)| [encode_gt(x), encode_gt(y)];
+        let encode_comms = |comms: &Vec<_>| {
+            comms
+                .iter()
+                .map(|(x, y)| [encode_pair(x), encode_pair(y)])
+                .collect()
+        };
+
+        AggregateProofJson {
+            curve: E::CURVE_NAME.to_string(),
+            version: JSON_VERSION,
+            com_ab: encode_pair(&proof.com_ab),
+            com_c: encode_pair(&proof.com_c),
+            ip_ab: encode_gt(&proof.ip_ab),
+            agg_c: encode_point(&proof.agg_c.to_affine()),
+            tmipp: TippMippProofJson {
+                gipa: GipaProofJson {
+                    nproofs: gipa.nproofs,
+                    comms_ab: encode_comms(&gipa.comms_ab),
+                    comms_c: encode_comms(&gipa.comms_c),
+                    z_ab: gipa.z_ab.iter().map(encode_pair).collect(),
+                    z_c: gipa
+                        .z_c
+                        .iter()
+                        .map(|(x, y)| [encode_point(&x.to_affine()), encode_point(&y.to_affine())])
+                        .collect(),
+                    final_a: encode_point(&gipa.final_a),
+                    final_b: encode_point(&gipa.final_b),
+                    final_c: encode_point(&gipa.final_c),
+                    final_vkey: encode_point_pair(&gipa.final_vkey),
+                    final_wkey: encode_point_pair(&gipa.final_wkey),
+                },
+                vkey_opening: encode_point_pair(&proof.tmipp.vkey_opening),
+                wkey_opening: encode_point_pair(&proof.tmipp.wkey_opening),
+            },
+        }
+    }
+}
+
+impl<E> TryFrom<AggregateProofJson> for AggregateProof<E>
+where
+    E: MultiMillerLoop + CurveName,
+    <E as Engine>::Gt: Compress,
+{
+    type Error = io::Error;
+
+    fn try_from(json: AggregateProofJson) -> io::Result<Self> {
+        check_header::<E>(&json.curve, json.version)?;
+
+        let gipa = json.tmipp.gipa;
+        let decode_pair =
+            |[x, y]: &[String; 2]| -> io::Result<_> { Ok((decode_gt(x)?, decode_gt(y)?)) };
+        let decode_comms = |comms: &[[[String; 2]; 2]]| {
+            comms
+                .iter()
+                .map(|[x, y]| Ok((decode_pair(x)?, decode_pair(y)?)))
+                .collect::<io::Result<Vec<_>>>()
+        };
+
+        let proof = AggregateProof {
+            com_ab: decode_pair(&json.com_ab)?,
+            com_c: decode_pair(&json.com_c)?,
+            ip_ab: decode_gt(&json.ip_ab)?,
+            agg_c: decode_point::<E::G1Affine>(&json.agg_c)?.to_curve(),
+            tmipp: TippMippProof {
+                gipa: GipaProof {
+                    nproofs: gipa.nproofs,
+                    comms_ab: decode_comms(&gipa.comms_ab)?,
+                    comms_c: decode_comms(&gipa.comms_c)?,
+                    z_ab: gipa
+                        .z_ab
+                        .iter()
+                        .map(decode_pair)
+                        .collect::<io::Result<_>>()?,
+                    z_c: gipa
+                        .z_c
+                        .iter()
+                        .map(|[x, y]| {
+                            Ok((
+                                decode_point::<E::G1Affine>(x)?.to_curve(),
+                                decode_point::<E::G1Affine>(y)?.to_curve(),
+                            ))
+                        })
+                        .collect::<io::Result<_>>()?,
+                    final_a: decode_point(&gipa.final_a)?,
+                    final_b: decode_point(&gipa.final_b)?,
+                    final_c: decode_point(&gipa.final_c)?,
+                    final_vkey: decode_point_pair(&gipa.final_vkey)?,
+                    final_wkey: decode_point_pair(&gipa.final_wkey)?,
+                },
+                vkey_opening: decode_point_pair(&json.tmipp.vkey_opening)?,
+                wkey_opening: decode_point_pair(&json.tmipp.wkey_opening)?,
+            },
+        };
+
+        proof
+            .parsing_check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+        Ok(proof)
+    }
+}
+
+fn check_header<E: CurveName>(curve: &str, version: u32) -> io::Result<()> {
+    if curve != E::CURVE_NAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected curve {}, got {}", E::CURVE_NAME, curve),
+        ));
+    }
+    if version != JSON_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported version {}", version),
+        ));
+    }
+
+    Ok(())
+}
+
+fn encode_point<G: GroupEncoding>(p: &G) -> String {
+    hex::encode(p.to_bytes())
+}
+
+fn encode_point_pair<G: GroupEncoding>((x, y): &(G, G)) -> [String; 2] {
+    [encode_point(x), encode_point(y)]
+}
+
+fn decode_point<G: GroupEncoding>(s: &str) -> io::Result<G> {
+    let mut repr = G::Repr::default();
+    hex::decode_to_slice(s, repr.as_mut())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    Option::from(G::from_bytes(&repr))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid point"))
+}
+
+fn decode_point_pair<G: GroupEncoding>([x, y]: &[String; 2]) -> io::Result<(G, G)> {
+    Ok((decode_point(x)?, decode_point(y)?))
+}
+
+fn encode_gt<Gt: Compress + Copy>(x: &Gt) -> String {
+    let mut bytes = Vec::new();
+    x.write_compressed(&mut bytes)
+        .expect("writing to a vector cannot fail");
+    hex::encode(bytes)
+}
+
+fn decode_gt<Gt: Compress>(s: &str) -> io::Result<Gt> {
+    let bytes = hex::decode(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut reader = &bytes[..];
+    let x = Gt::read_compressed(&mut reader)?;
+    if !reader.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "trailing bytes after target group element",
+        ));
+    }
+
+    Ok(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{create_random_proof, generate_random_parameters};
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use blstrs::{Bls12, G1Projective, G2Projective, Scalar as Fr};
+    use ff::Field;
+    use group::Group;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct SquareCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for SquareCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    self.x
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = y", |lc| lc + x, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_proof_and_vk_json_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquareCircuit { x: None }, rng).unwrap();
+        let x = Fr::random(&mut *rng);
+        let proof = create_random_proof(SquareCircuit { x: Some(x) }, &params, rng).unwrap();
+
+        let json = serde_json::to_string(&ProofJson::from(&proof)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["curve"], "bls12-381");
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["a"].as_str().unwrap().len(), 96);
+        assert_eq!(value["b"].as_str().unwrap().len(), 192);
+
+        let de_proof =
+            Proof::<Bls12>::try_from(serde_json::from_str::<ProofJson>(&json).unwrap()).unwrap();
+        assert!(de_proof == proof);
+
+        let json = serde_json::to_string_pretty(&VerifyingKeyJson::from(&params.vk)).unwrap();
+        let de_vk = VerifyingKey::<Bls12>::try_from(
+            serde_json::from_str::<VerifyingKeyJson>(&json).unwrap(),
+        )
+        .unwrap();
+        assert!(de_vk == params.vk);
+
+        // Wrong curve or version
+        let mut invalid = ProofJson::from(&proof);
+        invalid.curve = "bn254".to_string();
+        assert!(Proof::<Bls12>::try_from(invalid).is_err());
+        let mut invalid = ProofJson::from(&proof);
+        invalid.version = 2;
+        assert!(Proof::<Bls12>::try_from(invalid).is_err());
+
+        // Invalid points
+        let mut invalid = ProofJson::from(&proof);
+        invalid.a.truncate(94);
+        assert!(Proof::<Bls12>::try_from(invalid).is_err());
+        let mut invalid = VerifyingKeyJson::from(&params.vk);
+        invalid.ic[0] = encode_point(&blstrs::G1Affine::identity());
+        assert!(VerifyingKey::<Bls12>::try_from(invalid).is_err());
+        let mut invalid = VerifyingKeyJson::from(&params.vk);
+        invalid.alpha_g1 = encode_point(&blstrs::G1Affine::identity());
+        assert!(VerifyingKey::<Bls12>::try_from(invalid).is_err());
+        let mut invalid = VerifyingKeyJson::from(&params.vk);
+        invalid.delta_g2 = encode_point(&blstrs::G2Affine::identity());
+        assert!(VerifyingKey::<Bls12>::try_from(invalid).is_err());
+    }
+
+    #[test]
+    fn test_aggregate_proof_json_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let mut g1 = || G1Projective::random(&mut *rng);
+        let g1s = (0..12).map(|_| g1()).collect::<Vec<_>>();
+        let g2s = (0..5)
+            .map(|_| G2Projective::random(&mut *rng).to_affine())
+            .collect::<Vec<_>>();
+        let gts = (0..5)
+            .map(|i| Bls12::pairing(&g1s[i].to_affine(), &g2s[i]))
+            .collect::<Vec<_>>();
+
+        let proof = AggregateProof::<Bls12> {
+            com_ab: (gts[0], gts[1]),
+            com_c: (gts[2], gts[3]),
+            ip_ab: gts[4],
+            agg_c: g1s[0],
+            tmipp: TippMippProof {
+                gipa: GipaProof {
+                    nproofs: 4,
+                    comms_ab: vec![((gts[0], gts[1]), (gts[2], gts[3])); 2],
+                    comms_c: vec![((gts[3], gts[2]), (gts[1], gts[0])); 2],
+                    z_ab: vec![(gts[4], gts[0]), (gts[1], gts[4])],
+                    z_c: vec![(g1s[1], g1s[2]), (g1s[3], g1s[4])],
+                    final_a: g1s[5].to_affine(),
+                    final_b: g2s[0],
+                    final_c: g1s[6].to_affine(),
+                    final_vkey: (g2s[1], g2s[2]),
+                    final_wkey: (g1s[7].to_affine(), g1s[8].to_affine()),
+                },
+                vkey_opening: (g2s[3], g2s[4]),
+                wkey_opening: (g1s[9].to_affine(), g1s[10].to_affine()),
+            },
+        };
+
+        let json = serde_json::to_string(&AggregateProofJson::from(&proof)).unwrap();
+        let de_proof = AggregateProof::<Bls12>::try_from(
+            serde_json::from_str::<AggregateProofJson>(&json).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(de_proof, proof);
+
+        // Vectors of inconsistent length are rejected.
+        let mut invalid = AggregateProofJson::from(&proof);
+        invalid.tmipp.gipa.z_c.pop();
+        assert!(AggregateProof::<Bls12>::try_from(invalid).is_err());
+    }
+}
@@ -1,18 +1,26 @@
 use group::{prime::PrimeCurveAffine, UncompressedEncoding};
 use pairing::MultiMillerLoop;
 
+use crate::multiexp::{Source, SourceBuilder};
+use crate::util_cs::shape_cs::CircuitDigest;
 use crate::SynthesisError;
 
+use byteorder::{BigEndian, WriteBytesExt};
 use memmap::Mmap;
 use rayon::prelude::*;
 
 use std::fs::File;
-use std::io;
-use std::ops::Range;
+use std::io::{self, Write};
+use std::mem;
+use std::ops::{AddAssign, Range};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
 
-use super::{ParameterSource, PreparedVerifyingKey, VerifyingKey};
+use super::header::HashWriter;
+use super::{
+    CurveName, ParameterHeader, ParameterSource, PointEncoding, PreparedVerifyingKey,
+    QueryDensities, QuerySizes, VerifyingKey, NUM_PARAMETER_SECTIONS,
+};
 
 pub struct MappedParameters<E>
 where
@@ -22,8 +30,8 @@ where
     pub param_file_path: PathBuf,
     /// The file descriptor we have mmaped.
     pub param_file: File,
-    /// The actual mmap.
-    pub params: Mmap,
+    /// The actual mmap, shared with the sources returned by the `ParameterSource` impl.
+    pub params: Arc<Mmap>,
 
     /// This is always loaded (i.e. not lazily loaded).
     pub vk: VerifyingKey<E>,
@@ -50,39 +58,184 @@ where
     pub b_g2: Vec<Range<usize>>,
 
     pub checked: bool,
+    /// The encoding of the points in `params`.
+    pub encoding: PointEncoding,
+    /// The digest of the circuit, if the header of the file records one.
+    pub circuit_digest: Option<CircuitDigest>,
+    /// Decoded points of the queries, shared by all sources over them.
+    pub(crate) caches: QueryCaches<E>,
+}
+
+/// The [`QueryCache`] of each query of [`MappedParameters`].
+pub(crate) struct QueryCaches<E>
+where
+    E: MultiMillerLoop,
+{
+    pub(crate) h: Arc<QueryCache<E::G1Affine>>,
+    pub(crate) l: Arc<QueryCache<E::G1Affine>>,
+    pub(crate) a: Arc<QueryCache<E::G1Affine>>,
+    pub(crate) b_g1: Arc<QueryCache<E::G1Affine>>,
+    pub(crate) b_g2: Arc<QueryCache<E::G2Affine>>,
+}
+
+/// Decoded points of a query of [`MappedParameters`].
+///
+/// The query is validated once, the first time a source is built for it. Afterwards points
+/// decode without checks, in chunks which are shared by all sources currently using them,
+/// so that the windows of a multiexp decode every point only once, while only a chunk per
+/// window is held in memory.
+pub(crate) struct QueryCache<G> {
+    /// Whether all points of the query decoded successfully, checked if `checked` is set.
+    validated: Mutex<bool>,
+    /// The chunks of `SOURCE_CHUNK_SIZE` points, as long as any source holds on to them.
+    chunks: Vec<Mutex<Weak<Vec<G>>>>,
+}
+
+impl<G> QueryCache<G> {
+    /// An empty cache for a query of `len` points.
+    pub(crate) fn new(len: usize) -> Arc<Self> {
+        Arc::new(QueryCache {
+            validated: Mutex::new(false),
+            chunks: (0..len)
+                .step_by(SOURCE_CHUNK_SIZE)
+                .map(|_| Mutex::new(Weak::new()))
+                .collect(),
+        })
+    }
+}
+
+impl<E> MappedParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    pub fn query_sizes(&self) -> QuerySizes {
+        QuerySizes {
+            ic: self.vk.ic.len(),
+            h: self.h.len(),
+            l: self.l.len(),
+            a: self.a.len(),
+            b_g1: self.b_g1.len(),
+            b_g2: self.b_g2.len(),
+        }
+    }
+
+    /// Writes the subset of these parameters of the circuit with the densities `source` for
+    /// the circuit with the densities `circuit` as a parameter file with a header, see
+    /// [`Parameters::subset`]. The points are copied in the encoding of the mapped file,
+    /// without decoding them.
+    pub fn write_subset<W: Write>(
+        &self,
+        source: &QueryDensities,
+        circuit: &QueryDensities,
+        mut writer: W,
+    ) -> io::Result<()>
+    where
+        E: CurveName,
+    {
+        source
+            .check_params(&self.query_sizes(), self.circuit_digest)
+            .and_then(|_| source.check_subset(circuit))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+        let sizes = &circuit.sizes;
+
+        let mut vk_bytes = vec![];
+        match self.encoding {
+            PointEncoding::Uncompressed => self.vk.write(&mut vk_bytes)?,
+            PointEncoding::Compressed => self.vk.write_compressed(&mut vk_bytes)?,
+        }
+
+        let queries = [
+            self.query_bytes(&self.h, sizes.h),
+            self.query_bytes(&self.l, sizes.l),
+            self.query_bytes(&self.a, sizes.a),
+            self.query_bytes(&self.b_g1, sizes.b_g1),
+            self.query_bytes(&self.b_g2, sizes.b_g2),
+        ];
+        let write_body = |writer: &mut dyn Write| -> io::Result<()> {
+            writer.write_all(&vk_bytes)?;
+            for (query, len) in &queries {
+                writer.write_u32::<BigEndian>(*len as u32)?;
+                writer.write_all(query)?;
+            }
+            Ok(())
+        };
+
+        let mut hasher = HashWriter::new();
+        write_body(&mut hasher)?;
+        let mut section_lengths = [vk_bytes.len() as u64; NUM_PARAMETER_SECTIONS];
+        for (section_length, (query, _)) in section_lengths[1..].iter_mut().zip(&queries) {
+            *section_length = (mem::size_of::<u32>() + query.len()) as u64;
+        }
+
+        let header = ParameterHeader::new::<E>(
+            section_lengths,
+            hasher.finalize(),
+            self.encoding,
+            Some(circuit.circuit_digest),
+        );
+        header.write(&mut writer)?;
+        write_body(&mut writer)
+    }
+
+    /// The encoded first `len` points of a query.
+    fn query_bytes(&self, ranges: &[Range<usize>], len: usize) -> (&[u8], usize) {
+        match (ranges.first(), ranges[..len].last()) {
+            (Some(first), Some(last)) => (&self.params[first.start..last.end], len),
+            _ => (&[], 0),
+        }
+    }
+}
+
+impl<E> MappedParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// A source which lazily decodes the points at `ranges`, starting after `skip` of them.
+    /// Fails if the query contains an invalid point.
+    fn source<G>(
+        &self,
+        ranges: &[Range<usize>],
+        cache: &Arc<QueryCache<G>>,
+        skip: usize,
+    ) -> Result<MappedSource<G>, SynthesisError>
+    where
+        G: PrimeCurveAffine + UncompressedEncoding,
+    {
+        // The points of a query are stored back to back.
+        let start = ranges.first().map_or(0, |range| range.start);
+
+        let source = MappedSource {
+            mmap: self.params.clone(),
+            start,
+            len: ranges.len(),
+            encoding: self.encoding,
+            checked: self.checked,
+            cache: cache.clone(),
+            skip,
+        };
+        source.validate()?;
+
+        Ok(source)
+    }
 }
 
 impl<'a, E> ParameterSource<E> for &'a MappedParameters<E>
 where
     E: MultiMillerLoop,
 {
-    type G1Builder = (Arc<Vec<E::G1Affine>>, usize);
-    type G2Builder = (Arc<Vec<E::G2Affine>>, usize);
+    type G1Builder = MappedSource<E::G1Affine>;
+    type G2Builder = MappedSource<E::G2Affine>;
 
     fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
         Ok(&self.vk)
     }
 
     fn get_h(&self, _num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
-        let builder = self
-            .h
-            .par_iter()
-            .cloned()
-            .map(|h| read_g1::<E>(&self.params, h, self.checked))
-            .collect::<Result<_, _>>()?;
-
-        Ok((Arc::new(builder), 0))
+        self.source(&self.h, &self.caches.h, 0)
     }
 
     fn get_l(&self, _num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
-        let builder = self
-            .l
-            .par_iter()
-            .cloned()
-            .map(|l| read_g1::<E>(&self.params, l, self.checked))
-            .collect::<Result<_, _>>()?;
-
-        Ok((Arc::new(builder), 0))
+        self.source(&self.l, &self.caches.l, 0)
     }
 
     fn get_a(
@@ -90,16 +243,10 @@ where
         num_inputs: usize,
         _num_a: usize,
     ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
-        let builder = self
-            .a
-            .par_iter()
-            .cloned()
-            .map(|a| read_g1::<E>(&self.params, a, self.checked))
-            .collect::<Result<_, _>>()?;
-
-        let builder: Arc<Vec<_>> = Arc::new(builder);
-
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+        Ok((
+            self.source(&self.a, &self.caches.a, 0)?,
+            self.source(&self.a, &self.caches.a, num_inputs)?,
+        ))
     }
 
     fn get_b_g1(
@@ -107,16 +254,10 @@ where
         num_inputs: usize,
         _num_b_g1: usize,
     ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
-        let builder = self
-            .b_g1
-            .par_iter()
-            .cloned()
-            .map(|b_g1| read_g1::<E>(&self.params, b_g1, self.checked))
-            .collect::<Result<_, _>>()?;
-
-        let builder: Arc<Vec<_>> = Arc::new(builder);
-
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+        Ok((
+            self.source(&self.b_g1, &self.caches.b_g1, 0)?,
+            self.source(&self.b_g1, &self.caches.b_g1, num_inputs)?,
+        ))
     }
 
     fn get_b_g2(
@@ -124,22 +265,174 @@ where
         num_inputs: usize,
         _num_b_g2: usize,
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
-        let builder = self
-            .b_g2
-            .par_iter()
-            .cloned()
-            .map(|b_g2| read_g2::<E>(&self.params, b_g2, self.checked))
-            .collect::<Result<_, _>>()?;
+        Ok((
+            self.source(&self.b_g2, &self.caches.b_g2, 0)?,
+            self.source(&self.b_g2, &self.caches.b_g2, num_inputs)?,
+        ))
+    }
 
-        let builder: Arc<Vec<_>> = Arc::new(builder);
+    fn get_circuit_digest(&self) -> Option<CircuitDigest> {
+        self.circuit_digest
+    }
+}
 
-        Ok(((builder.clone(), 0), (builder, num_inputs)))
+/// Number of points a [`MappedSource`] decodes at once.
+const SOURCE_CHUNK_SIZE: usize = 1024;
+
+/// A [`SourceBuilder`] over a query of [`MappedParameters`], which decodes the points from
+/// the memory map on demand.
+///
+/// The CPU multiexp only ever holds a chunk of decoded points per window, instead of the
+/// whole query, see [`QueryCache`]. The GPU multiexp needs all points at once, so `get`
+/// still decodes the whole query.
+#[derive(Clone)]
+pub struct MappedSource<G> {
+    mmap: Arc<Mmap>,
+    /// Offset of the first point of the query.
+    start: usize,
+    /// Number of points in the query.
+    len: usize,
+    encoding: PointEncoding,
+    checked: bool,
+    cache: Arc<QueryCache<G>>,
+    /// Number of points the source skips initially.
+    skip: usize,
+}
+
+impl<G> MappedSource<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    /// Decodes every point of the query once, checked if `checked` is set, unless that
+    /// already happened for another source over the query. Concurrent callers wait for a
+    /// single validation.
+    fn validate(&self) -> io::Result<()> {
+        let mut validated = self.cache.validated.lock().unwrap();
+        if !*validated {
+            (0..self.cache.chunks.len())
+                .into_par_iter()
+                .try_for_each(|index| self.decode(index, self.checked).map(|_| ()))?;
+            *validated = true;
+        }
+
+        Ok(())
+    }
+
+    /// Decodes the chunk with the given index.
+    fn decode(&self, index: usize, checked: bool) -> io::Result<Vec<G>> {
+        let point_size = self.encoding.point_size::<G>();
+        let start = index * SOURCE_CHUNK_SIZE;
+        let end = (start + SOURCE_CHUNK_SIZE).min(self.len);
+        let bytes = &self.mmap[self.start + start * point_size..self.start + end * point_size];
+
+        bytes
+            .chunks(point_size)
+            .map(|bytes| self.encoding.read_point(bytes, checked))
+            .collect()
+    }
+
+    /// Returns the chunk with the given index, which is only decoded if no other source
+    /// currently holds on to it.
+    fn chunk(&self, index: usize) -> io::Result<Arc<Vec<G>>> {
+        let mut slot = self.cache.chunks[index].lock().unwrap();
+        if let Some(chunk) = slot.upgrade() {
+            return Ok(chunk);
+        }
+
+        // The points were validated when the source was built.
+        let chunk = Arc::new(self.decode(index, false)?);
+        *slot = Arc::downgrade(&chunk);
+
+        Ok(chunk)
+    }
+}
+
+impl<G> SourceBuilder<G> for MappedSource<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    type Source = MappedSourceIter<G>;
+
+    fn new(self) -> Self::Source {
+        let next = self.skip;
+        MappedSourceIter {
+            source: self,
+            next,
+            chunk: None,
+        }
+    }
+
+    fn get(self) -> (Arc<Vec<G>>, usize) {
+        let point_size = self.encoding.point_size::<G>();
+        let bytes = &self.mmap[self.start..self.start + self.len * point_size];
+        let points = bytes
+            .par_chunks(point_size)
+            .map(|bytes| self.encoding.read_point(bytes, false))
+            .collect::<io::Result<Vec<G>>>()
+            .expect("points were validated when the source was built");
+
+        (Arc::new(points), self.skip)
+    }
+}
+
+/// The [`Source`] of a [`MappedSource`].
+pub struct MappedSourceIter<G> {
+    source: MappedSource<G>,
+    /// Index of the next point.
+    next: usize,
+    /// The index of the chunk which was used last, and its points.
+    chunk: Option<(usize, Arc<Vec<G>>)>,
+}
+
+impl<G> Source<G> for MappedSourceIter<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    fn add_assign_mixed(
+        &mut self,
+        to: &mut <G as PrimeCurveAffine>::Curve,
+    ) -> Result<(), SynthesisError> {
+        if self.source.len <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        let index = self.next / SOURCE_CHUNK_SIZE;
+        if !matches!(self.chunk, Some((i, _)) if i == index) {
+            // Release the previous chunk before decoding the next one.
+            self.chunk = None;
+            self.chunk = Some((index, self.source.chunk(index)?));
+        }
+        let (_, chunk) = self.chunk.as_ref().expect("chunk was just decoded");
+
+        to.add_assign(&chunk[self.next - index * SOURCE_CHUNK_SIZE]);
+        self.next += 1;
+
+        Ok(())
+    }
+
+    fn skip(&mut self, amt: usize) -> Result<(), SynthesisError> {
+        if self.source.len <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        self.next += amt;
+
+        Ok(())
     }
 }
 
 // A re-usable method for parameter loading via mmap.  Unlike the
 // internal ones used elsewhere, this one does not update offset state
-// and simply does the cast and transform needed.
+// and simply does the cast and transform needed. Only supports
+// uncompressed points, see `PointEncoding::read_point`.
 pub fn read_g1<E: MultiMillerLoop>(
     mmap: &Mmap,
     range: Range<usize>,
@@ -176,7 +469,8 @@ pub fn read_g1<E: MultiMillerLoop>(
 
 // A re-usable method for parameter loading via mmap.  Unlike the
 // internal ones used elsewhere, this one does not update offset state
-// and simply does the cast and transform needed.
+// and simply does the cast and transform needed. Only supports
+// uncompressed points, see `PointEncoding::read_point`.
 pub fn read_g2<E: MultiMillerLoop>(
     mmap: &Mmap,
     range: Range<usize>,
@@ -210,3 +504,162 @@ pub fn read_g2<E: MultiMillerLoop>(
         Ok(affine)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    use crate::groth16::{generate_random_parameters, Parameters};
+    use crate::multicore::Worker;
+    use crate::multiexp::{multiexp, FullDensity};
+    use crate::{Circuit, ConstraintSystem};
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::{Field, PrimeField};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Repeatedly squares a private input, so that the queries span several chunks.
+    struct SquaringCircuit {
+        x: Option<Fr>,
+        n: usize,
+    }
+
+    impl Circuit<Fr> for SquaringCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut x_val = self.x;
+            let mut x = cs.alloc(|| "x", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+            for i in 0..self.n {
+                let y_val = x_val.map(|x| x.square());
+                let y = if i + 1 == self.n {
+                    cs.alloc_input(|| "y", || y_val.ok_or(SynthesisError::AssignmentMissing))?
+                } else {
+                    cs.alloc(
+                        || format!("x^2^{}", i + 1),
+                        || y_val.ok_or(SynthesisError::AssignmentMissing),
+                    )?
+                };
+                cs.enforce(
+                    || format!("square {}", i),
+                    |lc| lc + x,
+                    |lc| lc + x,
+                    |lc| lc + y,
+                );
+                x = y;
+                x_val = y_val;
+            }
+
+            Ok(())
+        }
+    }
+
+    fn check_source<G, S>(pool: &Worker, points: &Arc<Vec<G>>, skip: usize, source: S)
+    where
+        G: PrimeCurveAffine,
+        G::Scalar: PrimeField,
+        Bls12: pairing::Engine<Fr = G::Scalar>,
+        S: SourceBuilder<G>,
+    {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let exps = Arc::new(
+            (skip..points.len())
+                .map(|_| G::Scalar::random(&mut *rng).to_repr())
+                .collect::<Vec<_>>(),
+        );
+
+        let expected = multiexp::<_, _, _, Bls12, _>(
+            pool,
+            (points.clone(), skip),
+            FullDensity,
+            exps.clone(),
+            &mut None,
+        )
+        .wait()
+        .unwrap();
+        let (decoded, decoded_skip) = source.clone().get();
+        assert!(decoded == *points);
+        assert_eq!(decoded_skip, skip);
+        let actual = multiexp::<_, _, _, Bls12, _>(pool, source, FullDensity, exps, &mut None)
+            .wait()
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_mapped_sources() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let n = SOURCE_CHUNK_SIZE + 100;
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquaringCircuit { x: None, n }, rng).unwrap();
+        assert!(params.l.len() > SOURCE_CHUNK_SIZE);
+
+        let pool = Worker::new();
+        for &compressed in &[false, true] {
+            let mut bytes = vec![];
+            if compressed {
+                params.write_compressed(&mut bytes).unwrap();
+            } else {
+                params.write_with_header(&mut bytes).unwrap();
+            }
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(&bytes).unwrap();
+            file.flush().unwrap();
+
+            let mapped =
+                Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), false)
+                    .unwrap();
+            let mapped = &mapped;
+
+            check_source(&pool, &params.h, 0, mapped.get_h(0).unwrap());
+            check_source(&pool, &params.l, 0, mapped.get_l(0).unwrap());
+            let (a_inputs, a_aux) = mapped.get_a(2, 0).unwrap();
+            check_source(&pool, &params.a, 0, a_inputs);
+            check_source(&pool, &params.a, 2, a_aux);
+            let (_, b_g1_aux) = mapped.get_b_g1(2, 0).unwrap();
+            check_source(&pool, &params.b_g1, 2, b_g1_aux);
+            let (_, b_g2_aux) = mapped.get_b_g2(2, 0).unwrap();
+            check_source(&pool, &params.b_g2, 2, b_g2_aux);
+        }
+    }
+
+    #[test]
+    fn test_mapped_sources_invalid_point() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquaringCircuit { x: None, n: 10 }, rng)
+                .unwrap();
+        let mut bytes = vec![];
+        params.write_with_header(&mut bytes).unwrap();
+
+        let map = |bytes: &[u8]| {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            file.write_all(bytes).unwrap();
+            file.flush().unwrap();
+            Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), false).unwrap()
+        };
+
+        // A point of the l query which isn't on the curve. The digest of the header isn't
+        // checked, as `checked` isn't set.
+        let offset = map(&bytes).l[3].start;
+        bytes[offset + 10] ^= 1;
+        let mapped = map(&bytes);
+        let mapped = &mapped;
+
+        assert!(mapped.get_h(0).is_ok());
+        assert!(mapped.get_l(0).is_err());
+        // The query isn't considered validated after a failure.
+        assert!(mapped.get_l(0).is_err());
+    }
+}
@@ -8,22 +8,39 @@
 mod tests;
 
 pub mod aggregate;
+mod cached_params;
 mod ext;
 mod generator;
+mod header;
+pub mod json;
 mod mapped_params;
+pub mod mpc;
 mod params;
+mod powers_of_tau;
 mod proof;
 mod prover;
+mod r1cs_shape;
+mod simulator;
+pub mod solidity;
 mod verifier;
 mod verifying_key;
+mod witness;
 
 mod multiscalar;
+mod optimizer;
 
+pub use self::cached_params::*;
 pub use self::ext::*;
 pub use self::generator::*;
+pub use self::header::*;
 pub use self::mapped_params::*;
+pub use self::optimizer::*;
 pub use self::params::*;
+pub use self::powers_of_tau::*;
 pub use self::proof::*;
 pub use self::prover::*;
+pub use self::r1cs_shape::*;
+pub use self::simulator::*;
 pub use self::verifier::*;
 pub use self::verifying_key::*;
+pub use self::witness::*;
@@ -0,0 +1,625 @@
+//! Phase 2 of a multi-party computation (MPC) ceremony for Groth16 parameters.
+//!
+//! Starting from existing [`Parameters`], each participant samples a secret `delta'` and
+//! re-randomizes the circuit specific part of the CRS: `delta` in the verifying key is
+//! multiplied by `delta'` while the `h` and `l` queries are divided by it. Every contribution
+//! is accompanied by a proof of knowledge of `delta'`, so that anyone holding the initial
+//! parameters can verify the full transcript. As long as a single participant destroys their
+//! secret, nobody knows the resulting `delta`.
+//!
+//! Note that only `delta` is re-randomized. The remaining toxic waste (`alpha`, `beta`,
+//! `gamma` and `tau`) is inherited from the initial parameters, which therefore should come
+//! from a trusted phase 1 ceremony rather than from a single machine.
+
+use std::io::{self, Read, Write};
+use std::ops::{AddAssign, Mul};
+use std::sync::Arc;
+
+use blake2s_simd::State as Blake2s;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::Field;
+use group::{prime::PrimeCurveAffine, Curve, Group, UncompressedEncoding};
+use pairing::{Engine, MultiMillerLoop};
+use rand_chacha::ChaChaRng;
+use rand_core::{RngCore, SeedableRng};
+use rayon::prelude::*;
+
+use super::Parameters;
+use crate::SynthesisError;
+
+/// The public key published by a single participant of the ceremony. It proves knowledge of
+/// the participant's secret `delta'` without revealing it.
+#[derive(Clone, Debug)]
+pub struct PublicKey<E: MultiMillerLoop> {
+    /// `delta` in G1 after this contribution was applied.
+    pub delta_after: E::G1Affine,
+    /// A random point `s` in G1 and `s * delta'`.
+    pub s: E::G1Affine,
+    pub s_delta: E::G1Affine,
+    /// `r * delta'`, where `r` is derived from `transcript` by hashing into G2.
+    pub r_delta: E::G2Affine,
+    /// Hash of the ceremony state this contribution builds upon, together with `s` and
+    /// `s_delta`.
+    pub transcript: [u8; 32],
+}
+
+impl<E: MultiMillerLoop> PartialEq for PublicKey<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.delta_after == other.delta_after
+            && self.s == other.s
+            && self.s_delta == other.s_delta
+            && self.r_delta == other.r_delta
+            && self.transcript == other.transcript
+    }
+}
+
+impl<E: MultiMillerLoop> PublicKey<E> {
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.delta_after.to_uncompressed().as_ref())?;
+        writer.write_all(self.s.to_uncompressed().as_ref())?;
+        writer.write_all(self.s_delta.to_uncompressed().as_ref())?;
+        writer.write_all(self.r_delta.to_uncompressed().as_ref())?;
+        writer.write_all(&self.transcript)?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let delta_after = read_point::<E::G1Affine, _>(&mut reader)?;
+        let s = read_point::<E::G1Affine, _>(&mut reader)?;
+        let s_delta = read_point::<E::G1Affine, _>(&mut reader)?;
+        let r_delta = read_point::<E::G2Affine, _>(&mut reader)?;
+
+        let mut transcript = [0u8; 32];
+        reader.read_exact(&mut transcript)?;
+
+        Ok(PublicKey {
+            delta_after,
+            s,
+            s_delta,
+            r_delta,
+            transcript,
+        })
+    }
+
+    /// Returns the hash identifying this contribution, which participants publish so that
+    /// they can later find their contribution in the transcript.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut h = Blake2s::new();
+        self.write(HashWriter(&mut h))
+            .expect("writing to a hasher never fails");
+        finalize(h)
+    }
+}
+
+/// Groth16 parameters together with the transcript of all phase 2 contributions applied to
+/// them so far.
+#[derive(Clone)]
+pub struct MPCParameters<E: MultiMillerLoop> {
+    params: Parameters<E>,
+    cs_hash: [u8; 32],
+    contributions: Vec<PublicKey<E>>,
+}
+
+impl<E: MultiMillerLoop> PartialEq for MPCParameters<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.params == other.params
+            && self.cs_hash == other.cs_hash
+            && self.contributions == other.contributions
+    }
+}
+
+impl<E: MultiMillerLoop> MPCParameters<E> {
+    /// Starts a new ceremony from `params`, which must be published so that the transcript
+    /// can later be verified against them.
+    pub fn new(params: Parameters<E>) -> Self {
+        let cs_hash = hash_parameters(&params);
+
+        MPCParameters {
+            params,
+            cs_hash,
+            contributions: vec![],
+        }
+    }
+
+    /// The current parameters, usable for proving once the ceremony is complete.
+    pub fn get_params(&self) -> &Parameters<E> {
+        &self.params
+    }
+
+    /// The hash of the initial parameters this ceremony started from.
+    pub fn cs_hash(&self) -> [u8; 32] {
+        self.cs_hash
+    }
+
+    pub fn contributions(&self) -> &[PublicKey<E>] {
+        &self.contributions
+    }
+
+    /// Applies a fresh random contribution to the parameters, returning the hash of the
+    /// contribution. The secret is dropped before this function returns.
+    pub fn contribute<R: RngCore>(&mut self, rng: &mut R) -> [u8; 32] {
+        let delta = loop {
+            let delta = E::Fr::random(&mut *rng);
+            if !bool::from(delta.is_zero()) {
+                break delta;
+            }
+        };
+        let delta_inv = delta.invert().unwrap();
+
+        let s = E::G1::random(&mut *rng).to_affine();
+        let s_delta = s.mul(delta).to_affine();
+        let transcript = self.transcript(&s, &s_delta);
+        let r_delta = hash_to_g2::<E>(&transcript).mul(delta).to_affine();
+
+        let vk = &mut self.params.vk;
+        vk.delta_g1 = vk.delta_g1.mul(delta).to_affine();
+        vk.delta_g2 = vk.delta_g2.mul(delta).to_affine();
+        self.params.h = Arc::new(batch_mul::<E::G1Affine>(&self.params.h, delta_inv));
+        self.params.l = Arc::new(batch_mul::<E::G1Affine>(&self.params.l, delta_inv));
+
+        let pubkey = PublicKey {
+            delta_after: self.params.vk.delta_g1,
+            s,
+            s_delta,
+            r_delta,
+            transcript,
+        };
+        let hash = pubkey.hash();
+        self.contributions.push(pubkey);
+
+        hash
+    }
+
+    /// Verifies the whole transcript against the `initial` parameters the ceremony started
+    /// from. Returns the hashes of all contributions, in order.
+    pub fn verify(&self, initial: &Parameters<E>) -> Result<Vec<[u8; 32]>, SynthesisError> {
+        if hash_parameters(initial) != self.cs_hash {
+            return invalid("initial parameters do not match the ceremony");
+        }
+        ensure_same_circuit(initial, &self.params)?;
+
+        let mut current_delta = initial.vk.delta_g1;
+        let mut hashes = Vec::with_capacity(self.contributions.len());
+        for (i, pubkey) in self.contributions.iter().enumerate() {
+            verify_pubkey(
+                &self.cs_hash,
+                &self.contributions[..i],
+                pubkey,
+                &current_delta,
+            )?;
+            current_delta = pubkey.delta_after;
+            hashes.push(pubkey.hash());
+        }
+
+        if current_delta != self.params.vk.delta_g1 {
+            return invalid("delta does not match the last contribution");
+        }
+        ensure_rescaled(initial, &self.params)?;
+
+        Ok(hashes)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        self.params.write(&mut writer)?;
+        writer.write_all(&self.cs_hash)?;
+        writer.write_u32::<BigEndian>(self.contributions.len() as u32)?;
+        for pubkey in &self.contributions {
+            pubkey.write(&mut writer)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let params = Parameters::read_body(&mut reader, checked)?;
+
+        let mut cs_hash = [0u8; 32];
+        reader.read_exact(&mut cs_hash)?;
+
+        let len = reader.read_u32::<BigEndian>()? as usize;
+        let mut contributions = Vec::with_capacity(len);
+        for _ in 0..len {
+            contributions.push(PublicKey::read(&mut reader)?);
+        }
+
+        Ok(MPCParameters {
+            params,
+            cs_hash,
+            contributions,
+        })
+    }
+
+    fn transcript(&self, s: &E::G1Affine, s_delta: &E::G1Affine) -> [u8; 32] {
+        transcript_hash(&self.cs_hash, &self.contributions, s, s_delta)
+    }
+}
+
+/// Verifies that `after` is `before` with exactly one valid contribution applied. Returns
+/// the hash of that contribution.
+pub fn verify_contribution<E: MultiMillerLoop>(
+    before: &MPCParameters<E>,
+    after: &MPCParameters<E>,
+) -> Result<[u8; 32], SynthesisError> {
+    if before.cs_hash != after.cs_hash {
+        return invalid("parameters belong to different ceremonies");
+    }
+    if after.contributions.len() != before.contributions.len() + 1
+        || after.contributions[..before.contributions.len()] != before.contributions[..]
+    {
+        return invalid("transcript is not extended by exactly one contribution");
+    }
+    ensure_same_circuit(&before.params, &after.params)?;
+
+    let pubkey = after.contributions.last().unwrap();
+    verify_pubkey(
+        &before.cs_hash,
+        &before.contributions,
+        pubkey,
+        &before.params.vk.delta_g1,
+    )?;
+    if pubkey.delta_after != after.params.vk.delta_g1 {
+        return invalid("delta does not match the contribution");
+    }
+    ensure_rescaled(&before.params, &after.params)?;
+
+    Ok(pubkey.hash())
+}
+
+fn invalid<T>(msg: &str) -> Result<T, SynthesisError> {
+    Err(SynthesisError::InvalidContribution(msg.to_string()))
+}
+
+/// Checks the proof of knowledge in `pubkey` and that it moves delta away from `delta_before`.
+fn verify_pubkey<E: MultiMillerLoop>(
+    cs_hash: &[u8; 32],
+    previous: &[PublicKey<E>],
+    pubkey: &PublicKey<E>,
+    delta_before: &E::G1Affine,
+) -> Result<(), SynthesisError> {
+    // With the identity anywhere, the pairing checks below hold for a secret of zero, which
+    // would set delta to zero.
+    if bool::from(pubkey.s.is_identity())
+        || bool::from(pubkey.s_delta.is_identity())
+        || bool::from(pubkey.r_delta.is_identity())
+        || bool::from(pubkey.delta_after.is_identity())
+    {
+        return invalid("public key contains the point at infinity");
+    }
+    if transcript_hash(cs_hash, previous, &pubkey.s, &pubkey.s_delta) != pubkey.transcript {
+        return invalid("transcript hash mismatch");
+    }
+
+    let r = hash_to_g2::<E>(&pubkey.transcript).to_affine();
+    if !same_ratio::<E>((&pubkey.s, &pubkey.s_delta), (&r, &pubkey.r_delta)) {
+        return invalid("invalid proof of knowledge");
+    }
+    if !same_ratio::<E>((delta_before, &pubkey.delta_after), (&r, &pubkey.r_delta)) {
+        return invalid("delta was not updated by the proven secret");
+    }
+
+    Ok(())
+}
+
+/// Checks that everything except the delta dependent elements is unchanged.
+fn ensure_same_circuit<E: MultiMillerLoop>(
+    before: &Parameters<E>,
+    after: &Parameters<E>,
+) -> Result<(), SynthesisError> {
+    let (vk0, vk1) = (&before.vk, &after.vk);
+    if vk0.alpha_g1 != vk1.alpha_g1
+        || vk0.beta_g1 != vk1.beta_g1
+        || vk0.beta_g2 != vk1.beta_g2
+        || vk0.gamma_g2 != vk1.gamma_g2
+        || vk0.ic != vk1.ic
+    {
+        return invalid("verifying key was modified");
+    }
+    if before.a != after.a || before.b_g1 != after.b_g1 || before.b_g2 != after.b_g2 {
+        return invalid("A/B queries were modified");
+    }
+    if before.h.len() != after.h.len() || before.l.len() != after.l.len() {
+        return invalid("H/L query lengths were modified");
+    }
+
+    Ok(())
+}
+
+/// Checks that delta in G2 and the H/L queries were rescaled consistently with delta in G1.
+fn ensure_rescaled<E: MultiMillerLoop>(
+    before: &Parameters<E>,
+    after: &Parameters<E>,
+) -> Result<(), SynthesisError> {
+    let (vk0, vk1) = (&before.vk, &after.vk);
+    if bool::from(vk1.delta_g1.is_identity()) || bool::from(vk1.delta_g2.is_identity()) {
+        return invalid("delta is the point at infinity");
+    }
+    if !same_ratio::<E>(
+        (&vk0.delta_g1, &vk1.delta_g1),
+        (&vk0.delta_g2, &vk1.delta_g2),
+    ) {
+        return invalid("delta in G1 and G2 are inconsistent");
+    }
+
+    // Elements of H and L are divided by delta, so they move in the opposite direction.
+    let (h0, h1) = merge_pairs::<E::G1Affine>(&before.h, &after.h);
+    if !same_ratio::<E>((&h0, &h1), (&vk1.delta_g2, &vk0.delta_g2)) {
+        return invalid("H query was not rescaled by delta");
+    }
+    let (l0, l1) = merge_pairs::<E::G1Affine>(&before.l, &after.l);
+    if !same_ratio::<E>((&l0, &l1), (&vk1.delta_g2, &vk0.delta_g2)) {
+        return invalid("L query was not rescaled by delta");
+    }
+
+    Ok(())
+}
+
+/// Checks that `g1.0 / g1.1 = g2.0 / g2.1` in the exponent.
+pub(super) fn same_ratio<E: MultiMillerLoop>(
+    g1: (&E::G1Affine, &E::G1Affine),
+    g2: (&E::G2Affine, &E::G2Affine),
+) -> bool {
+    E::pairing(g1.0, g2.1) == E::pairing(g1.1, g2.0)
+}
+
+/// Compresses two vectors of points into a single pair using a random linear combination,
+/// preserving the ratio between their elements with overwhelming probability.
+pub(super) fn merge_pairs<G: PrimeCurveAffine>(v1: &[G], v2: &[G]) -> (G, G) {
+    assert_eq!(v1.len(), v2.len());
+
+    let chunk = (v1.len() / rayon::current_num_threads()).max(1);
+    let (s1, s2) = v1
+        .par_chunks(chunk)
+        .zip(v2.par_chunks(chunk))
+        .map(|(v1, v2)| {
+            let rng = &mut rand::thread_rng();
+            let mut acc1 = G::Curve::identity();
+            let mut acc2 = G::Curve::identity();
+            for (p1, p2) in v1.iter().zip(v2.iter()) {
+                let rho = G::Scalar::random(&mut *rng);
+                acc1.add_assign(&p1.mul(rho));
+                acc2.add_assign(&p2.mul(rho));
+            }
+            (acc1, acc2)
+        })
+        .reduce(
+            || (G::Curve::identity(), G::Curve::identity()),
+            |(a1, a2), (b1, b2)| (a1 + b1, a2 + b2),
+        );
+
+    (s1.to_affine(), s2.to_affine())
+}
+
+fn batch_mul<G: PrimeCurveAffine>(points: &[G], scalar: G::Scalar) -> Vec<G> {
+    let projective = points.par_iter().map(|p| p.mul(scalar)).collect::<Vec<_>>();
+    let mut affine = vec![G::identity(); projective.len()];
+    G::Curve::batch_normalize(&projective, &mut affine);
+    affine
+}
+
+/// Deterministically maps a transcript hash to a point in G2 with unknown discrete logarithm.
+fn hash_to_g2<E: Engine>(digest: &[u8; 32]) -> E::G2 {
+    E::G2::random(ChaChaRng::from_seed(*digest))
+}
+
+fn transcript_hash<E: MultiMillerLoop>(
+    cs_hash: &[u8; 32],
+    previous: &[PublicKey<E>],
+    s: &E::G1Affine,
+    s_delta: &E::G1Affine,
+) -> [u8; 32] {
+    let mut h = Blake2s::new();
+    h.update(cs_hash);
+    for pubkey in previous {
+        pubkey
+            .write(HashWriter(&mut h))
+            .expect("writing to a hasher never fails");
+    }
+    h.update(s.to_uncompressed().as_ref());
+    h.update(s_delta.to_uncompressed().as_ref());
+    finalize(h)
+}
+
+fn hash_parameters<E: MultiMillerLoop>(params: &Parameters<E>) -> [u8; 32] {
+    let mut h = Blake2s::new();
+    params
+        .write(HashWriter(&mut h))
+        .expect("writing to a hasher never fails");
+    finalize(h)
+}
+
+fn finalize(h: Blake2s) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(h.finalize().as_bytes());
+    out
+}
+
+// Public keys never contain the point at infinity, see `verify_pubkey`.
+fn read_point<G, R>(reader: &mut R) -> io::Result<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    R: Read,
+{
+    let mut repr = <G as UncompressedEncoding>::Uncompressed::default();
+    reader.read_exact(repr.as_mut())?;
+    let point: G = Option::from(G::from_uncompressed(&repr))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))?;
+    if bool::from(point.is_identity()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "point at infinity",
+        ));
+    }
+
+    Ok(point)
+}
+
+/// Adapter feeding everything written to it into a hasher.
+struct HashWriter<'a>(&'a mut Blake2s);
+
+impl<'a> Write for HashWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::{Circuit, ConstraintSystem};
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::PrimeField;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct MulCircuit<Scalar: PrimeField> {
+        a: Option<Scalar>,
+        b: Option<Scalar>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for MulCircuit<Scalar> {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || "c",
+                || Ok(self.a.ok_or(SynthesisError::AssignmentMissing)? * self.b.unwrap()),
+            )?;
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mpc_contributions() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let initial =
+            generate_random_parameters::<Bls12, _, _>(MulCircuit { a: None, b: None }, rng)
+                .unwrap();
+        let mut mpc = MPCParameters::new(initial.clone());
+        assert!(mpc.verify(&initial).unwrap().is_empty());
+
+        let mut hashes = vec![];
+        for _ in 0..2 {
+            let before = mpc.clone();
+            hashes.push(mpc.contribute(rng));
+            assert_eq!(
+                verify_contribution(&before, &mpc).unwrap(),
+                hashes[hashes.len() - 1]
+            );
+        }
+        assert_eq!(mpc.verify(&initial).unwrap(), hashes);
+        assert!(mpc.get_params().vk.delta_g1 != initial.vk.delta_g1);
+
+        // Proofs created with the final parameters verify.
+        let pvk = prepare_verifying_key(&mpc.get_params().vk);
+        let (a, b) = (Fr::random(&mut *rng), Fr::random(&mut *rng));
+        let circuit = MulCircuit {
+            a: Some(a),
+            b: Some(b),
+        };
+        let proof = create_random_proof(circuit, mpc.get_params(), rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[a * b]).unwrap());
+
+        // The transcript survives a serialization round trip.
+        let mut bytes = vec![];
+        mpc.write(&mut bytes).unwrap();
+        let de_mpc = MPCParameters::<Bls12>::read(&bytes[..], true).unwrap();
+        assert!(de_mpc == mpc);
+        assert_eq!(de_mpc.verify(&initial).unwrap(), hashes);
+    }
+
+    #[test]
+    fn test_mpc_invalid_contribution() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let initial =
+            generate_random_parameters::<Bls12, _, _>(MulCircuit { a: None, b: None }, rng)
+                .unwrap();
+        let before = MPCParameters::new(initial.clone());
+        let mut after = before.clone();
+        after.contribute(rng);
+
+        // Tampering with the H query is detected.
+        let mut tampered = after.clone();
+        let mut h = (*tampered.params.h).clone();
+        h[0] = h[0].to_curve().double().to_affine();
+        tampered.params.h = Arc::new(h);
+        assert!(verify_contribution(&before, &tampered).is_err());
+        assert!(tampered.verify(&initial).is_err());
+
+        // Replacing delta without knowing the secret is detected.
+        let mut tampered = after.clone();
+        tampered.params.vk.delta_g1 =
+            (tampered.params.vk.delta_g1.to_curve() + initial.vk.delta_g1.to_curve()).to_affine();
+        assert!(verify_contribution(&before, &tampered).is_err());
+
+        // Verification against different initial parameters fails.
+        let other = generate_random_parameters::<Bls12, _, _>(MulCircuit { a: None, b: None }, rng)
+            .unwrap();
+        assert!(after.verify(&other).is_err());
+    }
+
+    #[test]
+    fn test_mpc_identity_contribution() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let initial =
+            generate_random_parameters::<Bls12, _, _>(MulCircuit { a: None, b: None }, rng)
+                .unwrap();
+        let before = MPCParameters::new(initial.clone());
+
+        // A secret of zero satisfies all ratio checks, but sets delta to zero.
+        let g1 = <Bls12 as Engine>::G1Affine::identity();
+        let g2 = <Bls12 as Engine>::G2Affine::identity();
+        let mut after = before.clone();
+        let transcript = after.transcript(&g1, &g1);
+        after.params.vk.delta_g1 = g1;
+        after.params.vk.delta_g2 = g2;
+        after.params.h = Arc::new(vec![g1; initial.h.len()]);
+        after.params.l = Arc::new(vec![g1; initial.l.len()]);
+        let pubkey = PublicKey {
+            delta_after: g1,
+            s: g1,
+            s_delta: g1,
+            r_delta: g2,
+            transcript,
+        };
+        after.contributions.push(pubkey.clone());
+
+        match verify_contribution(&before, &after) {
+            Err(SynthesisError::InvalidContribution(msg)) => assert!(msg.contains("infinity")),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(after.verify(&initial).is_err());
+
+        // Such public keys can't be read either.
+        let mut bytes = vec![];
+        pubkey.write(&mut bytes).unwrap();
+        assert!(PublicKey::<Bls12>::read(&bytes[..]).is_err());
+    }
+}
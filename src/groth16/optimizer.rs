@@ -0,0 +1,649 @@
+//! An optimization pass over the constraints of an [`R1csShape`], which runs between
+//! synthesis and parameter generation or proving.
+//!
+//! The pass
+//!
+//! - eliminates linear constraints, i.e. constraints `A * B = C` where `A` or `B` is a
+//!   constant, by solving them for an auxiliary variable and substituting that variable in
+//!   all other constraints,
+//! - turns constraints with the same product `A * B` as an earlier one into the linear
+//!   constraint that their `C`s are equal, which deduplicates variables computed twice, and
+//! - drops constraints which are duplicates of earlier ones up to scaling, like repeated
+//!   booleanity checks of the same bit.
+//!
+//! Inputs are never eliminated, so verification doesn't change. The eliminated auxiliary
+//! variables are determined by the remaining ones, so the optimized constraints are
+//! satisfied exactly by the assignments of the original ones with the eliminated variables
+//! removed, see [`OptimizedShape::reduce`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use ff::PrimeField;
+
+use super::{Assignment, R1csShape, SparseMatrix};
+use crate::util_cs::shape_cs::ShapeCS;
+use crate::{ConstraintSystem, LinearCombination, SynthesisError};
+
+// The terms of a linear combination as pairs of column and coefficient, sorted by column
+// and without zero coefficients.
+type Row<Scalar> = Vec<(usize, Scalar)>;
+
+fn matrix_row<Scalar: PrimeField>(matrix: &SparseMatrix<Scalar>, row: usize) -> Row<Scalar> {
+    let mut terms = BTreeMap::new();
+    for (column, coeff) in matrix.row(row) {
+        *terms.entry(column).or_insert_with(Scalar::zero) += coeff;
+    }
+
+    terms
+        .into_iter()
+        .filter(|(_, coeff)| !bool::from(coeff.is_zero()))
+        .collect()
+}
+
+// `a + k * b`
+fn add_scaled<Scalar: PrimeField>(
+    a: &[(usize, Scalar)],
+    b: &[(usize, Scalar)],
+    k: Scalar,
+) -> Row<Scalar> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        let (column, coeff) = if j == b.len() || (i < a.len() && a[i].0 < b[j].0) {
+            i += 1;
+            a[i - 1]
+        } else if i == a.len() || b[j].0 < a[i].0 {
+            j += 1;
+            (b[j - 1].0, b[j - 1].1 * k)
+        } else {
+            i += 1;
+            j += 1;
+            (a[i - 1].0, a[i - 1].1 + b[j - 1].1 * k)
+        };
+        if !bool::from(coeff.is_zero()) {
+            out.push((column, coeff));
+        }
+    }
+
+    out
+}
+
+fn scale<Scalar: PrimeField>(row: &[(usize, Scalar)], k: Scalar) -> Row<Scalar> {
+    row.iter()
+        .map(|&(column, coeff)| (column, coeff * k))
+        .collect()
+}
+
+// The value of a row which only contains the constant one, if it does.
+fn constant<Scalar: PrimeField>(row: &[(usize, Scalar)]) -> Option<Scalar> {
+    match row {
+        [] => Some(Scalar::zero()),
+        [(0, coeff)] => Some(*coeff),
+        _ => None,
+    }
+}
+
+// Scales a row such that its first coefficient is one, returning the inverse of the factor.
+fn normalize<Scalar: PrimeField>(row: &[(usize, Scalar)]) -> (Row<Scalar>, Scalar) {
+    match row.first() {
+        Some(&(_, first)) => (scale(row, first.invert().unwrap()), first),
+        None => (vec![], Scalar::one()),
+    }
+}
+
+fn row_key<Scalar: PrimeField>(row: &[(usize, Scalar)], key: &mut Vec<u8>) {
+    key.extend_from_slice(&(row.len() as u64).to_le_bytes());
+    for (column, coeff) in row {
+        key.extend_from_slice(&(*column as u64).to_le_bytes());
+        key.extend_from_slice(coeff.to_repr().as_ref());
+    }
+}
+
+struct Optimizer<Scalar: PrimeField> {
+    num_inputs: usize,
+    // The number of occurrences of each column in the original matrices, used to pick the
+    // variable to eliminate from a linear constraint.
+    occurrences: Vec<usize>,
+    // Expressions for eliminated columns. They may contain columns eliminated later on.
+    substitutions: HashMap<usize, Row<Scalar>>,
+}
+
+impl<Scalar: PrimeField> Optimizer<Scalar> {
+    // Substitutes all eliminated columns, until only remaining ones are left.
+    fn resolve(&self, mut row: Row<Scalar>) -> Row<Scalar> {
+        while row
+            .iter()
+            .any(|(column, _)| self.substitutions.contains_key(column))
+        {
+            let mut resolved = vec![];
+            for (column, coeff) in row {
+                match self.substitutions.get(&column) {
+                    Some(expr) => resolved = add_scaled(&resolved, expr, coeff),
+                    None => resolved = add_scaled(&resolved, &[(column, coeff)], Scalar::one()),
+                }
+            }
+            row = resolved;
+        }
+
+        row
+    }
+
+    // Eliminates an auxiliary variable using the linear constraint `eq = 0`, which has to be
+    // resolved. Returns whether the constraint can be dropped.
+    fn eliminate(&mut self, eq: Row<Scalar>) -> bool {
+        debug_assert!(
+            eq.iter()
+                .all(|(column, _)| !self.substitutions.contains_key(column)),
+            "eliminating with an unresolved constraint"
+        );
+        if eq.is_empty() {
+            // Trivially satisfied.
+            return true;
+        }
+
+        // Never pick an eliminated column, which would replace its substitution.
+        let pick = eq
+            .iter()
+            .filter(|(column, _)| {
+                *column >= self.num_inputs && !self.substitutions.contains_key(column)
+            })
+            .min_by_key(|(column, _)| (self.occurrences[*column], *column));
+        let (column, coeff) = match pick {
+            Some(&term) => term,
+            // Only inputs, or unsatisfiable if only the constant one.
+            None => return false,
+        };
+
+        // column = -(eq - coeff * column) / coeff
+        let rest = add_scaled(&eq, &[(column, coeff)], -Scalar::one());
+        let expr = scale(&rest, -coeff.invert().unwrap());
+        self.substitutions.insert(column, expr);
+
+        true
+    }
+}
+
+/// An [`R1csShape`] after the optimization pass of [`R1csShape::optimize`], together with
+/// the mapping of assignments of the original shape to the optimized one.
+#[derive(Clone, Debug)]
+pub struct OptimizedShape<Scalar: PrimeField> {
+    shape: R1csShape<Scalar>,
+    original_num_aux: usize,
+    // The auxiliary variables of the original shape which are kept, in order.
+    kept_aux: Vec<usize>,
+    // The eliminated auxiliary variables of the original shape, with their value as linear
+    // combination of the inputs and kept auxiliary variables of the original shape.
+    eliminated: Vec<(usize, Row<Scalar>)>,
+}
+
+impl<Scalar: PrimeField> OptimizedShape<Scalar> {
+    /// The optimized shape, which parameters are generated for and proofs are created with.
+    pub fn shape(&self) -> &R1csShape<Scalar> {
+        &self.shape
+    }
+
+    pub fn into_shape(self) -> R1csShape<Scalar> {
+        self.shape
+    }
+
+    /// The number of auxiliary variables which were eliminated.
+    pub fn num_eliminated(&self) -> usize {
+        self.eliminated.len()
+    }
+
+    fn check_assignment(
+        &self,
+        assignment: &Assignment<Scalar>,
+        num_aux: usize,
+    ) -> Result<(), SynthesisError> {
+        if assignment.inputs.len() != self.shape.num_inputs() || assignment.aux.len() != num_aux {
+            return Err(SynthesisError::IncompatibleLengthVector(format!(
+                "assignment of {} inputs and {} auxiliary variables, expected {} and {}",
+                assignment.inputs.len(),
+                assignment.aux.len(),
+                self.shape.num_inputs(),
+                num_aux
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Maps an assignment of the original shape, e.g. as synthesized by
+    /// [`synthesize_assignment`](super::synthesize_assignment), to an assignment of the
+    /// optimized shape by dropping the eliminated variables.
+    pub fn reduce(
+        &self,
+        assignment: Assignment<Scalar>,
+    ) -> Result<Assignment<Scalar>, SynthesisError> {
+        self.check_assignment(&assignment, self.original_num_aux)?;
+
+        let Assignment { inputs, aux } = assignment;
+        let aux = self.kept_aux.iter().map(|&i| aux[i]).collect();
+
+        Ok(Assignment { inputs, aux })
+    }
+
+    /// Maps an assignment of the optimized shape back to the original shape, computing the
+    /// values of the eliminated variables.
+    pub fn expand(
+        &self,
+        assignment: Assignment<Scalar>,
+    ) -> Result<Assignment<Scalar>, SynthesisError> {
+        self.check_assignment(&assignment, self.kept_aux.len())?;
+
+        let Assignment { inputs, aux: kept } = assignment;
+        let num_inputs = inputs.len();
+        let mut aux = vec![Scalar::zero(); self.original_num_aux];
+        for (&i, value) in self.kept_aux.iter().zip(kept) {
+            aux[i] = value;
+        }
+        for (i, expr) in &self.eliminated {
+            aux[*i] = expr.iter().fold(Scalar::zero(), |acc, &(column, coeff)| {
+                let value = if column < num_inputs {
+                    inputs[column]
+                } else {
+                    aux[column - num_inputs]
+                };
+                acc + value * coeff
+            });
+        }
+
+        Ok(Assignment { inputs, aux })
+    }
+}
+
+impl<Scalar: PrimeField> R1csShape<Scalar> {
+    /// Optimizes the constraints: linear constraints are eliminated by substituting an
+    /// auxiliary variable, constraints with the same product as an earlier one are turned
+    /// into linear ones, and duplicate constraints, like repeated booleanity checks, are
+    /// dropped. Inputs are never eliminated.
+    ///
+    /// The optimized shape is a different circuit, with its own parameters and circuit
+    /// digest, which are only compatible with assignments mapped by
+    /// [`OptimizedShape::reduce`].
+    pub fn optimize(&self) -> OptimizedShape<Scalar> {
+        let num_inputs = self.num_inputs();
+        let num_aux = self.num_aux();
+
+        let mut occurrences = vec![0; num_inputs + num_aux];
+        for matrix in [self.a(), self.b(), self.c()].iter() {
+            for row in 0..matrix.num_rows() {
+                for (column, _) in matrix.row(row) {
+                    occurrences[column] += 1;
+                }
+            }
+        }
+
+        let mut optimizer = Optimizer {
+            num_inputs,
+            occurrences,
+            substitutions: HashMap::new(),
+        };
+        let mut constraints = (0..self.num_constraints())
+            .map(|row| {
+                (
+                    matrix_row(self.a(), row),
+                    matrix_row(self.b(), row),
+                    matrix_row(self.c(), row),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Eliminating variables can make other constraints linear or duplicates, so repeat
+        // until nothing changes.
+        loop {
+            let num_constraints = constraints.len();
+            let mut products = HashMap::new();
+            let mut kept: Vec<(Row<Scalar>, Row<Scalar>, Row<Scalar>)> = vec![];
+
+            for (a, b, c) in constraints {
+                let a = optimizer.resolve(a);
+                let b = optimizer.resolve(b);
+                let c = optimizer.resolve(c);
+
+                let linear = match (constant(&a), constant(&b)) {
+                    (Some(k), _) => Some(add_scaled(&scale(&b, k), &c, -Scalar::one())),
+                    (None, Some(k)) => Some(add_scaled(&scale(&a, k), &c, -Scalar::one())),
+                    (None, None) => None,
+                };
+                if let Some(eq) = linear {
+                    if !optimizer.eliminate(eq) {
+                        kept.push((a, b, c));
+                    }
+                    continue;
+                }
+
+                // Normalize the product, which is commutative and invariant under scaling
+                // of the factors when the result is scaled accordingly.
+                let (na, ka) = normalize(&a);
+                let (nb, kb) = normalize(&b);
+                let nc = scale(&c, (ka * kb).invert().unwrap());
+                let mut key_a = vec![];
+                row_key(&na, &mut key_a);
+                let mut key_b = vec![];
+                row_key(&nb, &mut key_b);
+                let key = if key_a <= key_b {
+                    [key_a, key_b].concat()
+                } else {
+                    [key_b, key_a].concat()
+                };
+
+                match products.get(&key) {
+                    None => {
+                        products.insert(key, nc);
+                        kept.push((a, b, c));
+                    }
+                    Some(other_c) => {
+                        // A * B = C and A * B = C', so C = C'. C' was stored before later
+                        // eliminations, so it has to be resolved again.
+                        let other_c = optimizer.resolve(other_c.clone());
+                        let eq = add_scaled(&nc, &other_c, -Scalar::one());
+                        if !optimizer.eliminate(eq) {
+                            kept.push((a, b, c));
+                        }
+                    }
+                }
+            }
+
+            constraints = kept;
+            if constraints.len() == num_constraints {
+                break;
+            }
+        }
+
+        // Remaining constraints might still contain variables eliminated after them.
+        let constraints = constraints
+            .into_iter()
+            .map(|(a, b, c)| {
+                (
+                    optimizer.resolve(a),
+                    optimizer.resolve(b),
+                    optimizer.resolve(c),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let kept_aux = (0..num_aux)
+            .filter(|i| !optimizer.substitutions.contains_key(&(num_inputs + i)))
+            .collect::<Vec<_>>();
+        let mut eliminated = optimizer
+            .substitutions
+            .keys()
+            .map(|&column| {
+                let expr = optimizer.resolve(vec![(column, Scalar::one())]);
+                (column - num_inputs, expr)
+            })
+            .collect::<Vec<_>>();
+        eliminated.sort_by_key(|(i, _)| *i);
+
+        // Replay the remaining constraints over the kept variables, which also computes
+        // the digest of the optimized shape.
+        let mut cs = ShapeCS::new();
+        let mut variables = HashMap::new();
+        for i in 0..num_inputs {
+            let var = cs
+                .alloc_input(|| "", || Ok(Scalar::zero()))
+                .expect("shape synthesis can't fail");
+            variables.insert(i, var);
+        }
+        for &i in &kept_aux {
+            let var = cs
+                .alloc(|| "", || Ok(Scalar::zero()))
+                .expect("shape synthesis can't fail");
+            variables.insert(num_inputs + i, var);
+        }
+        let lc = |row: &[(usize, Scalar)]| {
+            row.iter()
+                .fold(LinearCombination::zero(), |lc, &(column, coeff)| {
+                    lc + (coeff, variables[&column])
+                })
+        };
+        for (a, b, c) in &constraints {
+            cs.enforce(|| "", |_| lc(a), |_| lc(b), |_| lc(c));
+        }
+
+        OptimizedShape {
+            shape: R1csShape::from_shape_cs(&cs),
+            original_num_aux: num_aux,
+            kept_aux,
+            eliminated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof_from_assignment, generate_random_parameters, prepare_verifying_key,
+        synthesize_assignment, verify_proof,
+    };
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use crate::Circuit;
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of bits `b` and `x` such that `x^3 + sum(2^i * b_i)` is the public
+    // input, with the redundancy gadgets tend to produce: copies through linear
+    // constraints, repeated booleanity checks and products computed twice.
+    struct RedundantCircuit {
+        x: Option<Fr>,
+        bits: Vec<Option<bool>>,
+    }
+
+    impl Circuit<Fr> for RedundantCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let value = |v: Option<Fr>| move || v.ok_or(SynthesisError::AssignmentMissing);
+            let bit_value = |b: Option<bool>| b.map(|b| if b { Fr::one() } else { Fr::zero() });
+
+            let mut packed = LinearCombination::zero();
+            let mut packed_value = Some(Fr::zero());
+            let mut coeff = Fr::one();
+            for (i, bit) in self.bits.iter().enumerate() {
+                let var = cs.alloc(|| format!("bit {}", i), value(bit_value(*bit)))?;
+                for j in 0..2 {
+                    // (1 - b) * b = 0, and again as b * (b - 1) = 0.
+                    cs.enforce(
+                        || format!("boolean {} {}", i, j),
+                        |lc| {
+                            if j == 0 {
+                                lc + CS::one() - var
+                            } else {
+                                lc + var
+                            }
+                        },
+                        |lc| {
+                            if j == 0 {
+                                lc + var
+                            } else {
+                                lc + var - CS::one()
+                            }
+                        },
+                        |lc| lc,
+                    );
+                }
+                packed = packed + (coeff, var);
+                packed_value = packed_value.and_then(|p| bit_value(*bit).map(|b| p + b * coeff));
+                coeff = coeff.double();
+            }
+            let num = cs.alloc(|| "num", value(packed_value))?;
+            cs.enforce(
+                || "pack",
+                |lc| lc + &packed,
+                |lc| lc + CS::one(),
+                |lc| lc + num,
+            );
+
+            let x = cs.alloc(|| "x", value(self.x))?;
+            let x_copy = cs.alloc(|| "x copy", value(self.x))?;
+            cs.enforce(
+                || "copy",
+                |lc| lc + x,
+                |lc| lc + CS::one(),
+                |lc| lc + x_copy,
+            );
+            let x2 = self.x.map(|x| x.square());
+            let sq = cs.alloc(|| "x^2", value(x2))?;
+            cs.enforce(|| "square", |lc| lc + x, |lc| lc + x, |lc| lc + sq);
+            let sq_again = cs.alloc(|| "x^2 again", value(x2))?;
+            cs.enforce(
+                || "square again",
+                |lc| lc + x_copy,
+                |lc| lc + x,
+                |lc| lc + sq_again,
+            );
+            let x3 = x2.and_then(|x2| self.x.map(|x| x2 * x));
+            let cube = cs.alloc(|| "x^3", value(x3))?;
+            cs.enforce(
+                || "cube",
+                |lc| lc + sq_again,
+                |lc| lc + x_copy,
+                |lc| lc + cube,
+            );
+
+            let out = cs.alloc_input(
+                || "out",
+                value(x3.and_then(|x3| packed_value.map(|p| x3 + p))),
+            )?;
+            cs.enforce(
+                || "out",
+                |lc| lc + cube + num,
+                |lc| lc + CS::one(),
+                |lc| lc + out,
+            );
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_optimize() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let bits = vec![Some(true), Some(false), Some(true), Some(true)];
+        let x = Fr::random(&mut *rng);
+        let circuit = || RedundantCircuit {
+            x: Some(x),
+            bits: bits.clone(),
+        };
+
+        let shape = R1csShape::from_circuit(RedundantCircuit {
+            x: None,
+            bits: vec![None; 4],
+        })
+        .unwrap();
+        assert_eq!(shape.num_constraints(), 4 * 2 + 1 + 1 + 1 + 1 + 1 + 1);
+        assert_eq!(shape.num_aux(), 4 + 1 + 5);
+
+        let optimized = shape.optimize();
+        // One booleanity check per bit, and x^2 and x^3.
+        assert_eq!(optimized.shape().num_constraints(), 4 + 2);
+        // `num`, `x copy`, `x^2 again` and one of `x^3` or a bit are eliminated.
+        assert_eq!(optimized.num_eliminated(), 4);
+        assert_eq!(optimized.shape().num_aux(), shape.num_aux() - 4);
+        assert_eq!(optimized.shape().num_inputs(), shape.num_inputs());
+        assert_ne!(optimized.shape().digest(), shape.digest());
+
+        // Optimizing again doesn't change anything.
+        let again = optimized.shape().optimize();
+        assert_eq!(again.num_eliminated(), 0);
+        assert_eq!(again.shape().digest(), optimized.shape().digest());
+
+        let assignment = synthesize_assignment(circuit()).unwrap();
+        let reduced = optimized.reduce(assignment.clone()).unwrap();
+        assert_eq!(reduced.aux.len(), optimized.shape().num_aux());
+        assert_eq!(optimized.expand(reduced.clone()).unwrap(), assignment);
+        assert!(optimized.reduce(reduced.clone()).is_err());
+
+        let mut cs = TestConstraintSystem::new();
+        optimized
+            .shape()
+            .circuit_with_assignment(&reduced)
+            .synthesize(&mut cs)
+            .unwrap();
+        assert!(cs.is_satisfied());
+
+        // A wrong assignment of a kept variable still fails.
+        let mut wrong = reduced.clone();
+        wrong.aux[0] += Fr::one();
+        let mut cs = TestConstraintSystem::new();
+        optimized
+            .shape()
+            .circuit_with_assignment(&wrong)
+            .synthesize(&mut cs)
+            .unwrap();
+        assert!(!cs.is_satisfied());
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(optimized.shape().circuit(), rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        let proof =
+            create_random_proof_from_assignment(optimized.shape(), reduced, &params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &assignment.inputs[1..]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[x]).unwrap());
+    }
+
+    // `x * y = c1`, `1 * c1 = z` and `x * y = c2`, where `c1` is used least, so it's the
+    // first pick both for `c1 = z` and for `c1 = c2` if the latter isn't resolved.
+    struct SharedProductCircuit {
+        x: Fr,
+        y: Fr,
+        z: Fr,
+    }
+
+    impl Circuit<Fr> for SharedProductCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let xy = self.x * self.y;
+            let x = cs.alloc(|| "x", || Ok(self.x))?;
+            let y = cs.alloc(|| "y", || Ok(self.y))?;
+            let c1 = cs.alloc(|| "c1", || Ok(xy))?;
+            cs.enforce(|| "x * y = c1", |lc| lc + x, |lc| lc + y, |lc| lc + c1);
+            let z = cs.alloc(|| "z", || Ok(self.z))?;
+            cs.enforce(|| "c1 = z", |lc| lc + CS::one(), |lc| lc + c1, |lc| lc + z);
+            let c2 = cs.alloc(|| "c2", || Ok(xy))?;
+            cs.enforce(|| "x * y = c2", |lc| lc + x, |lc| lc + y, |lc| lc + c2);
+
+            let z2 = cs.alloc(|| "z^2", || Ok(self.z.square()))?;
+            cs.enforce(|| "z^2", |lc| lc + z, |lc| lc + z, |lc| lc + z2);
+            let c2_2 = cs.alloc(|| "c2^2", || Ok(xy.square()))?;
+            cs.enforce(|| "c2^2", |lc| lc + c2, |lc| lc + c2, |lc| lc + c2_2);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_optimize_shared_product() {
+        let (x, y) = (Fr::from(3u64), Fr::from(5u64));
+        let shape = R1csShape::from_circuit(SharedProductCircuit { x, y, z: x * y }).unwrap();
+        let optimized = shape.optimize();
+
+        let is_satisfied = |shape: &R1csShape<Fr>, assignment: &Assignment<Fr>| {
+            let mut cs = TestConstraintSystem::new();
+            shape
+                .circuit_with_assignment(assignment)
+                .synthesize(&mut cs)
+                .unwrap();
+            cs.is_satisfied()
+        };
+
+        // Any assignment satisfying the optimized shape has to expand to one satisfying the
+        // original shape, in particular one with `z != c1`, if `c1 = z` was lost.
+        for z in [x * y, x * y + Fr::one()].iter() {
+            let assignment = synthesize_assignment(SharedProductCircuit { x, y, z: *z }).unwrap();
+            let reduced = optimized.reduce(assignment).unwrap();
+            assert!(is_satisfied(optimized.shape(), &reduced));
+            let expanded = optimized.expand(reduced).unwrap();
+            assert!(is_satisfied(&shape, &expanded));
+            // `z` is the fourth auxiliary variable.
+            assert_eq!(expanded.aux[3], x * y);
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use ff::PrimeField;
 use group::{prime::PrimeCurveAffine, UncompressedEncoding};
 use pairing::MultiMillerLoop;
 
-use crate::multiexp::SourceBuilder;
-use crate::SynthesisError;
+use crate::multiexp::{DensityTracker, SourceBuilder};
+use crate::util_cs::shape_cs::CircuitDigest;
+use crate::{Circuit, SynthesisError};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use memmap::{Mmap, MmapOptions};
+use std::fmt;
 use std::fs::File;
 use std::io::{self, Read, Write};
 use std::mem;
@@ -13,7 +16,15 @@ use std::ops::Range;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use super::{MappedParameters, VerifyingKey};
+use rayon::prelude::*;
+
+use super::generator::synthesize_assembly;
+use super::header::{body_digest, HashReader, HashWriter};
+use super::mapped_params::{QueryCache, QueryCaches};
+use super::{
+    CurveName, MappedParameters, ParameterHeader, PointEncoding, VerifyingKey,
+    NUM_PARAMETER_SECTIONS, PARAMETERS_MAGIC,
+};
 
 #[derive(Clone)]
 pub struct Parameters<E>
@@ -22,6 +33,11 @@ where
 {
     pub vk: VerifyingKey<E>,
 
+    /// The digest of the circuit these parameters were generated for, if known. Proving
+    /// fails if it doesn't match the circuit being proven. Legacy parameter files and files
+    /// without a digest in their header don't have one.
+    pub circuit_digest: Option<CircuitDigest>,
+
     // Elements of the form ((tau^i * t(tau)) / delta) for i between 0 and
     // m-2 inclusive. Never contains points at infinity.
     pub h: Arc<Vec<E::G1Affine>>,
@@ -57,65 +73,454 @@ where
     }
 }
 
+/// The number of points in each query of [`Parameters`], including the IC query of the
+/// verifying key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuerySizes {
+    pub ic: usize,
+    pub h: usize,
+    pub l: usize,
+    pub a: usize,
+    pub b_g1: usize,
+    pub b_g2: usize,
+}
+
+impl QuerySizes {
+    /// The sizes of the queries of parameters generated for `circuit`, i.e. the points a
+    /// prover for `circuit` uses. The circuit is only synthesized, without a witness.
+    pub fn of_circuit<Scalar, C>(circuit: C) -> Result<Self, SynthesisError>
+    where
+        Scalar: PrimeField,
+        C: Circuit<Scalar>,
+    {
+        Ok(QueryDensities::of_circuit(circuit)?.sizes)
+    }
+
+    /// Checks that `sizes` describes a subset of parameters with these sizes.
+    pub(crate) fn check_subset(&self, sizes: &QuerySizes) -> Result<(), SynthesisError> {
+        // Inputs precede the auxiliary variables in the A and B queries, so they can't be
+        // removed, and the h query depends on the evaluation domain.
+        for &(name, needed, available) in &[("ic", sizes.ic, self.ic), ("h", sizes.h, self.h)] {
+            if needed != available {
+                return Err(SynthesisError::IncompatibleLengthVector(format!(
+                    "the circuit needs {} points of the {} query, but subsets must keep all {}",
+                    needed, name, available
+                )));
+            }
+        }
+
+        for &(name, needed, available) in &[
+            ("l", sizes.l, self.l),
+            ("a", sizes.a, self.a),
+            ("b_g1", sizes.b_g1, self.b_g1),
+            ("b_g2", sizes.b_g2, self.b_g2),
+        ] {
+            if needed > available {
+                return Err(SynthesisError::IncompatibleLengthVector(format!(
+                    "the circuit needs {} points of the {} query, but the parameters only have {}",
+                    needed, name, available
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Which variables of a circuit have a point in the A and B queries of its parameters, and
+/// the digest of the circuit. The inputs always have a point in the A query.
+///
+/// Parameters can only be cut down to those of a smaller circuit if its densities are a
+/// prefix of theirs, see [`Parameters::subset`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryDensities {
+    pub(crate) sizes: QuerySizes,
+    pub(crate) a_aux_density: DensityTracker,
+    pub(crate) b_input_density: DensityTracker,
+    pub(crate) b_aux_density: DensityTracker,
+    pub(crate) circuit_digest: CircuitDigest,
+}
+
+impl QueryDensities {
+    /// The densities of the queries of parameters generated for `circuit`. The circuit is
+    /// only synthesized, without a witness.
+    pub fn of_circuit<Scalar, C>(circuit: C) -> Result<Self, SynthesisError>
+    where
+        Scalar: PrimeField,
+        C: Circuit<Scalar>,
+    {
+        let assembly = synthesize_assembly(circuit)?;
+
+        // The evaluation domain, as computed by `EvaluationDomain::from_coeffs`.
+        let mut m = 1;
+        let mut exp = 0;
+        while m < assembly.num_constraints {
+            m *= 2;
+            exp += 1;
+            if exp >= Scalar::S {
+                return Err(SynthesisError::PolynomialDegreeTooLarge);
+            }
+        }
+
+        // Variables which don't occur in A or B have no point in the respective query.
+        let density = |lcs: &[Vec<(Scalar, usize)>]| {
+            let mut density = DensityTracker::new();
+            for (i, lc) in lcs.iter().enumerate() {
+                density.add_element();
+                if !lc.is_empty() {
+                    density.inc(i);
+                }
+            }
+            density
+        };
+        let a_input_density = density(&assembly.at_inputs);
+        let a_aux_density = density(&assembly.at_aux);
+        let b_input_density = density(&assembly.bt_inputs);
+        let b_aux_density = density(&assembly.bt_aux);
+        let b = b_input_density.get_total_density() + b_aux_density.get_total_density();
+
+        let circuit_digest = assembly
+            .circuit_digest
+            .ok_or(SynthesisError::CircuitDigestUnavailable)?;
+
+        Ok(QueryDensities {
+            sizes: QuerySizes {
+                ic: assembly.num_inputs,
+                h: m - 1,
+                l: assembly.num_aux,
+                a: a_input_density.get_total_density() + a_aux_density.get_total_density(),
+                b_g1: b,
+                b_g2: b,
+            },
+            a_aux_density,
+            b_input_density,
+            b_aux_density,
+            circuit_digest,
+        })
+    }
+
+    /// The sizes of the queries.
+    pub fn sizes(&self) -> &QuerySizes {
+        &self.sizes
+    }
+
+    /// The digest of the circuit.
+    pub fn circuit_digest(&self) -> CircuitDigest {
+        self.circuit_digest
+    }
+
+    /// Checks that these densities belong to parameters with the query sizes `sizes` and
+    /// the circuit digest `circuit_digest`, if they record one.
+    pub(crate) fn check_params(
+        &self,
+        sizes: &QuerySizes,
+        circuit_digest: Option<CircuitDigest>,
+    ) -> Result<(), SynthesisError> {
+        if let Some(expected) = circuit_digest {
+            if expected != self.circuit_digest {
+                return Err(SynthesisError::CircuitDigestMismatch {
+                    expected,
+                    actual: self.circuit_digest,
+                });
+            }
+        }
+        if *sizes != self.sizes {
+            return Err(SynthesisError::IncompatibleLengthVector(format!(
+                "the parameters have query sizes {:?}, but the source circuit needs {:?}",
+                sizes, self.sizes
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `subset` describes a circuit whose queries are a prefix of these: it has
+    /// the same inputs and evaluation domain, and its auxiliary variables occur in the A and
+    /// B queries exactly where the first auxiliary variables of this circuit do.
+    pub(crate) fn check_subset(&self, subset: &QueryDensities) -> Result<(), SynthesisError> {
+        self.sizes.check_subset(&subset.sizes)?;
+
+        for &(name, needed, available) in &[
+            ("b input", &subset.b_input_density, &self.b_input_density),
+            ("a auxiliary", &subset.a_aux_density, &self.a_aux_density),
+            ("b auxiliary", &subset.b_aux_density, &self.b_aux_density),
+        ] {
+            if needed.bv[..] != available.bv[..needed.bv.len()] {
+                return Err(SynthesisError::IncompatibleLengthVector(format!(
+                    "the {} density of the circuit isn't a prefix of the parameters'",
+                    name
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A problem found by [`Parameters::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationIssue {
+    /// A query doesn't have the number of points the circuit needs.
+    LengthMismatch {
+        query: &'static str,
+        expected: usize,
+        actual: usize,
+    },
+    /// A query contains points at infinity, which valid parameters never do.
+    IdentityPoints {
+        query: &'static str,
+        count: usize,
+        first_index: usize,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::LengthMismatch {
+                query,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the {} query has {} points, but the circuit needs {}",
+                query, actual, expected
+            ),
+            ValidationIssue::IdentityPoints {
+                query,
+                count,
+                first_index,
+            } => write!(
+                f,
+                "the {} query contains {} points at infinity, the first at index {}",
+                query, count, first_index
+            ),
+        }
+    }
+}
+
+/// The result of [`Parameters::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// The query sizes the circuit needs.
+    pub expected: QuerySizes,
+    /// The query sizes of the parameters.
+    pub actual: QuerySizes,
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Whether the parameters match the circuit.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
 impl<E> Parameters<E>
 where
     E: MultiMillerLoop,
 {
-    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
-        self.vk.write(&mut writer)?;
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_body(writer, PointEncoding::Uncompressed)
+    }
 
-        writer.write_u32::<BigEndian>(self.h.len() as u32)?;
-        for g in &self.h[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
-        }
+    /// Writes the parameters preceded by a [`ParameterHeader`], which identifies the file
+    /// format and curve and allows readers to detect corrupted or truncated files.
+    ///
+    /// Computing the digest of the header requires serializing the parameters twice.
+    pub fn write_with_header<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        E: CurveName,
+    {
+        self.write_with_encoding(writer, PointEncoding::Uncompressed)
+    }
 
-        writer.write_u32::<BigEndian>(self.l.len() as u32)?;
-        for g in &self.l[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
-        }
+    /// Writes the parameters with a [`ParameterHeader`] like `write_with_header`, but with
+    /// compressed points, which halves the size of the file. Loading compressed parameters
+    /// is slower, as every point has to be decompressed.
+    pub fn write_compressed<W: Write>(&self, writer: W) -> io::Result<()>
+    where
+        E: CurveName,
+    {
+        self.write_with_encoding(writer, PointEncoding::Compressed)
+    }
 
-        writer.write_u32::<BigEndian>(self.a.len() as u32)?;
-        for g in &self.a[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
+    fn write_with_encoding<W: Write>(
+        &self,
+        mut writer: W,
+        encoding: PointEncoding,
+    ) -> io::Result<()>
+    where
+        E: CurveName,
+    {
+        let mut hasher = HashWriter::new();
+        self.write_body(&mut hasher, encoding)?;
+
+        let header = ParameterHeader::new::<E>(
+            self.section_lengths(encoding),
+            hasher.finalize(),
+            encoding,
+            self.circuit_digest,
+        );
+        header.write(&mut writer)?;
+        self.write_body(&mut writer, encoding)
+    }
+
+    fn write_body<W: Write>(&self, mut writer: W, encoding: PointEncoding) -> io::Result<()> {
+        match encoding {
+            PointEncoding::Uncompressed => self.vk.write(&mut writer)?,
+            PointEncoding::Compressed => self.vk.write_compressed(&mut writer)?,
         }
 
-        writer.write_u32::<BigEndian>(self.b_g1.len() as u32)?;
-        for g in &self.b_g1[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
+        write_points(&mut writer, &self.h, encoding)?;
+        write_points(&mut writer, &self.l, encoding)?;
+        write_points(&mut writer, &self.a, encoding)?;
+        write_points(&mut writer, &self.b_g1, encoding)?;
+        write_points(&mut writer, &self.b_g2, encoding)?;
+
+        Ok(())
+    }
+
+    /// The lengths of the sections of the body in bytes.
+    fn section_lengths(&self, encoding: PointEncoding) -> [u64; NUM_PARAMETER_SECTIONS] {
+        let u32_len = mem::size_of::<u32>();
+        let g1_len = encoding.point_size::<E::G1Affine>();
+        let g2_len = encoding.point_size::<E::G2Affine>();
+
+        let vk_len = 3 * g1_len + 3 * g2_len + u32_len + self.vk.ic.len() * g1_len;
+        [
+            vk_len as u64,
+            (u32_len + self.h.len() * g1_len) as u64,
+            (u32_len + self.l.len() * g1_len) as u64,
+            (u32_len + self.a.len() * g1_len) as u64,
+            (u32_len + self.b_g1.len() * g1_len) as u64,
+            (u32_len + self.b_g2.len() * g2_len) as u64,
+        ]
+    }
+}
+
+impl<E> Parameters<E>
+where
+    E: MultiMillerLoop,
+{
+    pub fn query_sizes(&self) -> QuerySizes {
+        QuerySizes {
+            ic: self.vk.ic.len(),
+            h: self.h.len(),
+            l: self.l.len(),
+            a: self.a.len(),
+            b_g1: self.b_g1.len(),
+            b_g2: self.b_g2.len(),
         }
+    }
 
-        writer.write_u32::<BigEndian>(self.b_g2.len() as u32)?;
-        for g in &self.b_g2[..] {
-            writer.write_all(g.to_uncompressed().as_ref())?;
+    /// Extracts the parameters of a smaller circuit with the query densities `circuit` from
+    /// these parameters of the circuit with the densities `source`, see
+    /// [`QueryDensities::of_circuit`], by truncating every query. Fails if `source` doesn't
+    /// match these parameters, or if the densities of `circuit` aren't a prefix of those of
+    /// `source`. The subset records the digest of `circuit`.
+    ///
+    /// The result is only valid for a circuit which equals the circuit of these parameters
+    /// with its trailing auxiliary variables, and all terms involving them, removed.
+    /// Constraints which only involve removed variables stay as `0 * 0 = 0`, so that both
+    /// circuits have the same evaluation domain.
+    pub fn subset(
+        &self,
+        source: &QueryDensities,
+        circuit: &QueryDensities,
+    ) -> Result<Self, SynthesisError> {
+        source.check_params(&self.query_sizes(), self.circuit_digest)?;
+        source.check_subset(circuit)?;
+        let sizes = &circuit.sizes;
+
+        Ok(Parameters {
+            vk: self.vk.clone(),
+            circuit_digest: Some(circuit.circuit_digest),
+            h: self.h.clone(),
+            l: Arc::new(self.l[..sizes.l].to_vec()),
+            a: Arc::new(self.a[..sizes.a].to_vec()),
+            b_g1: Arc::new(self.b_g1[..sizes.b_g1].to_vec()),
+            b_g2: Arc::new(self.b_g2[..sizes.b_g2].to_vec()),
+        })
+    }
+
+    /// Checks that these parameters belong to `circuit`: every query has the size the
+    /// circuit needs, see [`QuerySizes::of_circuit`], and no query contains a point at
+    /// infinity. Curve and subgroup membership of the points is checked when reading them.
+    ///
+    /// Errors are only returned if the circuit can't be synthesized; mismatches are listed
+    /// in the report.
+    pub fn validate<C>(&self, circuit: &C) -> Result<ValidationReport, SynthesisError>
+    where
+        C: Circuit<E::Fr> + Clone,
+    {
+        let expected = QuerySizes::of_circuit(circuit.clone())?;
+        let actual = self.query_sizes();
+
+        let mut issues = vec![];
+        for &(query, expected, actual) in &[
+            ("ic", expected.ic, actual.ic),
+            ("h", expected.h, actual.h),
+            ("l", expected.l, actual.l),
+            ("a", expected.a, actual.a),
+            ("b_g1", expected.b_g1, actual.b_g1),
+            ("b_g2", expected.b_g2, actual.b_g2),
+        ] {
+            if expected != actual {
+                issues.push(ValidationIssue::LengthMismatch {
+                    query,
+                    expected,
+                    actual,
+                });
+            }
         }
 
-        Ok(())
+        // The points of the verifying key other than IC, in the order they are serialized.
+        let vk = &self.vk;
+        let vk_g1 = [vk.alpha_g1, vk.beta_g1, vk.delta_g1];
+        let vk_g2 = [vk.beta_g2, vk.gamma_g2, vk.delta_g2];
+        issues.extend(identity_points("vk_g1", &vk_g1[..]));
+        issues.extend(identity_points("vk_g2", &vk_g2[..]));
+        issues.extend(identity_points("ic", &vk.ic));
+        issues.extend(identity_points("h", &self.h));
+        issues.extend(identity_points("l", &self.l));
+        issues.extend(identity_points("a", &self.a));
+        issues.extend(identity_points("b_g1", &self.b_g1));
+        issues.extend(identity_points("b_g2", &self.b_g2));
+
+        Ok(ValidationReport {
+            expected,
+            actual,
+            issues,
+        })
     }
+}
 
+impl<E> Parameters<E>
+where
+    E: MultiMillerLoop,
+{
     // Quickly iterates through the parameter file, recording all
     // parameter offsets and caches the verifying key (vk) for quick
     // access via reference.
+    //
+    // If the file has a header, it is validated against the file, but
+    // the digest of the whole file is only checked if `checked` is set.
     pub fn build_mapped_parameters(
         param_file_path: PathBuf,
         checked: bool,
     ) -> io::Result<MappedParameters<E>> {
-        let mut offset: usize = 0;
         let param_file = File::open(&param_file_path)?;
         let params = unsafe { MmapOptions::new().map(&param_file)? };
 
-        let u32_len = mem::size_of::<u32>();
-        let g1_len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
-        let g2_len = mem::size_of::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
-
-        let read_length = |params: &Mmap, offset: &mut usize| -> Result<usize, std::io::Error> {
-            let mut raw_len = &params[*offset..*offset + u32_len];
-            *offset += u32_len;
+        let header = read_mmap_header(&params, checked)?;
+        let encoding = header
+            .as_ref()
+            .map_or(PointEncoding::Uncompressed, |header| header.encoding());
+        let mut offset = header.as_ref().map_or(0, |header| header.size());
 
-            match raw_len.read_u32::<BigEndian>() {
-                Ok(len) => Ok(len as usize),
-                Err(err) => Err(err),
-            }
-        };
+        let g1_len = encoding.point_size::<E::G1Affine>();
+        let g2_len = encoding.point_size::<E::G2Affine>();
 
         let get_offsets = |params: &Mmap,
                            offset: &mut usize,
@@ -134,7 +539,8 @@ where
             Ok(())
         };
 
-        let vk = VerifyingKey::<E>::read_mmap(&params, &mut offset)?;
+        let vk = read_mmap_vk::<E>(&params, &mut offset, encoding)?;
+        check_section_end(&header, 0, offset)?;
 
         let mut h = vec![];
         let mut l = vec![];
@@ -143,17 +549,29 @@ where
         let mut b_g2 = vec![];
 
         get_offsets(&params, &mut offset, &mut h, g1_len)?;
+        check_section_end(&header, 1, offset)?;
         get_offsets(&params, &mut offset, &mut l, g1_len)?;
+        check_section_end(&header, 2, offset)?;
         get_offsets(&params, &mut offset, &mut a, g1_len)?;
+        check_section_end(&header, 3, offset)?;
         get_offsets(&params, &mut offset, &mut b_g1, g1_len)?;
+        check_section_end(&header, 4, offset)?;
         get_offsets(&params, &mut offset, &mut b_g2, g2_len)?;
+        check_section_end(&header, 5, offset)?;
 
         let pvk = super::prepare_verifying_key(&vk);
+        let caches = QueryCaches {
+            h: QueryCache::new(h.len()),
+            l: QueryCache::new(l.len()),
+            a: QueryCache::new(a.len()),
+            b_g1: QueryCache::new(b_g1.len()),
+            b_g2: QueryCache::new(b_g2.len()),
+        };
 
         Ok(MappedParameters {
             param_file_path,
             param_file,
-            params,
+            params: Arc::new(params),
             vk,
             pvk,
             h,
@@ -162,6 +580,9 @@ where
             b_g1,
             b_g2,
             checked,
+            encoding,
+            circuit_digest: header.and_then(|header| header.circuit_digest),
+            caches,
         })
     }
 
@@ -169,122 +590,99 @@ where
     // advantageous to use (can be called by read_cached_params in
     // rust-fil-proofs repo).  It's equivalent to the existing read
     // method, in that it loads all parameters to RAM.
+    //
+    // As with `build_mapped_parameters`, the digest of a header is
+    // only checked if `checked` is set.
     pub fn read_mmap(mmap: &Mmap, checked: bool) -> io::Result<Self> {
-        let u32_len = mem::size_of::<u32>();
-        let g1_len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
-        let g2_len = mem::size_of::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
-
-        let read_g1 = |mmap: &Mmap, offset: &mut usize| -> io::Result<E::G1Affine> {
-            let ptr = &mmap[*offset..*offset + g1_len];
-            *offset += g1_len;
-            // Safety: this operation is safe, because it's simply
-            // casting to a known struct at the correct offset, given
-            // the structure of the on-disk data.
-            let repr = unsafe {
-                &*(ptr as *const [u8] as *const <E::G1Affine as UncompressedEncoding>::Uncompressed)
-            };
-
-            let affine: E::G1Affine = {
-                let affine_opt = if checked {
-                    E::G1Affine::from_uncompressed(&repr)
-                } else {
-                    E::G1Affine::from_uncompressed_unchecked(&repr)
-                };
-                Option::from(affine_opt)
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
-            }?;
-
-            if affine.is_identity().into() {
-                Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "point at infinity",
-                ))
-            } else {
-                Ok(affine)
-            }
-        };
-
-        let read_g2 = |mmap: &Mmap, offset: &mut usize| -> io::Result<E::G2Affine> {
-            let ptr = &mmap[*offset..*offset + g2_len];
-            *offset += g2_len;
-            // Safety: this operation is safe, because it's simply
-            // casting to a known struct at the correct offset, given
-            // the structure of the on-disk data.
-            let repr = unsafe {
-                &*(ptr as *const [u8] as *const <E::G2Affine as UncompressedEncoding>::Uncompressed)
-            };
-
-            let affine: E::G2Affine = {
-                let affine_opt = if checked {
-                    E::G2Affine::from_uncompressed(&repr)
-                } else {
-                    E::G2Affine::from_uncompressed_unchecked(&repr)
-                };
-                Option::from(affine_opt)
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
-            }?;
-
-            if affine.is_identity().into() {
-                Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "point at infinity",
-                ))
-            } else {
-                Ok(affine)
-            }
-        };
+        let header = read_mmap_header(mmap, checked)?;
+        let encoding = header
+            .as_ref()
+            .map_or(PointEncoding::Uncompressed, |header| header.encoding());
+
+        let mut offset = header.as_ref().map_or(0, |header| header.size());
+        let vk = read_mmap_vk::<E>(&mmap, &mut offset, encoding)?;
+        check_section_end(&header, 0, offset)?;
+
+        let h = read_mmap_points(&mmap, &mut offset, checked, encoding)?;
+        check_section_end(&header, 1, offset)?;
+        let l = read_mmap_points(&mmap, &mut offset, checked, encoding)?;
+        check_section_end(&header, 2, offset)?;
+        let a = read_mmap_points(&mmap, &mut offset, checked, encoding)?;
+        check_section_end(&header, 3, offset)?;
+        let b_g1 = read_mmap_points(&mmap, &mut offset, checked, encoding)?;
+        check_section_end(&header, 4, offset)?;
+        let b_g2 = read_mmap_points(&mmap, &mut offset, checked, encoding)?;
+        check_section_end(&header, 5, offset)?;
 
-        let read_length = |mmap: &Mmap, offset: &mut usize| -> Result<usize, std::io::Error> {
-            let mut raw_len = &mmap[*offset..*offset + u32_len];
-            *offset += u32_len;
+        Ok(Parameters {
+            vk,
+            circuit_digest: header.and_then(|header| header.circuit_digest),
+            h: Arc::new(h),
+            l: Arc::new(l),
+            a: Arc::new(a),
+            b_g1: Arc::new(b_g1),
+            b_g2: Arc::new(b_g2),
+        })
+    }
 
-            match raw_len.read_u32::<BigEndian>() {
-                Ok(len) => Ok(len as usize),
-                Err(err) => Err(err),
-            }
-        };
+    /// Reads parameters, with or without a [`ParameterHeader`]. If there is a header, it is
+    /// validated and the digest is checked once all parameters are read. The curve recorded in
+    /// the header isn't checked, see [`ParameterHeader::validate_format`].
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let mut magic = [0u8; PARAMETERS_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != PARAMETERS_MAGIC {
+            // A legacy file without header, the bytes read so far belong to the verifying key.
+            return Self::read_body(&mut (&magic[..]).chain(reader), checked);
+        }
 
-        let get_g1s = |mmap: &Mmap,
-                       offset: &mut usize,
-                       param: &mut Vec<E::G1Affine>|
-         -> Result<(), std::io::Error> {
-            let len = read_length(&mmap, &mut *offset)?;
-            for _ in 0..len {
-                (*param).push(read_g1(&mmap, &mut *offset)?);
-            }
+        let header = ParameterHeader::read_after_magic(&mut reader)?;
+        header.validate_format(None)?;
+
+        let mut reader = HashReader::new(reader);
+        let mut params = Self::read_body_with_encoding(&mut reader, checked, header.encoding())?;
+        let (len, digest) = reader.finalize();
+        if len != header.body().end - header.body().start {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "parameter file body doesn't match the header",
+            ));
+        }
+        header.check_digest(&digest)?;
+        params.circuit_digest = header.circuit_digest;
 
-            Ok(())
-        };
+        Ok(params)
+    }
+}
 
-        let get_g2s = |mmap: &Mmap,
-                       offset: &mut usize,
-                       param: &mut Vec<E::G2Affine>|
-         -> Result<(), std::io::Error> {
-            let len = read_length(&mmap, &mut *offset)?;
-            for _ in 0..len {
-                (*param).push(read_g2(&mmap, &mut *offset)?);
-            }
+impl<E> Parameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Reads parameters as written by `write`, i.e. without a header.
+    pub(crate) fn read_body<R: Read>(reader: R, checked: bool) -> io::Result<Self> {
+        Self::read_body_with_encoding(reader, checked, PointEncoding::Uncompressed)
+    }
 
-            Ok(())
+    fn read_body_with_encoding<R: Read>(
+        mut reader: R,
+        checked: bool,
+        encoding: PointEncoding,
+    ) -> io::Result<Self> {
+        let vk = match encoding {
+            PointEncoding::Uncompressed => VerifyingKey::<E>::read(&mut reader)?,
+            PointEncoding::Compressed => VerifyingKey::<E>::read_compressed(&mut reader)?,
         };
 
-        let mut offset: usize = 0;
-        let vk = VerifyingKey::<E>::read_mmap(&mmap, &mut offset)?;
-
-        let mut h = vec![];
-        let mut l = vec![];
-        let mut a = vec![];
-        let mut b_g1 = vec![];
-        let mut b_g2 = vec![];
-
-        get_g1s(&mmap, &mut offset, &mut h)?;
-        get_g1s(&mmap, &mut offset, &mut l)?;
-        get_g1s(&mmap, &mut offset, &mut a)?;
-        get_g1s(&mmap, &mut offset, &mut b_g1)?;
-        get_g2s(&mmap, &mut offset, &mut b_g2)?;
+        let h = read_points(&mut reader, checked, encoding)?;
+        let l = read_points(&mut reader, checked, encoding)?;
+        let a = read_points(&mut reader, checked, encoding)?;
+        let b_g1 = read_points(&mut reader, checked, encoding)?;
+        let b_g2 = read_points(&mut reader, checked, encoding)?;
 
         Ok(Parameters {
             vk,
+            circuit_digest: None,
             h: Arc::new(h),
             l: Arc::new(l),
             a: Arc::new(a),
@@ -292,106 +690,176 @@ where
             b_g2: Arc::new(b_g2),
         })
     }
+}
 
-    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
-        let read_g1 = |reader: &mut R| -> io::Result<E::G1Affine> {
-            let mut repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(repr.as_mut())?;
-
-            let affine: E::G1Affine = {
-                let affine_opt = if checked {
-                    E::G1Affine::from_uncompressed(&repr)
-                } else {
-                    E::G1Affine::from_uncompressed_unchecked(&repr)
-                };
-                Option::from(affine_opt)
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
-            }?;
-
-            if affine.is_identity().into() {
-                Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "point at infinity",
-                ))
-            } else {
-                Ok(affine)
-            }
-        };
+/// Reports the points at infinity in `points`, if there are any.
+fn identity_points<G: PrimeCurveAffine>(
+    query: &'static str,
+    points: &[G],
+) -> Option<ValidationIssue> {
+    let is_identity = |p: &G| bool::from(p.is_identity());
+    let first_index = points.par_iter().position_first(is_identity)?;
+    let count = points[first_index..]
+        .par_iter()
+        .filter(|p| is_identity(p))
+        .count();
+
+    Some(ValidationIssue::IdentityPoints {
+        query,
+        count,
+        first_index,
+    })
+}
 
-        let read_g2 = |reader: &mut R| -> io::Result<E::G2Affine> {
-            let mut repr = <E::G2Affine as UncompressedEncoding>::Uncompressed::default();
-            reader.read_exact(repr.as_mut())?;
-
-            let affine: E::G2Affine = {
-                let affine_opt = if checked {
-                    E::G2Affine::from_uncompressed(&repr)
-                } else {
-                    E::G2Affine::from_uncompressed_unchecked(&repr)
-                };
-                Option::from(affine_opt)
-                    .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
-            }?;
-
-            if affine.is_identity().into() {
-                Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "point at infinity",
-                ))
-            } else {
-                Ok(affine)
-            }
-        };
+/// Reads parameters in either encoding and writes them with a [`ParameterHeader`] in
+/// `encoding`, e.g. to compress an existing parameter file.
+///
+/// `checked` is passed on to [`Parameters::read`]; when the source is trusted, skipping the
+/// subgroup checks makes the conversion considerably faster.
+pub fn convert_parameters<E, R, W>(
+    reader: R,
+    writer: W,
+    encoding: PointEncoding,
+    checked: bool,
+) -> io::Result<()>
+where
+    E: MultiMillerLoop + CurveName,
+    R: Read,
+    W: Write,
+{
+    let params = Parameters::<E>::read(reader, checked)?;
+    params.write_with_encoding(writer, encoding)
+}
 
-        let vk = VerifyingKey::<E>::read(&mut reader)?;
+/// Number of points which are read at once and then decoded in parallel by `read_points`.
+const READ_CHUNK_SIZE: usize = 1 << 16;
 
-        let mut h = vec![];
-        let mut l = vec![];
-        let mut a = vec![];
-        let mut b_g1 = vec![];
-        let mut b_g2 = vec![];
+fn write_points<G, W>(mut writer: W, points: &[G], encoding: PointEncoding) -> io::Result<()>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    W: Write,
+{
+    writer.write_u32::<BigEndian>(points.len() as u32)?;
+    for g in points {
+        encoding.write_point(g, &mut writer)?;
+    }
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                h.push(read_g1(&mut reader)?);
-            }
-        }
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                l.push(read_g1(&mut reader)?);
-            }
-        }
+    Ok(())
+}
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                a.push(read_g1(&mut reader)?);
-            }
-        }
+/// Reads a length prefixed list of points, decoding them in parallel.
+fn read_points<G, R>(mut reader: R, checked: bool, encoding: PointEncoding) -> io::Result<Vec<G>>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    R: Read,
+{
+    let len = reader.read_u32::<BigEndian>()? as usize;
+    let point_size = encoding.point_size::<G>();
+
+    // The length isn't trusted for allocations before the points were actually read.
+    let mut points = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+    let mut buf = vec![0u8; len.min(READ_CHUNK_SIZE) * point_size];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk_len = remaining.min(READ_CHUNK_SIZE);
+        let buf = &mut buf[..chunk_len * point_size];
+        reader.read_exact(buf)?;
+
+        let chunk = buf
+            .par_chunks(point_size)
+            .map(|bytes| encoding.read_point(bytes, checked))
+            .collect::<io::Result<Vec<G>>>()?;
+        points.extend(chunk);
+        remaining -= chunk_len;
+    }
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                b_g1.push(read_g1(&mut reader)?);
-            }
-        }
+    Ok(points)
+}
 
-        {
-            let len = reader.read_u32::<BigEndian>()? as usize;
-            for _ in 0..len {
-                b_g2.push(read_g2(&mut reader)?);
-            }
+fn read_length(mmap: &Mmap, offset: &mut usize) -> io::Result<usize> {
+    let u32_len = mem::size_of::<u32>();
+    if *offset + u32_len > mmap.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "parameter file is truncated",
+        ));
+    }
+    let mut raw_len = &mmap[*offset..*offset + u32_len];
+    *offset += u32_len;
+
+    Ok(raw_len.read_u32::<BigEndian>()? as usize)
+}
+
+/// Reads a length prefixed list of points from a memory mapped file, decoding them in
+/// parallel.
+fn read_mmap_points<G>(
+    mmap: &Mmap,
+    offset: &mut usize,
+    checked: bool,
+    encoding: PointEncoding,
+) -> io::Result<Vec<G>>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    let len = read_length(mmap, offset)?;
+    let end = *offset + len * encoding.point_size::<G>();
+    if end > mmap.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "parameter file is truncated",
+        ));
+    }
+
+    let points = mmap[*offset..end]
+        .par_chunks(encoding.point_size::<G>())
+        .map(|bytes| encoding.read_point(bytes, checked))
+        .collect::<io::Result<Vec<G>>>()?;
+    *offset = end;
+
+    Ok(points)
+}
+
+fn read_mmap_vk<E: MultiMillerLoop>(
+    mmap: &Mmap,
+    offset: &mut usize,
+    encoding: PointEncoding,
+) -> io::Result<VerifyingKey<E>> {
+    match encoding {
+        PointEncoding::Uncompressed => VerifyingKey::<E>::read_mmap(mmap, offset),
+        PointEncoding::Compressed => {
+            let mut reader = mmap.get(*offset..).unwrap_or(&[]);
+            let vk = VerifyingKey::<E>::read_compressed(&mut reader)?;
+            *offset = mmap.len() - reader.len();
+            Ok(vk)
         }
+    }
+}
 
-        Ok(Parameters {
-            vk,
-            h: Arc::new(h),
-            l: Arc::new(l),
-            a: Arc::new(a),
-            b_g1: Arc::new(b_g1),
-            b_g2: Arc::new(b_g2),
-        })
+/// Reads and validates the header of a memory mapped parameter file, if there is one.
+/// Checking the digest requires reading the whole file.
+fn read_mmap_header(mmap: &Mmap, check_digest: bool) -> io::Result<Option<ParameterHeader>> {
+    let header = match ParameterHeader::from_bytes(mmap)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    header.validate_format(Some(mmap.len() as u64))?;
+
+    if check_digest {
+        let body = header.body();
+        header.check_digest(&body_digest(&mmap[body.start as usize..body.end as usize]))?;
+    }
+
+    Ok(Some(header))
+}
+
+fn check_section_end(
+    header: &Option<ParameterHeader>,
+    section: usize,
+    offset: usize,
+) -> io::Result<()> {
+    match header {
+        Some(header) => header.check_section_end(section, offset as u64),
+        None => Ok(()),
     }
 }
 
@@ -420,6 +888,12 @@ where
         num_inputs: usize,
         num_aux: usize,
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError>;
+
+    /// The digest of the circuit the parameters were generated for, which the prover checks
+    /// against the circuit being proven. Without a digest nothing is checked.
+    fn get_circuit_digest(&self) -> Option<CircuitDigest> {
+        None
+    }
 }
 
 impl<'a, E> ParameterSource<E> for &'a Parameters<E>
@@ -464,4 +938,285 @@ where
     ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
         Ok(((self.b_g2.clone(), 0), (self.b_g2.clone(), num_inputs)))
     }
+
+    fn get_circuit_digest(&self) -> Option<CircuitDigest> {
+        self.circuit_digest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::ConstraintSystem;
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    const EXTRA_STEPS: usize = 3;
+
+    // Proves knowledge of a cube root of `y`. The full circuit additionally squares an
+    // unrelated value a few times; the prefix circuit has the same constraints with all
+    // terms of those trailing variables removed.
+    #[derive(Clone)]
+    struct PrefixCircuit {
+        x: Option<Fr>,
+        full: bool,
+    }
+
+    impl Circuit<Fr> for PrefixCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x_val = self.x;
+            let x = cs.alloc(|| "x", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let x2 = cs.alloc(
+                || "x^2",
+                || {
+                    x_val
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    x_val
+                        .map(|x| x.square() * x)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = x^2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x^2 * x = y", |lc| lc + x2, |lc| lc + x, |lc| lc + y);
+
+            if !self.full {
+                for i in 0..EXTRA_STEPS {
+                    cs.enforce(|| format!("trivial {}", i), |lc| lc, |lc| lc, |lc| lc);
+                }
+                return Ok(());
+            }
+
+            let mut z_val = x_val.map(|x| x + Fr::one());
+            let mut z = cs.alloc(|| "z", || z_val.ok_or(SynthesisError::AssignmentMissing))?;
+            for i in 0..EXTRA_STEPS {
+                let next_val = z_val.map(|z| z.square());
+                let next = cs.alloc(
+                    || format!("z {}", i + 1),
+                    || next_val.ok_or(SynthesisError::AssignmentMissing),
+                )?;
+                cs.enforce(
+                    || format!("square {}", i),
+                    |lc| lc + z,
+                    |lc| lc + z,
+                    |lc| lc + next,
+                );
+                z = next;
+                z_val = next_val;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parameters_subset() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let full = PrefixCircuit {
+            x: None,
+            full: true,
+        };
+        let params = generate_random_parameters::<Bls12, _, _>(full.clone(), rng).unwrap();
+        let full_densities = QueryDensities::of_circuit(full).unwrap();
+        let full_sizes = *full_densities.sizes();
+        assert_eq!(full_sizes, params.query_sizes());
+
+        let prefix = PrefixCircuit {
+            x: None,
+            full: false,
+        };
+        let densities = QueryDensities::of_circuit(prefix.clone()).unwrap();
+        let sizes = *densities.sizes();
+        assert_eq!(sizes.ic, full_sizes.ic);
+        assert_eq!(sizes.l, full_sizes.l - EXTRA_STEPS - 1);
+        assert!(sizes.a < full_sizes.a);
+
+        let subset = params.subset(&full_densities, &densities).unwrap();
+        assert_eq!(subset.query_sizes(), sizes);
+        assert_eq!(
+            subset.circuit_digest,
+            Some(CircuitDigest::of_circuit(prefix).unwrap())
+        );
+        assert!(subset.subset(&densities, &full_densities).is_err());
+        // The source densities must be those of the parameters.
+        assert!(params.subset(&densities, &densities).is_err());
+
+        // The prefix circuit can be proven with the subset alone.
+        let pvk = prepare_verifying_key(&subset.vk);
+        let x = Fr::random(&mut *rng);
+        let proof = create_random_proof(
+            PrefixCircuit {
+                x: Some(x),
+                full: false,
+            },
+            &subset,
+            &mut *rng,
+        )
+        .unwrap();
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[x.square()]).unwrap());
+
+        // Mapped parameters write the same subset, in their own encoding.
+        for &compressed in &[false, true] {
+            let mut file = tempfile::NamedTempFile::new().unwrap();
+            if compressed {
+                params.write_compressed(&mut file).unwrap();
+            } else {
+                params.write_with_header(&mut file).unwrap();
+            }
+            file.flush().unwrap();
+            let mapped =
+                Parameters::<Bls12>::build_mapped_parameters(file.path().to_path_buf(), true)
+                    .unwrap();
+            assert_eq!(mapped.query_sizes(), full_sizes);
+
+            let mut bytes = vec![];
+            mapped
+                .write_subset(&full_densities, &densities, &mut bytes)
+                .unwrap();
+            let header = ParameterHeader::read(&bytes[..]).unwrap();
+            assert_eq!(header.encoding(), mapped.encoding);
+            assert_eq!(header.circuit_digest, subset.circuit_digest);
+            assert!(Parameters::<Bls12>::read(&bytes[..], true).unwrap() == subset);
+
+            assert!(mapped
+                .write_subset(&full_densities, &full_densities, &mut vec![])
+                .is_ok());
+            assert!(mapped
+                .write_subset(&densities, &densities, &mut vec![])
+                .is_err());
+        }
+    }
+
+    // Constrains `u * v = u`, or `v * u = u` if swapped: both circuits have the same query
+    // sizes, but `u` and `v` occur in different queries.
+    #[derive(Clone)]
+    struct SwapCircuit {
+        swapped: bool,
+    }
+
+    impl Circuit<Fr> for SwapCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let u = cs.alloc(|| "u", || Ok(Fr::one()))?;
+            let v = cs.alloc(|| "v", || Ok(Fr::one()))?;
+            let (a, b) = if self.swapped { (v, u) } else { (u, v) };
+            cs.enforce(|| "a * b = u", |lc| lc + a, |lc| lc + b, |lc| lc + u);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parameters_subset_densities() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let source = SwapCircuit { swapped: false };
+        let params = generate_random_parameters::<Bls12, _, _>(source.clone(), rng).unwrap();
+        let source = QueryDensities::of_circuit(source).unwrap();
+        let swapped = QueryDensities::of_circuit(SwapCircuit { swapped: true }).unwrap();
+        assert_eq!(source.sizes(), swapped.sizes());
+        assert_ne!(source.circuit_digest(), swapped.circuit_digest());
+
+        assert!(params.subset(&source, &source).unwrap() == params);
+        match params.subset(&source, &swapped) {
+            Err(SynthesisError::IncompatibleLengthVector(msg)) => {
+                assert!(msg.contains("density"), "{}", msg)
+            }
+            _ => panic!("the subset of a circuit with other densities must fail"),
+        }
+        // The swapped densities don't belong to the parameters.
+        assert!(matches!(
+            params.subset(&swapped, &swapped),
+            Err(SynthesisError::CircuitDigestMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_parameters_validate() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let full = PrefixCircuit {
+            x: None,
+            full: true,
+        };
+        let prefix = PrefixCircuit {
+            x: None,
+            full: false,
+        };
+        let params = generate_random_parameters::<Bls12, _, _>(full.clone(), rng).unwrap();
+
+        let report = params.validate(&full).unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.expected, report.actual);
+
+        // Parameters of another circuit
+        let report = params.validate(&prefix).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.issues[0],
+            ValidationIssue::LengthMismatch {
+                query: "l",
+                expected: 2,
+                actual: 2 + EXTRA_STEPS + 1,
+            }
+        );
+        assert_eq!(
+            report.issues[0].to_string(),
+            "the l query has 6 points, but the circuit needs 2"
+        );
+        assert!(params
+            .subset(
+                &QueryDensities::of_circuit(full.clone()).unwrap(),
+                &QueryDensities::of_circuit(prefix.clone()).unwrap()
+            )
+            .unwrap()
+            .validate(&prefix)
+            .unwrap()
+            .is_valid());
+
+        // Points at infinity
+        let mut corrupted = params.clone();
+        let mut l = (*corrupted.l).clone();
+        l[1] = <Bls12 as pairing::Engine>::G1Affine::identity();
+        l[3] = <Bls12 as pairing::Engine>::G1Affine::identity();
+        corrupted.l = Arc::new(l);
+        corrupted.vk.delta_g2 = <Bls12 as pairing::Engine>::G2Affine::identity();
+        let report = corrupted.validate(&full).unwrap();
+        assert_eq!(
+            report.issues,
+            vec![
+                ValidationIssue::IdentityPoints {
+                    query: "vk_g2",
+                    count: 1,
+                    first_index: 2,
+                },
+                ValidationIssue::IdentityPoints {
+                    query: "l",
+                    count: 2,
+                    first_index: 1,
+                },
+            ]
+        );
+    }
 }
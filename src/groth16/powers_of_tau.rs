@@ -0,0 +1,546 @@
+use std::io::{self, Read, Write};
+use std::ops::AddAssign;
+use std::sync::Arc;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::Field;
+use group::{
+    prime::{PrimeCurve, PrimeCurveAffine},
+    Group, UncompressedEncoding,
+};
+use pairing::{Engine, MultiMillerLoop};
+use rayon::prelude::*;
+
+use super::generator::synthesize_assembly;
+use super::mpc::{merge_pairs, same_ratio};
+use super::{Parameters, VerifyingKey};
+
+use crate::domain::EvaluationDomain;
+use crate::gpu;
+use crate::multicore::Worker;
+use crate::{Circuit, SynthesisError};
+
+/// Upper bound on the number of points allocated for before they were read, as the size of
+/// the powers comes from the file.
+const READ_CHUNK_SIZE: usize = 1 << 16;
+
+/// The output of a phase 1 "powers of tau" ceremony, supporting circuits with up to `size`
+/// constraints (including one per public input).
+#[derive(Clone)]
+pub struct PowersOfTau<E>
+where
+    E: MultiMillerLoop,
+{
+    // tau^i in G1 for i between 0 and 2 * size - 2 inclusive.
+    pub tau_powers_g1: Vec<E::G1Affine>,
+
+    // tau^i in G2 for i between 0 and size - 1 inclusive.
+    pub tau_powers_g2: Vec<E::G2Affine>,
+
+    // alpha * tau^i and beta * tau^i in G1 for i between 0 and size - 1 inclusive.
+    pub alpha_tau_powers_g1: Vec<E::G1Affine>,
+    pub beta_tau_powers_g1: Vec<E::G1Affine>,
+
+    // beta in G2.
+    pub beta_g2: E::G2Affine,
+}
+
+impl<E> PartialEq for PowersOfTau<E>
+where
+    E: MultiMillerLoop,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.tau_powers_g1 == other.tau_powers_g1
+            && self.tau_powers_g2 == other.tau_powers_g2
+            && self.alpha_tau_powers_g1 == other.alpha_tau_powers_g1
+            && self.beta_tau_powers_g1 == other.beta_tau_powers_g1
+            && self.beta_g2 == other.beta_g2
+    }
+}
+
+impl<E> PowersOfTau<E>
+where
+    E: MultiMillerLoop,
+{
+    /// The maximum domain size these powers support.
+    pub fn size(&self) -> usize {
+        self.tau_powers_g2.len()
+    }
+
+    /// Writes the powers, prefixed with their size as a big-endian `u32` and followed by the
+    /// accumulator layout read by [`PowersOfTau::read_accumulator`].
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.size() as u32)?;
+
+        for g in &self.tau_powers_g1 {
+            writer.write_all(g.to_uncompressed().as_ref())?;
+        }
+        for g in &self.tau_powers_g2 {
+            writer.write_all(g.to_uncompressed().as_ref())?;
+        }
+        for g in &self.alpha_tau_powers_g1 {
+            writer.write_all(g.to_uncompressed().as_ref())?;
+        }
+        for g in &self.beta_tau_powers_g1 {
+            writer.write_all(g.to_uncompressed().as_ref())?;
+        }
+        writer.write_all(self.beta_g2.to_uncompressed().as_ref())?;
+
+        Ok(())
+    }
+
+    /// Reads powers written by [`PowersOfTau::write`].
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let size = reader.read_u32::<BigEndian>()? as usize;
+        Self::read_accumulator(reader, size, checked)
+    }
+
+    /// Reads the uncompressed accumulator of a powers of tau ceremony supporting `size`
+    /// constraints: `2 * size - 1` powers of tau in G1, followed by `size` powers of tau in
+    /// G2, `size` powers of `alpha * tau` and `beta * tau` in G1 and finally beta in G2.
+    ///
+    /// Any header preceding the accumulator, such as the hash of the previous transcript,
+    /// must already be consumed from `reader`. `size` must be a power of two, like the sizes
+    /// of evaluation domains.
+    pub fn read_accumulator<R: Read>(
+        mut reader: R,
+        size: usize,
+        checked: bool,
+    ) -> io::Result<Self> {
+        if !size.is_power_of_two() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "size of the powers of tau must be a power of two",
+            ));
+        }
+
+        let tau_powers_g1 = read_points::<E::G1Affine, _>(&mut reader, 2 * size - 1, checked)?;
+        let tau_powers_g2 = read_points::<E::G2Affine, _>(&mut reader, size, checked)?;
+        let alpha_tau_powers_g1 = read_points::<E::G1Affine, _>(&mut reader, size, checked)?;
+        let beta_tau_powers_g1 = read_points::<E::G1Affine, _>(&mut reader, size, checked)?;
+        let beta_g2 = read_points::<E::G2Affine, _>(&mut reader, 1, checked)?[0];
+
+        Ok(PowersOfTau {
+            tau_powers_g1,
+            tau_powers_g2,
+            alpha_tau_powers_g1,
+            beta_tau_powers_g1,
+            beta_g2,
+        })
+    }
+
+    /// Checks that the powers are consistent with each other, i.e. that they were all
+    /// computed from the same alpha, beta and tau.
+    pub fn verify(&self) -> Result<(), SynthesisError> {
+        let size = self.size();
+        if size == 0
+            || self.tau_powers_g1.len() != 2 * size - 1
+            || self.alpha_tau_powers_g1.len() != size
+            || self.beta_tau_powers_g1.len() != size
+        {
+            return Err(SynthesisError::MalformedSrs);
+        }
+
+        let g1 = self.tau_powers_g1[0];
+        let g2 = self.tau_powers_g2[0];
+        if g1 != E::G1Affine::generator() || g2 != E::G2Affine::generator() {
+            return Err(SynthesisError::MalformedSrs);
+        }
+
+        if size > 1 {
+            let tau_g1 = self.tau_powers_g1[1];
+            let tau_g2 = self.tau_powers_g2[1];
+
+            // Ratio between consecutive powers in G1 and G2.
+            let (a, b) = power_pairs(&self.tau_powers_g1);
+            if !same_ratio::<E>((&a, &b), (&g2, &tau_g2)) {
+                return Err(SynthesisError::MalformedSrs);
+            }
+            let (a, b) = power_pairs(&self.tau_powers_g2);
+            if !same_ratio::<E>((&g1, &tau_g1), (&a, &b)) {
+                return Err(SynthesisError::MalformedSrs);
+            }
+
+            // Alpha and beta multiples of the powers in G1.
+            let (a, b) = power_pairs(&self.alpha_tau_powers_g1);
+            if !same_ratio::<E>((&a, &b), (&g2, &tau_g2)) {
+                return Err(SynthesisError::MalformedSrs);
+            }
+            let (a, b) = power_pairs(&self.beta_tau_powers_g1);
+            if !same_ratio::<E>((&a, &b), (&g2, &tau_g2)) {
+                return Err(SynthesisError::MalformedSrs);
+            }
+        }
+
+        if !same_ratio::<E>((&g1, &self.beta_tau_powers_g1[0]), (&g2, &self.beta_g2)) {
+            return Err(SynthesisError::MalformedSrs);
+        }
+        if bool::from(self.alpha_tau_powers_g1[0].is_identity())
+            || bool::from(self.beta_g2.is_identity())
+        {
+            return Err(SynthesisError::MalformedSrs);
+        }
+
+        Ok(())
+    }
+}
+
+/// Create parameters for a circuit from the output of a powers of tau ceremony.
+///
+/// The powers are converted into the Lagrange basis of the circuit's evaluation domain via
+/// an inverse FFT in the exponent. As phase 1 provides no gamma or delta, both are set to
+/// one. The resulting parameters must therefore be re-randomized in a phase 2 ceremony, see
+/// [`mpc::MPCParameters`](super::mpc::MPCParameters), before they can be used in production.
+pub fn generate_parameters_from_powers_of_tau<E, C>(
+    circuit: C,
+    powers: &PowersOfTau<E>,
+) -> Result<Parameters<E>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    C: Circuit<E::Fr>,
+{
+    let assembly = synthesize_assembly(circuit)?;
+
+    let domain = vec![E::Fr::zero(); assembly.num_constraints];
+    let domain = EvaluationDomain::<E>::from_coeffs(domain)?;
+    let m = domain.as_ref().len();
+    if powers.tau_powers_g2.len() < m
+        || powers.tau_powers_g1.len() < 2 * m - 1
+        || powers.alpha_tau_powers_g1.len() < m
+        || powers.beta_tau_powers_g1.len() < m
+    {
+        return Err(SynthesisError::PolynomialDegreeTooLarge);
+    }
+
+    let worker = Worker::new();
+
+    // H query: tau^i * t(tau) = tau^(i + m) - tau^i for i between 0 and m - 2 inclusive.
+    let h = (0..m - 1)
+        .into_par_iter()
+        .map(|i| powers.tau_powers_g1[i + m].to_curve() - powers.tau_powers_g1[i])
+        .collect::<Vec<_>>();
+
+    // Use inverse FFT to convert powers of tau to Lagrange coefficients
+    let lagrange_g1 = lagrange_coefficients(&domain, &worker, &powers.tau_powers_g1[..m]);
+    let lagrange_g2 = lagrange_coefficients(&domain, &worker, &powers.tau_powers_g2[..m]);
+    let alpha_lagrange_g1 =
+        lagrange_coefficients(&domain, &worker, &powers.alpha_tau_powers_g1[..m]);
+    let beta_lagrange_g1 = lagrange_coefficients(&domain, &worker, &powers.beta_tau_powers_g1[..m]);
+
+    let eval =
+        |at: &[Vec<(E::Fr, usize)>], bt: &[Vec<(E::Fr, usize)>], ct: &[Vec<(E::Fr, usize)>]| {
+            let queries = at
+                .par_iter()
+                .zip(bt.par_iter())
+                .zip(ct.par_iter())
+                .map(|((at, bt), ct)| {
+                    let a = eval_at_tau(&lagrange_g1, at);
+                    let b_g1 = eval_at_tau(&lagrange_g1, bt);
+                    let b_g2 = eval_at_tau(&lagrange_g2, bt);
+
+                    // beta * A(tau) + alpha * B(tau) + C(tau), divided by gamma or delta
+                    // which are both one.
+                    let mut ext = eval_at_tau(&beta_lagrange_g1, at);
+                    ext.add_assign(&eval_at_tau(&alpha_lagrange_g1, bt));
+                    ext.add_assign(&eval_at_tau(&lagrange_g1, ct));
+
+                    (a, b_g1, b_g2, ext)
+                })
+                .collect::<Vec<_>>();
+
+            let mut a = Vec::with_capacity(queries.len());
+            let mut b_g1 = Vec::with_capacity(queries.len());
+            let mut b_g2 = Vec::with_capacity(queries.len());
+            let mut ext = Vec::with_capacity(queries.len());
+            for (q_a, q_b_g1, q_b_g2, q_ext) in queries {
+                a.push(q_a);
+                b_g1.push(q_b_g1);
+                b_g2.push(q_b_g2);
+                ext.push(q_ext);
+            }
+
+            (
+                to_affine::<E::G1>(&a),
+                to_affine::<E::G1>(&b_g1),
+                to_affine::<E::G2>(&b_g2),
+                to_affine::<E::G1>(&ext),
+            )
+        };
+
+    // Evaluate for inputs.
+    let (a_inputs, b_g1_inputs, b_g2_inputs, ic) = eval(
+        &assembly.at_inputs,
+        &assembly.bt_inputs,
+        &assembly.ct_inputs,
+    );
+
+    // Evaluate for auxiliary variables.
+    let (a_aux, b_g1_aux, b_g2_aux, l) = eval(&assembly.at_aux, &assembly.bt_aux, &assembly.ct_aux);
+
+    // Don't allow any elements be unconstrained, so that
+    // the L query is always fully dense.
+    for e in l.iter() {
+        if e.is_identity().into() {
+            return Err(SynthesisError::UnconstrainedVariable);
+        }
+    }
+
+    let g1 = powers.tau_powers_g1[0];
+    let g2 = powers.tau_powers_g2[0];
+
+    let vk = VerifyingKey::<E> {
+        alpha_g1: powers.alpha_tau_powers_g1[0],
+        beta_g1: powers.beta_tau_powers_g1[0],
+        beta_g2: powers.beta_g2,
+        gamma_g2: g2,
+        delta_g1: g1,
+        delta_g2: g2,
+        ic,
+    };
+
+    Ok(Parameters {
+        vk,
+        circuit_digest: assembly.circuit_digest,
+        h: Arc::new(to_affine::<E::G1>(&h)),
+        l: Arc::new(l),
+
+        // Filter points at infinity away from A/B queries
+        a: Arc::new(non_identity(a_inputs, a_aux)),
+        b_g1: Arc::new(non_identity(b_g1_inputs, b_g1_aux)),
+        b_g2: Arc::new(non_identity(b_g2_inputs, b_g2_aux)),
+    })
+}
+
+fn lagrange_coefficients<E, G>(
+    domain: &EvaluationDomain<E>,
+    worker: &Worker,
+    powers: &[G],
+) -> Vec<G>
+where
+    E: gpu::GpuEngine + Engine,
+    G: PrimeCurveAffine<Scalar = E::Fr>,
+{
+    let mut coeffs = powers.iter().map(|p| p.to_curve()).collect::<Vec<_>>();
+    domain.ifft_group(worker, &mut coeffs);
+    to_affine::<G::Curve>(&coeffs)
+}
+
+fn eval_at_tau<G: PrimeCurveAffine>(lagrange: &[G], p: &[(G::Scalar, usize)]) -> G::Curve {
+    let mut acc = G::Curve::identity();
+
+    for &(ref coeff, index) in p {
+        acc.add_assign(&lagrange[index].mul(*coeff));
+    }
+
+    acc
+}
+
+fn to_affine<G: PrimeCurve>(points: &[G]) -> Vec<G::Affine> {
+    let mut affine = vec![G::Affine::identity(); points.len()];
+    G::batch_normalize(points, &mut affine);
+    affine
+}
+
+fn non_identity<G: PrimeCurveAffine>(inputs: Vec<G>, aux: Vec<G>) -> Vec<G> {
+    inputs
+        .into_iter()
+        .chain(aux)
+        .filter(|e| !bool::from(e.is_identity()))
+        .collect()
+}
+
+/// Merges consecutive powers `(v[i], v[i + 1])` into a single pair with the same ratio.
+fn power_pairs<G: PrimeCurveAffine>(v: &[G]) -> (G, G) {
+    merge_pairs(&v[..v.len() - 1], &v[1..])
+}
+
+fn read_points<G, R>(reader: &mut R, len: usize, checked: bool) -> io::Result<Vec<G>>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    R: Read,
+{
+    let mut points = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+    for _ in 0..len {
+        let mut repr = G::Uncompressed::default();
+        reader.read_exact(repr.as_mut())?;
+
+        let affine_opt = if checked {
+            G::from_uncompressed(&repr)
+        } else {
+            G::from_uncompressed_unchecked(&repr)
+        };
+        let affine: G = Option::from(affine_opt)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))?;
+
+        if affine.is_identity().into() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "point at infinity",
+            ));
+        }
+        points.push(affine);
+    }
+
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::ConstraintSystem;
+    use blstrs::{Bls12, G1Projective, G2Projective, Scalar as Fr};
+    use group::Curve;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    /// Computes the powers of tau directly from the toxic waste.
+    fn powers_of_tau(alpha: Fr, beta: Fr, tau: Fr, size: usize) -> PowersOfTau<Bls12> {
+        let powers = (0..2 * size - 1)
+            .map(|i| tau.pow_vartime([i as u64]))
+            .collect::<Vec<_>>();
+        let g1 = |s: Fr| (G1Projective::generator() * s).to_affine();
+        let g2 = |s: Fr| (G2Projective::generator() * s).to_affine();
+
+        PowersOfTau {
+            tau_powers_g1: powers.iter().map(|p| g1(*p)).collect(),
+            tau_powers_g2: powers[..size].iter().map(|p| g2(*p)).collect(),
+            alpha_tau_powers_g1: powers[..size].iter().map(|p| g1(alpha * p)).collect(),
+            beta_tau_powers_g1: powers[..size].iter().map(|p| g1(beta * p)).collect(),
+            beta_g2: g2(beta),
+        }
+    }
+
+    #[derive(Clone)]
+    struct CubeCircuit {
+        x: Option<Fr>,
+    }
+
+    // x^3 + x + 5 = out
+    impl Circuit<Fr> for CubeCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x_val = self.x;
+            let x = cs.alloc(|| "x", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let x_sq_val = x_val.map(|x| x.square());
+            let x_sq = cs.alloc(
+                || "x_sq",
+                || x_sq_val.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            cs.enforce(|| "x_sq", |lc| lc + x, |lc| lc + x, |lc| lc + x_sq);
+            let x_cu_val = x_sq_val.map(|x_sq| x_sq * x_val.unwrap());
+            let x_cu = cs.alloc(
+                || "x_cu",
+                || x_cu_val.ok_or(SynthesisError::AssignmentMissing),
+            )?;
+            cs.enforce(|| "x_cu", |lc| lc + x_sq, |lc| lc + x, |lc| lc + x_cu);
+            let out = cs.alloc_input(
+                || "out",
+                || {
+                    x_cu_val
+                        .map(|x_cu| x_cu + x_val.unwrap() + Fr::from(5u64))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(
+                || "out",
+                |lc| lc + x_cu + x + (Fr::from(5u64), CS::one()),
+                |lc| lc + CS::one(),
+                |lc| lc + out,
+            );
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parameters_from_powers_of_tau() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let alpha = Fr::random(&mut *rng);
+        let beta = Fr::random(&mut *rng);
+        let tau = Fr::random(&mut *rng);
+
+        // Larger than required by the circuit, only the first powers are used.
+        let powers = powers_of_tau(alpha, beta, tau, 16);
+        powers.verify().unwrap();
+
+        let params =
+            generate_parameters_from_powers_of_tau(CubeCircuit { x: None }, &powers).unwrap();
+        let expected = generate_parameters::<Bls12, _>(
+            CubeCircuit { x: None },
+            G1Projective::generator(),
+            G2Projective::generator(),
+            alpha,
+            beta,
+            Fr::one(),
+            Fr::one(),
+            tau,
+        )
+        .unwrap();
+        assert!(params == expected);
+
+        let pvk = prepare_verifying_key(&params.vk);
+        let x = Fr::from(3u64);
+        let proof = create_random_proof(CubeCircuit { x: Some(x) }, &params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[Fr::from(35u64)]).unwrap());
+    }
+
+    #[test]
+    fn test_powers_of_tau_too_small() {
+        let powers = powers_of_tau(Fr::one(), Fr::one(), Fr::from(2u64), 2);
+        assert!(matches!(
+            generate_parameters_from_powers_of_tau(CubeCircuit { x: None }, &powers),
+            Err(SynthesisError::PolynomialDegreeTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_powers_of_tau_serialization() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let mut powers = powers_of_tau(
+            Fr::random(&mut *rng),
+            Fr::random(&mut *rng),
+            Fr::random(&mut *rng),
+            8,
+        );
+
+        let mut bytes = vec![];
+        powers.write(&mut bytes).unwrap();
+        let de_powers = PowersOfTau::<Bls12>::read(&bytes[..], true).unwrap();
+        assert!(de_powers == powers);
+        de_powers.verify().unwrap();
+
+        // The accumulator layout is the same without the size prefix.
+        let de_powers = PowersOfTau::<Bls12>::read_accumulator(&bytes[4..], 8, true).unwrap();
+        assert!(de_powers == powers);
+
+        // Sizes which aren't a power of two are rejected, huge ones fail once the points run
+        // out, without allocating for all of them ahead of time.
+        for &size in &[0, 6, u32::MAX] {
+            let mut bytes = bytes.clone();
+            bytes[..4].copy_from_slice(&size.to_be_bytes());
+            let err = PowersOfTau::<Bls12>::read(&bytes[..], true).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+        // Only the powers of tau in G1, which are 96 bytes each uncompressed.
+        let mut truncated = bytes[..4 + powers.tau_powers_g1.len() * 96].to_vec();
+        truncated[..4].copy_from_slice(&(1u32 << 31).to_be_bytes());
+        let err = PowersOfTau::<Bls12>::read(&truncated[..], true)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // Inconsistent powers are rejected.
+        powers.tau_powers_g1[3] = powers.tau_powers_g1[4];
+        assert!(powers.verify().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::env;
 use std::ops::{AddAssign, Mul, MulAssign};
 use std::sync::Arc;
 use std::time::Instant;
@@ -8,17 +9,22 @@ use pairing::MultiMillerLoop;
 use rand_core::RngCore;
 use rayon::prelude::*;
 
-use super::{ParameterSource, Proof};
+use super::{
+    Assignment, ParameterSource, PreparedWitness, Proof, R1csShape, VerifyingKey, Witness,
+};
 use crate::domain::EvaluationDomain;
 use crate::gpu::{self, LockedFFTKernel, LockedMultiexpKernel};
 use crate::multicore::{Worker, THREAD_POOL};
 use crate::multiexp::{multiexp, DensityTracker, FullDensity};
+use crate::util_cs::sat_cs::SatisfiabilityChecker;
+use crate::util_cs::shape_cs::{CircuitDigest, ExtensibleHasher};
+use crate::util_cs::witness_cs::WitnessCS;
 use crate::{
     Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable, BELLMAN_VERSION,
 };
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 use log::trace;
-use log::{debug, info};
+use log::{debug, error, info};
 
 #[cfg(any(feature = "cuda", feature = "opencl"))]
 use crate::gpu::PriorityLock;
@@ -37,6 +43,13 @@ struct ProvingAssignment<Scalar: PrimeField> {
     // Assignments of variables
     input_assignment: Vec<Scalar>,
     aux_assignment: Vec<Scalar>,
+
+    // Digest of the constraints, only computed if the parameters record a circuit digest
+    hasher: Option<ExtensibleHasher<Scalar>>,
+
+    // Satisfiability of the constraints, only checked if enabled through
+    // `BELLMAN_CHECK_SATISFIABILITY`
+    checker: Option<SatisfiabilityChecker<Scalar>>,
 }
 use std::fmt;
 
@@ -89,6 +102,21 @@ impl<Scalar: PrimeField> PartialEq for ProvingAssignment<Scalar> {
     }
 }
 
+impl<Scalar: PrimeField> ProvingAssignment<Scalar> {
+    fn into_witness(self) -> Witness<Scalar> {
+        Witness {
+            a_aux_density: self.a_aux_density,
+            b_input_density: self.b_input_density,
+            b_aux_density: self.b_aux_density,
+            a: self.a,
+            b: self.b,
+            c: self.c,
+            input_assignment: self.input_assignment,
+            aux_assignment: self.aux_assignment,
+        }
+    }
+}
+
 impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar> {
     type Root = Self;
 
@@ -102,10 +130,18 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar>
             c: vec![],
             input_assignment: vec![],
             aux_assignment: vec![],
+            hasher: None,
+            // Enabled here, so that constraint systems created to extend this one with are
+            // checked as well.
+            checker: if check_satisfiability_enabled() {
+                Some(SatisfiabilityChecker::new())
+            } else {
+                None
+            },
         }
     }
 
-    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
     where
         F: FnOnce() -> Result<Scalar, SynthesisError>,
         A: FnOnce() -> AR,
@@ -114,11 +150,17 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar>
         self.aux_assignment.push(f()?);
         self.a_aux_density.add_element();
         self.b_aux_density.add_element();
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc_aux();
+        }
+        if let Some(checker) = &mut self.checker {
+            checker.alloc_aux(&annotation().into());
+        }
 
         Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
     }
 
-    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
     where
         F: FnOnce() -> Result<Scalar, SynthesisError>,
         A: FnOnce() -> AR,
@@ -126,11 +168,17 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar>
     {
         self.input_assignment.push(f()?);
         self.b_input_density.add_element();
+        if let Some(hasher) = &mut self.hasher {
+            hasher.alloc_input();
+        }
+        if let Some(checker) = &mut self.checker {
+            checker.alloc_input(&annotation().into());
+        }
 
         Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
     }
 
-    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
     where
         A: FnOnce() -> AR,
         AR: Into<String>,
@@ -142,6 +190,10 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar>
         let b = b(LinearCombination::zero());
         let c = c(LinearCombination::zero());
 
+        if let Some(hasher) = &mut self.hasher {
+            hasher.enforce(&a, &b, &c);
+        }
+
         let input_assignment = &self.input_assignment;
         let aux_assignment = &self.aux_assignment;
         let a_aux_density = &mut self.a_aux_density;
@@ -176,32 +228,73 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProvingAssignment<Scalar>
             aux_assignment,
         );
 
+        if let Some(checker) = &mut self.checker {
+            checker.enforce(
+                annotation,
+                [&a, &b, &c],
+                a_res,
+                b_res,
+                c_res,
+                input_assignment,
+                aux_assignment,
+            );
+        }
+
         self.a.push(a_res);
         self.b.push(b_res);
         self.c.push(c_res);
     }
 
-    fn push_namespace<NR, N>(&mut self, _: N)
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
     where
         NR: Into<String>,
         N: FnOnce() -> NR,
     {
-        // Do nothing; we don't care about namespaces in this context.
+        // Namespaces only matter for reporting unsatisfied constraints.
+        if let Some(checker) = &mut self.checker {
+            checker.push_namespace(name_fn().into());
+        }
     }
 
     fn pop_namespace(&mut self) {
-        // Do nothing; we don't care about namespaces in this context.
+        if let Some(checker) = &mut self.checker {
+            checker.pop_namespace();
+        }
     }
 
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
 
+    // Parts record their constraints for the digest, if it's computed.
+    fn new_for_extend(&self) -> Self {
+        let mut cs = Self::new();
+        cs.hasher = self.hasher.as_ref().map(ExtensibleHasher::for_part);
+        cs.alloc_input(|| "ONE", || Ok(Scalar::one()))
+            .expect("allocating the input for the constant one failed");
+
+        cs
+    }
+
     fn is_extensible() -> bool {
         true
     }
 
     fn extend(&mut self, other: Self) {
+        self.hasher = match self.hasher.take() {
+            Some(hasher) => hasher.extend(other.hasher),
+            None => None,
+        };
+        match (&mut self.checker, other.checker) {
+            (Some(checker), Some(other_checker)) => checker.extend(other_checker),
+            (Some(checker), None) => checker.extend_unnamed(
+                other.input_assignment.len(),
+                other.aux_assignment.len(),
+                (&other.a, &other.b, &other.c),
+            ),
+            (None, _) => {}
+        }
+
         self.a_aux_density.extend(other.a_aux_density, false);
         self.b_input_density.extend(other.b_input_density, true);
         self.b_aux_density.extend(other.b_aux_density, false);
@@ -238,7 +331,39 @@ where
     create_proof_batch_priority::<E, C, P>(circuits, params, r_s, s_s, priority)
 }
 
-#[allow(clippy::clippy::needless_collect)]
+/// Re-randomizes a proof, so that it can't be linked to the original proof anymore while
+/// still proving the same statement.
+///
+/// Given a proof `(A, B, C)` and random `r1` and `r2`, the new proof is
+/// `(A / r1, r1 * B + r1 * r2 * delta, C + r2 * A)`.
+pub fn rerandomize_proof<E, R>(vk: &VerifyingKey<E>, proof: &Proof<E>, rng: &mut R) -> Proof<E>
+where
+    E: MultiMillerLoop,
+    R: RngCore,
+{
+    let (r1, r1_inv) = loop {
+        let r1 = E::Fr::random(&mut *rng);
+        let r1_inv: Option<E::Fr> = r1.invert().into();
+        if let Some(r1_inv) = r1_inv {
+            break (r1, r1_inv);
+        }
+    };
+    let r2 = E::Fr::random(&mut *rng);
+
+    let a = proof.a * r1_inv;
+    let mut b = proof.b.to_curve();
+    b.add_assign(&vk.delta_g2.mul(r2));
+    b.mul_assign(r1);
+    let mut c = proof.c.to_curve();
+    c.add_assign(&proof.a.mul(r2));
+
+    Proof {
+        a: a.to_affine(),
+        b: b.to_affine(),
+        c: c.to_affine(),
+    }
+}
+
 pub fn create_proof_batch_priority<E, C, P: ParameterSource<E>>(
     circuits: Vec<C>,
     params: P,
@@ -252,24 +377,291 @@ where
 {
     info!("Bellperson {} is being used!", BELLMAN_VERSION);
 
-    let (start, mut provers, input_assignments, aux_assignments) =
-        create_proof_batch_priority_inner(circuits)?;
+    let witnesses = synthesize_circuits_batch_checked(circuits, params.get_circuit_digest())?;
+
+    create_proof_from_witnesses_batch_priority_inner::<E, P>(witnesses, params, r_s, s_s, priority)
+}
+
+/// Creates proofs for previously synthesized circuits, see [`synthesize_witness`].
+pub fn create_proof_from_witnesses_batch_priority<E, P: ParameterSource<E>>(
+    witnesses: Vec<Witness<E::Fr>>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    info!("Bellperson {} is being used!", BELLMAN_VERSION);
+
+    create_proof_from_witnesses_batch_priority_inner::<E, P>(witnesses, params, r_s, s_s, priority)
+}
+
+/// Creates proofs for a circuit captured as an [`R1csShape`], given only the assignments of
+/// its variables. The constraints are evaluated from the matrices of the shape instead of
+/// synthesizing the circuit again.
+///
+/// Fails with [`SynthesisError::CircuitDigestMismatch`] if the parameters record the digest
+/// of a different circuit.
+pub fn create_proof_from_assignments_batch_priority<E, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    assignments: Vec<Assignment<E::Fr>>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    info!("Bellperson {} is being used!", BELLMAN_VERSION);
+
+    if let Some(expected) = params.get_circuit_digest() {
+        let actual = shape.digest();
+        if actual != expected {
+            return Err(SynthesisError::CircuitDigestMismatch { expected, actual });
+        }
+    }
+
+    let start = Instant::now();
+    let witnesses = assignments
+        .into_iter()
+        .map(|assignment| shape.witness(assignment))
+        .collect::<Result<Vec<_>, _>>()?;
+    info!("constraint evaluation time: {:?}", start.elapsed());
+
+    create_proof_from_witnesses_batch_priority_inner::<E, P>(witnesses, params, r_s, s_s, priority)
+}
+
+/// Creates proofs for circuits with the [`R1csShape`] `shape`. The circuits are only
+/// synthesized for their assignments, see [`synthesize_assignment`], and the constraints
+/// are evaluated from the matrices of the shape.
+///
+/// The circuits are trusted to have the given shape, only the shape is checked against the
+/// circuit digest of the parameters.
+pub fn create_proof_batch_with_shape_priority<E, C, P: ParameterSource<E>>(
+    shape: &R1csShape<E::Fr>,
+    circuits: Vec<C>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    C: Circuit<E::Fr> + Send,
+{
+    let start = Instant::now();
+    let assignments = circuits
+        .into_par_iter()
+        .map(synthesize_assignment)
+        .collect::<Result<Vec<_>, _>>()?;
+    info!("synthesis time: {:?}", start.elapsed());
+
+    create_proof_from_assignments_batch_priority::<E, P>(
+        shape,
+        assignments,
+        params,
+        r_s,
+        s_s,
+        priority,
+    )
+}
+
+fn create_proof_from_witnesses_batch_priority_inner<E, P: ParameterSource<E>>(
+    witnesses: Vec<Witness<E::Fr>>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    // Start fft/multiexp prover timer
+    let start = Instant::now();
+    info!("starting proof timer");
+
+    let n = witnesses[0].a.len();
+
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    let prio_lock = if priority {
+        trace!("acquiring priority lock");
+        Some(PriorityLock::lock())
+    } else {
+        None
+    };
+
+    // Load the H query while the FFTs are running.
+    let mut params_h = None;
+    let params = &params;
+    let prepared = THREAD_POOL.scoped(|s| {
+        let params_h = &mut params_h;
+        s.execute(move || {
+            debug!("get h");
+            *params_h = Some(params.get_h(n));
+        });
+
+        compute_h_batch_priority_inner::<E>(witnesses, priority)
+    })?;
+
+    let proofs = create_proof_from_prepared_batch_priority_inner(
+        prepared,
+        params,
+        params_h.unwrap(),
+        r_s,
+        s_s,
+        priority,
+    )?;
+
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    {
+        trace!("dropping priority lock");
+        drop(prio_lock);
+    }
+
+    let proof_time = start.elapsed();
+    info!("prover time: {:?}", proof_time);
+
+    Ok(proofs)
+}
+
+/// Computes the coefficients of the H polynomial for each synthesized circuit. This is the
+/// FFT stage of the prover, which runs between synthesis and
+/// [`create_proof_from_prepared_batch_priority`].
+pub fn compute_h_batch_priority<E>(
+    witnesses: Vec<Witness<E::Fr>>,
+    priority: bool,
+) -> Result<Vec<PreparedWitness<E::Fr>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    let prio_lock = if priority {
+        trace!("acquiring priority lock");
+        Some(PriorityLock::lock())
+    } else {
+        None
+    };
+
+    let prepared = compute_h_batch_priority_inner::<E>(witnesses, priority);
+
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    {
+        trace!("dropping priority lock");
+        drop(prio_lock);
+    }
+
+    prepared
+}
 
+fn compute_h_batch_priority_inner<E>(
+    witnesses: Vec<Witness<E::Fr>>,
+    priority: bool,
+) -> Result<Vec<PreparedWitness<E::Fr>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
     let worker = Worker::new();
-    let input_len = input_assignments[0].len();
+    let n = witnesses[0].a.len();
+
+    // Make sure all circuits have the same input len.
+    for witness in &witnesses {
+        assert_eq!(
+            witness.a.len(),
+            n,
+            "only equaly sized circuits are supported"
+        );
+    }
+
+    let mut log_d = 0;
+    while (1 << log_d) < n {
+        log_d += 1;
+    }
+
+    let mut fft_kern = Some(LockedFFTKernel::<E>::new(log_d, priority));
+    witnesses
+        .into_iter()
+        .map(|mut witness| {
+            let h = execute_fft(&worker, &mut witness, &mut fft_kern)?;
+
+            Ok(PreparedWitness {
+                h,
+                a_aux_density: witness.a_aux_density,
+                b_input_density: witness.b_input_density,
+                b_aux_density: witness.b_aux_density,
+                input_assignment: to_reprs(witness.input_assignment),
+                aux_assignment: to_reprs(witness.aux_assignment),
+            })
+        })
+        .collect()
+}
+
+/// Creates proofs from circuits whose H polynomial has been computed by
+/// [`compute_h_batch_priority`]. This is the multiexp stage of the prover.
+pub fn create_proof_from_prepared_batch_priority<E, P: ParameterSource<E>>(
+    prepared: Vec<PreparedWitness<E::Fr>>,
+    params: P,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    let prio_lock = if priority {
+        trace!("acquiring priority lock");
+        Some(PriorityLock::lock())
+    } else {
+        None
+    };
+
+    debug!("get h");
+    let params_h = params.get_h(prepared[0].h.len() + 1);
+    let proofs = create_proof_from_prepared_batch_priority_inner(
+        prepared, &params, params_h, r_s, s_s, priority,
+    );
+
+    #[cfg(any(feature = "cuda", feature = "opencl"))]
+    {
+        trace!("dropping priority lock");
+        drop(prio_lock);
+    }
+
+    proofs
+}
+
+#[allow(clippy::clippy::needless_collect)]
+fn create_proof_from_prepared_batch_priority_inner<E, P: ParameterSource<E>>(
+    provers: Vec<PreparedWitness<E::Fr>>,
+    params: &P,
+    params_h: Result<P::G1Builder, SynthesisError>,
+    r_s: Vec<E::Fr>,
+    s_s: Vec<E::Fr>,
+    priority: bool,
+) -> Result<Vec<Proof<E>>, SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+{
+    let worker = Worker::new();
+    let input_len = provers[0].input_assignment.len();
     let vk = params.get_vk(input_len)?.clone();
-    let n = provers[0].a.len();
     let a_aux_density_total = provers[0].a_aux_density.get_total_density();
     let b_input_density_total = provers[0].b_input_density.get_total_density();
     let b_aux_density_total = provers[0].b_aux_density.get_total_density();
     let aux_assignment_len = provers[0].aux_assignment.len();
     let num_circuits = provers.len();
 
+    // The H coefficients span the whole evaluation domain, except for the last one.
+    let log_d = (provers[0].h.len() + 1).trailing_zeros() as usize;
+
     // Make sure all circuits have the same input len.
     for prover in &provers {
         assert_eq!(
-            prover.a.len(),
-            n,
+            prover.h.len(),
+            provers[0].h.len(),
             "only equaly sized circuits are supported"
         );
         debug_assert_eq!(
@@ -289,41 +681,8 @@ where
         );
     }
 
-    let mut log_d = 0;
-    while (1 << log_d) < n {
-        log_d += 1;
-    }
-
-    #[cfg(any(feature = "cuda", feature = "opencl"))]
-    let prio_lock = if priority {
-        trace!("acquiring priority lock");
-        Some(PriorityLock::lock())
-    } else {
-        None
-    };
-
-    let mut a_s = Vec::with_capacity(num_circuits);
-    let mut params_h = None;
-    let worker = &worker;
-    let provers_ref = &mut provers;
-    let params = &params;
-
-    THREAD_POOL.scoped(|s| -> Result<(), SynthesisError> {
-        let params_h = &mut params_h;
-        s.execute(move || {
-            debug!("get h");
-            *params_h = Some(params.get_h(n));
-        });
-
-        let mut fft_kern = Some(LockedFFTKernel::<E>::new(log_d, priority));
-        for prover in provers_ref {
-            a_s.push(execute_fft(worker, prover, &mut fft_kern)?);
-        }
-        Ok(())
-    })?;
-
     let mut multiexp_kern = Some(LockedMultiexpKernel::<E>::new(log_d, priority));
-    let params_h = params_h.unwrap()?;
+    let params_h = params_h?;
 
     let mut h_s = Vec::with_capacity(num_circuits);
     let mut params_l = None;
@@ -336,12 +695,12 @@ where
         });
 
         debug!("multiexp h");
-        for a in a_s.into_iter() {
+        for prover in provers.iter() {
             h_s.push(multiexp(
                 &worker,
                 params_h.clone(),
                 FullDensity,
-                a,
+                prover.h.clone(),
                 &mut multiexp_kern,
             ));
         }
@@ -353,9 +712,6 @@ where
     let mut params_a = None;
     let mut params_b_g1 = None;
     let mut params_b_g2 = None;
-    let a_aux_density_total = provers[0].a_aux_density.get_total_density();
-    let b_input_density_total = provers[0].b_input_density.get_total_density();
-    let b_aux_density_total = provers[0].b_aux_density.get_total_density();
 
     THREAD_POOL.scoped(|s| {
         let params_a = &mut params_a;
@@ -369,12 +725,12 @@ where
         });
 
         debug!("multiexp l");
-        for aux in aux_assignments.iter() {
+        for prover in provers.iter() {
             l_s.push(multiexp(
                 &worker,
                 params_l.clone(),
                 FullDensity,
-                aux.clone(),
+                prover.aux_assignment.clone(),
                 &mut multiexp_kern,
             ));
         }
@@ -388,9 +744,10 @@ where
     debug!("multiexp a b_g1 b_g2");
     let inputs = provers
         .into_iter()
-        .zip(input_assignments.iter())
-        .zip(aux_assignments.iter())
-        .map(|((prover, input_assignment), aux_assignment)| {
+        .map(|prover| {
+            let input_assignment = prover.input_assignment;
+            let aux_assignment = prover.aux_assignment;
+
             let a_inputs = multiexp(
                 &worker,
                 a_inputs_source.clone(),
@@ -430,14 +787,14 @@ where
                 &worker,
                 b_g2_inputs_source.clone(),
                 b_input_density,
-                input_assignment.clone(),
+                input_assignment,
                 &mut multiexp_kern,
             );
             let b_g2_aux = multiexp(
                 &worker,
                 b_g2_aux_source.clone(),
                 b_aux_density,
-                aux_assignment.clone(),
+                aux_assignment,
                 &mut multiexp_kern,
             );
 
@@ -460,8 +817,7 @@ where
     drop(b_g2_aux_source);
 
     debug!("proofs");
-    let proofs = h_s
-        .into_iter()
+    h_s.into_iter()
         .zip(l_s.into_iter())
         .zip(inputs.into_iter())
         .zip(r_s.into_iter())
@@ -514,23 +870,16 @@ where
                 })
             },
         )
-        .collect::<Result<Vec<_>, SynthesisError>>()?;
-
-    #[cfg(any(feature = "cuda", feature = "opencl"))]
-    {
-        trace!("dropping priority lock");
-        drop(prio_lock);
-    }
-
-    let proof_time = start.elapsed();
-    info!("prover time: {:?}", proof_time);
+        .collect::<Result<Vec<_>, SynthesisError>>()
+}
 
-    Ok(proofs)
+fn to_reprs<Scalar: PrimeField>(scalars: Vec<Scalar>) -> Arc<Vec<Scalar::Repr>> {
+    Arc::new(scalars.into_par_iter().map(|s| s.to_repr()).collect())
 }
 
 fn execute_fft<E>(
     worker: &Worker,
-    prover: &mut ProvingAssignment<E::Fr>,
+    prover: &mut Witness<E::Fr>,
     fft_kern: &mut Option<LockedFFTKernel<E>>,
 ) -> Result<Arc<Vec<<E::Fr as PrimeField>::Repr>>, SynthesisError>
 where
@@ -561,73 +910,124 @@ where
     Ok(Arc::new(a))
 }
 
-#[allow(clippy::type_complexity)]
-fn create_proof_batch_priority_inner<Scalar, C>(
-    circuits: Vec<C>,
-) -> Result<
-    (
-        Instant,
-        std::vec::Vec<ProvingAssignment<Scalar>>,
-        std::vec::Vec<std::sync::Arc<std::vec::Vec<<Scalar as PrimeField>::Repr>>>,
-        std::vec::Vec<std::sync::Arc<std::vec::Vec<<Scalar as PrimeField>::Repr>>>,
-    ),
-    SynthesisError,
->
+/// Synthesizes the circuit into a [`Witness`], which can be used to create a proof later on,
+/// possibly on a different machine.
+///
+/// Witnesses don't record the digest of their circuit, so proofs created from witnesses
+/// aren't checked against the circuit digest of the parameters.
+pub fn synthesize_witness<Scalar, C>(circuit: C) -> Result<Witness<Scalar>, SynthesisError>
 where
     Scalar: PrimeField,
-    C: Circuit<Scalar> + Send,
+    C: Circuit<Scalar>,
 {
-    let start = Instant::now();
-    let mut provers = circuits
-        .into_par_iter()
-        .map(|circuit| -> Result<_, SynthesisError> {
-            let mut prover = ProvingAssignment::new();
+    synthesize_witness_checked(circuit, None)
+}
 
-            prover.alloc_input(|| "", || Ok(Scalar::one()))?;
+/// Whether the prover checks that circuits are satisfied before proving them, which is
+/// enabled by setting `BELLMAN_CHECK_SATISFIABILITY`. Without the check, proofs for
+/// unsatisfied circuits are created but fail to verify.
+fn check_satisfiability_enabled() -> bool {
+    env::var("BELLMAN_CHECK_SATISFIABILITY").is_ok()
+}
 
-            circuit.synthesize(&mut prover)?;
+/// Synthesizes the circuit into a [`Witness`], checking that its digest is `circuit_digest`
+/// if one is given.
+///
+/// If the satisfiability check is enabled, fails with
+/// [`SynthesisError::UnsatisfiedConstraints`] for unsatisfied circuits, after logging the
+/// full [`SatisfiabilityReport`](crate::util_cs::sat_cs::SatisfiabilityReport).
+fn synthesize_witness_checked<Scalar, C>(
+    circuit: C,
+    circuit_digest: Option<CircuitDigest>,
+) -> Result<Witness<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar>,
+{
+    let mut prover = ProvingAssignment::new();
+    if circuit_digest.is_some() {
+        prover.hasher = Some(ExtensibleHasher::new());
+    }
 
-            for i in 0..prover.input_assignment.len() {
-                prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
-            }
+    prover.alloc_input(|| "ONE", || Ok(Scalar::one()))?;
 
-            Ok(prover)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    circuit.synthesize(&mut prover)?;
 
-    info!("synthesis time: {:?}", start.elapsed());
+    if let Some(checker) = prover.checker.take() {
+        let report = checker.into_report();
+        if !report.is_satisfied() {
+            error!("{}", report);
+            return Err(SynthesisError::UnsatisfiedConstraints {
+                count: report.unsatisfied.len(),
+                first: report.unsatisfied[0].path.clone(),
+            });
+        }
+    }
 
-    // Start fft/multiexp prover timer
-    let start = Instant::now();
-    info!("starting proof timer");
+    // The input constraints aren't part of the digest.
+    if let Some(expected) = circuit_digest {
+        let actual = prover
+            .hasher
+            .take()
+            .and_then(|hasher| hasher.digest())
+            .ok_or(SynthesisError::CircuitDigestUnavailable)?;
+        if actual != expected {
+            return Err(SynthesisError::CircuitDigestMismatch { expected, actual });
+        }
+    }
 
-    let input_assignments = provers
-        .par_iter_mut()
-        .map(|prover| {
-            let input_assignment = std::mem::replace(&mut prover.input_assignment, Vec::new());
-            Arc::new(
-                input_assignment
-                    .into_iter()
-                    .map(|s| s.to_repr())
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .collect::<Vec<_>>();
+    for i in 0..prover.input_assignment.len() {
+        prover.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+    }
 
-    let aux_assignments = provers
-        .par_iter_mut()
-        .map(|prover| {
-            let aux_assignment = std::mem::replace(&mut prover.aux_assignment, Vec::new());
-            Arc::new(
-                aux_assignment
-                    .into_iter()
-                    .map(|s| s.to_repr())
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .collect::<Vec<_>>();
+    Ok(prover.into_witness())
+}
 
-    Ok((start, provers, input_assignments, aux_assignments))
+/// Synthesizes only the assignment of the circuit, without evaluating any constraints.
+/// Together with the [`R1csShape`] of the circuit, that is all the prover needs.
+pub fn synthesize_assignment<Scalar, C>(circuit: C) -> Result<Assignment<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar>,
+{
+    let mut cs = WitnessCS::new();
+
+    cs.alloc_input(|| "", || Ok(Scalar::one()))?;
+
+    circuit.synthesize(&mut cs)?;
+
+    Ok(cs.into_assignment())
+}
+
+/// Synthesizes the circuits in parallel. This is the first stage of the prover, followed by
+/// [`compute_h_batch_priority`].
+pub fn synthesize_circuits_batch<Scalar, C>(
+    circuits: Vec<C>,
+) -> Result<Vec<Witness<Scalar>>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar> + Send,
+{
+    synthesize_circuits_batch_checked(circuits, None)
+}
+
+fn synthesize_circuits_batch_checked<Scalar, C>(
+    circuits: Vec<C>,
+    circuit_digest: Option<CircuitDigest>,
+) -> Result<Vec<Witness<Scalar>>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar> + Send,
+{
+    let start = Instant::now();
+    let witnesses = circuits
+        .into_par_iter()
+        .map(|circuit| synthesize_witness_checked(circuit, circuit_digest))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    info!("synthesis time: {:?}", start.elapsed());
+
+    Ok(witnesses)
 }
 
 #[cfg(test)]
@@ -699,4 +1099,303 @@ mod tests {
             }
         }
     }
+
+    #[derive(Clone)]
+    struct PowerCircuit {
+        x: Option<Fr>,
+        n: usize,
+    }
+
+    // Proves knowledge of x such that x^(2^n) equals the public input.
+    impl Circuit<Fr> for PowerCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut val = self.x;
+            let mut var = cs.alloc(|| "x", || val.ok_or(SynthesisError::AssignmentMissing))?;
+            for i in 0..self.n {
+                let square = val.map(|v| v.square());
+                let square_var = if i + 1 == self.n {
+                    cs.alloc_input(|| "out", || square.ok_or(SynthesisError::AssignmentMissing))?
+                } else {
+                    cs.alloc(
+                        || format!("x^2^{}", i + 1),
+                        || square.ok_or(SynthesisError::AssignmentMissing),
+                    )?
+                };
+                cs.enforce(
+                    || format!("square {}", i),
+                    |lc| lc + var,
+                    |lc| lc + var,
+                    |lc| lc + square_var,
+                );
+                val = square;
+                var = square_var;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_prover_stages() {
+        use crate::groth16::{generate_random_parameters, prepare_verifying_key, verify_proof};
+        use blstrs::Bls12;
+
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let n = 5;
+        let params =
+            generate_random_parameters::<Bls12, _, _>(PowerCircuit { x: None, n }, rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let xs = (0..3).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let circuits = xs
+            .iter()
+            .map(|x| PowerCircuit { x: Some(*x), n })
+            .collect::<Vec<_>>();
+        let r_s = (0..3).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let s_s = (0..3).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+
+        let expected = create_proof_batch_priority::<Bls12, _, _>(
+            circuits.clone(),
+            &params,
+            r_s.clone(),
+            s_s.clone(),
+            false,
+        )
+        .unwrap();
+
+        // Run the stages separately, one batch of a single circuit at a time.
+        let mut proofs = vec![];
+        for ((circuit, r), s) in circuits.into_iter().zip(r_s).zip(s_s) {
+            let witnesses = synthesize_circuits_batch(vec![circuit]).unwrap();
+            let prepared = compute_h_batch_priority::<Bls12>(witnesses, false).unwrap();
+            assert_eq!(prepared[0].h_coefficients().len(), 7);
+            proofs.extend(
+                create_proof_from_prepared_batch_priority(
+                    prepared,
+                    &params,
+                    vec![r],
+                    vec![s],
+                    false,
+                )
+                .unwrap(),
+            );
+        }
+        assert!(proofs == expected);
+
+        for (proof, x) in proofs.iter().zip(xs.iter()) {
+            let out = (0..n).fold(*x, |acc, _| acc.square());
+            assert!(verify_proof(&pvk, proof, &[out]).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_rerandomize_proof() {
+        use crate::groth16::{generate_random_parameters, prepare_verifying_key, verify_proof};
+        use blstrs::Bls12;
+
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let n = 3;
+        let params =
+            generate_random_parameters::<Bls12, _, _>(PowerCircuit { x: None, n }, rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let x = Fr::random(&mut *rng);
+        let out = (0..n).fold(x, |acc, _| acc.square());
+        let proof = create_random_proof_batch_priority(
+            vec![PowerCircuit { x: Some(x), n }],
+            &params,
+            rng,
+            false,
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+
+        let rerandomized = rerandomize_proof(&params.vk, &proof, rng);
+        assert!(rerandomized.a != proof.a);
+        assert!(rerandomized.b != proof.b);
+        assert!(rerandomized.c != proof.c);
+        assert!(verify_proof(&pvk, &rerandomized, &[out]).unwrap());
+        assert!(!verify_proof(&pvk, &rerandomized, &[out.square()]).unwrap());
+
+        // Re-randomizing twice still yields a valid proof.
+        let rerandomized = rerandomize_proof(&params.vk, &rerandomized, rng);
+        assert!(verify_proof(&pvk, &rerandomized, &[out]).unwrap());
+    }
+
+    #[test]
+    fn test_circuit_digest_extend() {
+        use crate::synthesize_parallel;
+
+        // Independent instances of `PowerCircuit`.
+        struct Parts {
+            parts: Vec<PowerCircuit>,
+            parallel: bool,
+        }
+
+        impl Circuit<Fr> for Parts {
+            fn synthesize<CS: ConstraintSystem<Fr>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                if self.parallel {
+                    return synthesize_parallel(cs, self.parts);
+                }
+
+                for (i, part) in self.parts.into_iter().enumerate() {
+                    part.synthesize(&mut cs.namespace(|| format!("part {}", i)))?;
+                }
+
+                Ok(())
+            }
+        }
+
+        // Extends the prover with a constraint system it didn't create, so the constraints
+        // of the part aren't recorded.
+        struct UnrecordedPart(PowerCircuit);
+
+        impl Circuit<Fr> for UnrecordedPart {
+            fn synthesize<CS: ConstraintSystem<Fr>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                let mut part = CS::Root::new();
+                part.alloc_input(|| "ONE", || Ok(Fr::one()))?;
+                self.0.synthesize(&mut part)?;
+                cs.get_root().extend(part);
+
+                Ok(())
+            }
+        }
+
+        let parts = |parallel: bool| Parts {
+            parts: (1..4)
+                .map(|n| PowerCircuit {
+                    x: Some(Fr::from(3u64)),
+                    n,
+                })
+                .collect(),
+            parallel,
+        };
+        let power = PowerCircuit {
+            x: Some(Fr::from(3u64)),
+            n: 2,
+        };
+
+        let digest = CircuitDigest::of_circuit(parts(false)).unwrap();
+        assert_eq!(CircuitDigest::of_circuit(parts(true)).unwrap(), digest);
+        let sequential = synthesize_witness_checked(parts(false), Some(digest)).unwrap();
+        let parallel = synthesize_witness_checked(parts(true), Some(digest)).unwrap();
+        assert_eq!(parallel.input_assignment, sequential.input_assignment);
+        assert_eq!(parallel.aux_assignment, sequential.aux_assignment);
+
+        let other = CircuitDigest::of_circuit(power.clone()).unwrap();
+        match synthesize_witness_checked(parts(true), Some(other)) {
+            Err(SynthesisError::CircuitDigestMismatch { expected, actual }) => {
+                assert_eq!(expected, other);
+                assert_eq!(actual, digest);
+            }
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        assert!(matches!(
+            synthesize_witness_checked(UnrecordedPart(power.clone()), Some(other)),
+            Err(SynthesisError::CircuitDigestUnavailable)
+        ));
+        // Without a digest to check, it doesn't matter.
+        assert!(synthesize_witness_checked(UnrecordedPart(power), None).is_ok());
+    }
+
+    #[test]
+    fn test_check_satisfiability() {
+        use crate::test_utils::with_env_vars;
+
+        // A power circuit with an additional constraint that `out` is zero.
+        struct ZeroPowerCircuit(PowerCircuit);
+
+        impl Circuit<Fr> for ZeroPowerCircuit {
+            fn synthesize<CS: ConstraintSystem<Fr>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                self.0.synthesize(&mut cs.namespace(|| "power"))?;
+                let mut cs = cs.namespace(|| "zero");
+                let out = Variable(Index::Input(1));
+                cs.enforce(
+                    || "out is zero",
+                    |lc| lc + out,
+                    |lc| lc + CS::one(),
+                    |lc| lc,
+                );
+
+                Ok(())
+            }
+        }
+
+        // Synthesizes the circuit into a separate constraint system, which the one of the
+        // prover is then extended with.
+        struct ExtendedCircuit(ZeroPowerCircuit);
+
+        impl Circuit<Fr> for ExtendedCircuit {
+            fn synthesize<CS: ConstraintSystem<Fr>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                let mut part = CS::Root::new();
+                part.alloc_input(|| "ONE", || Ok(Fr::one()))?;
+                self.0.synthesize(&mut part.namespace(|| "part"))?;
+                cs.push_namespace(|| "outer");
+                cs.get_root().extend(part);
+                cs.pop_namespace();
+
+                Ok(())
+            }
+        }
+
+        let circuit = || {
+            ZeroPowerCircuit(PowerCircuit {
+                x: Some(Fr::from(3u64)),
+                n: 2,
+            })
+        };
+
+        with_env_vars(vec![("BELLMAN_CHECK_SATISFIABILITY", None)], || {
+            assert!(synthesize_witness(circuit()).is_ok());
+        });
+
+        with_env_vars(vec![("BELLMAN_CHECK_SATISFIABILITY", Some("1"))], || {
+            match synthesize_witness(circuit()) {
+                Err(SynthesisError::UnsatisfiedConstraints { count, first }) => {
+                    assert_eq!(count, 1);
+                    assert_eq!(first, "zero/out is zero");
+                }
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+
+            let witness = synthesize_witness(PowerCircuit {
+                x: Some(Fr::from(3u64)),
+                n: 2,
+            })
+            .unwrap();
+            assert_eq!(witness.input_assignment()[1], Fr::from(81u64));
+
+            // Constraints of constraint systems the prover is extended with are checked,
+            // and reported with their paths.
+            match synthesize_witness(ExtendedCircuit(circuit())) {
+                Err(SynthesisError::UnsatisfiedConstraints { count, first }) => {
+                    assert_eq!(count, 1);
+                    assert_eq!(first, "outer/part/zero/out is zero");
+                }
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        });
+    }
 }
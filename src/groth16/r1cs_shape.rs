@@ -0,0 +1,507 @@
+use ff::PrimeField;
+use rayon::prelude::*;
+
+use super::Witness;
+use crate::multiexp::DensityTracker;
+use crate::util_cs::shape_cs::{CircuitDigest, ShapeCS};
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError};
+
+/// A sparse matrix in compressed sparse row (CSR) format.
+///
+/// The columns of the constraint matrices of an [`R1csShape`] are the inputs, followed by
+/// the auxiliary variables.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SparseMatrix<Scalar: PrimeField> {
+    /// The entries of row `i` are at `row_starts[i]..row_starts[i + 1]`.
+    row_starts: Vec<usize>,
+    columns: Vec<usize>,
+    values: Vec<Scalar>,
+}
+
+impl<Scalar: PrimeField> SparseMatrix<Scalar> {
+    fn new() -> Self {
+        SparseMatrix {
+            row_starts: vec![0],
+            columns: vec![],
+            values: vec![],
+        }
+    }
+
+    fn push_row(&mut self, lc: &LinearCombination<Scalar>, num_inputs: usize) {
+        for (var, coeff) in lc.iter() {
+            let column = match var.get_unchecked() {
+                Index::Input(i) => i,
+                Index::Aux(i) => num_inputs + i,
+            };
+            self.columns.push(column);
+            self.values.push(*coeff);
+        }
+        self.row_starts.push(self.columns.len());
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.row_starts.len() - 1
+    }
+
+    /// The number of entries which are stored, i.e. the number of terms of all rows.
+    pub fn num_entries(&self) -> usize {
+        self.values.len()
+    }
+
+    /// The entries of a row as pairs of column and value.
+    pub fn row(&self, row: usize) -> impl Iterator<Item = (usize, &Scalar)> + '_ {
+        let range = self.row_starts[row]..self.row_starts[row + 1];
+        self.columns[range.clone()]
+            .iter()
+            .cloned()
+            .zip(&self.values[range])
+    }
+
+    /// Multiplies the matrix with the vector of all variables, given as the assignment of
+    /// the inputs and of the auxiliary variables.
+    pub fn mul_assignment(
+        &self,
+        input_assignment: &[Scalar],
+        aux_assignment: &[Scalar],
+    ) -> Vec<Scalar> {
+        let num_inputs = input_assignment.len();
+        let one = Scalar::one();
+
+        (0..self.num_rows())
+            .into_par_iter()
+            .map(|row| {
+                let mut acc = Scalar::zero();
+                for (column, coeff) in self.row(row) {
+                    let value = if column < num_inputs {
+                        input_assignment[column]
+                    } else {
+                        aux_assignment[column - num_inputs]
+                    };
+                    if coeff == &one {
+                        acc += value;
+                    } else {
+                        acc += value * coeff;
+                    }
+                }
+                acc
+            })
+            .collect()
+    }
+
+    /// Marks the auxiliary variables, or the inputs if `inputs` is set, which appear in the
+    /// matrix, like the prover does while synthesizing.
+    fn density(&self, num_inputs: usize, num_aux: usize, inputs: bool) -> DensityTracker {
+        let (range, len) = if inputs {
+            (0..num_inputs, num_inputs)
+        } else {
+            (num_inputs..num_inputs + num_aux, num_aux)
+        };
+
+        let mut density = DensityTracker::new();
+        for _ in 0..len {
+            density.add_element();
+        }
+        for column in &self.columns {
+            if range.contains(column) {
+                density.inc(column - range.start);
+            }
+        }
+
+        density
+    }
+}
+
+/// The constraint matrices A, B and C of a circuit, captured once by synthesizing it, see
+/// [`R1csShape::from_circuit`].
+///
+/// Circuits which are proven many times don't need to be synthesized again for every proof:
+/// given the assignment of all variables, the shape evaluates the constraints directly,
+/// see [`create_proof_from_assignments_batch_priority`](super::create_proof_from_assignments_batch_priority).
+/// Parameters are generated from the shape by replaying its constraints, see
+/// [`R1csShape::circuit`].
+///
+/// The input constraints `x * 0 = 0`, which the parameter generator and the prover add for
+/// every input, are not part of the matrices.
+#[derive(Clone, Debug)]
+pub struct R1csShape<Scalar: PrimeField> {
+    num_inputs: usize,
+    num_aux: usize,
+    a: SparseMatrix<Scalar>,
+    b: SparseMatrix<Scalar>,
+    c: SparseMatrix<Scalar>,
+    // Densities of the queries, which only depend on the matrices.
+    a_aux_density: DensityTracker,
+    b_input_density: DensityTracker,
+    b_aux_density: DensityTracker,
+    digest: CircuitDigest,
+}
+
+impl<Scalar: PrimeField> R1csShape<Scalar> {
+    /// Synthesizes the circuit without a witness and records its constraints.
+    pub fn from_circuit<C: Circuit<Scalar>>(circuit: C) -> Result<Self, SynthesisError> {
+        let mut cs = ShapeCS::new();
+        cs.alloc_input(|| "", || Ok(Scalar::one()))?;
+        circuit.synthesize(&mut cs)?;
+
+        Ok(Self::from_shape_cs(&cs))
+    }
+
+    /// Captures the constraints of a circuit synthesized into `cs`, which must start with
+    /// the input for the constant one.
+    pub fn from_shape_cs(cs: &ShapeCS<Scalar>) -> Self {
+        let num_inputs = cs.num_inputs();
+        let num_aux = cs.num_aux();
+
+        let mut a = SparseMatrix::new();
+        let mut b = SparseMatrix::new();
+        let mut c = SparseMatrix::new();
+        for (a_lc, b_lc, c_lc) in cs.constraints() {
+            a.push_row(a_lc, num_inputs);
+            b.push_row(b_lc, num_inputs);
+            c.push_row(c_lc, num_inputs);
+        }
+
+        R1csShape {
+            num_inputs,
+            num_aux,
+            // Inputs have full density in the A query because of the input constraints.
+            a_aux_density: a.density(num_inputs, num_aux, false),
+            b_input_density: b.density(num_inputs, num_aux, true),
+            b_aux_density: b.density(num_inputs, num_aux, false),
+            a,
+            b,
+            c,
+            digest: cs.digest(),
+        }
+    }
+
+    /// The number of inputs, including the constant one.
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    /// The number of constraints, without the input constraints.
+    pub fn num_constraints(&self) -> usize {
+        self.a.num_rows()
+    }
+
+    pub fn a(&self) -> &SparseMatrix<Scalar> {
+        &self.a
+    }
+
+    pub fn b(&self) -> &SparseMatrix<Scalar> {
+        &self.b
+    }
+
+    pub fn c(&self) -> &SparseMatrix<Scalar> {
+        &self.c
+    }
+
+    /// The digest of the circuit, which is the same as for the circuit the shape was
+    /// captured from.
+    pub fn digest(&self) -> CircuitDigest {
+        self.digest
+    }
+
+    /// A circuit which replays the constraints, without an assignment. Used to generate
+    /// parameters for the shape.
+    pub fn circuit(&self) -> ShapeCircuit<'_, Scalar> {
+        ShapeCircuit {
+            shape: self,
+            assignment: None,
+        }
+    }
+
+    /// A circuit which replays the constraints with the given assignment.
+    pub fn circuit_with_assignment<'a>(
+        &'a self,
+        assignment: &'a Assignment<Scalar>,
+    ) -> ShapeCircuit<'a, Scalar> {
+        ShapeCircuit {
+            shape: self,
+            assignment: Some(assignment),
+        }
+    }
+
+    fn check_assignment(&self, assignment: &Assignment<Scalar>) -> Result<(), SynthesisError> {
+        if assignment.inputs.len() != self.num_inputs || assignment.aux.len() != self.num_aux {
+            return Err(SynthesisError::IncompatibleLengthVector(format!(
+                "assignment of {} inputs and {} auxiliary variables, expected {} and {}",
+                assignment.inputs.len(),
+                assignment.aux.len(),
+                self.num_inputs,
+                self.num_aux
+            )));
+        }
+        if assignment.inputs[0] != Scalar::one() {
+            return Err(SynthesisError::Unsatisfiable);
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the constraints for `assignment`, which results in the same witness as
+    /// synthesizing the circuit with that assignment.
+    pub fn witness(
+        &self,
+        assignment: Assignment<Scalar>,
+    ) -> Result<Witness<Scalar>, SynthesisError> {
+        self.check_assignment(&assignment)?;
+        let Assignment { inputs, aux } = assignment;
+
+        let mut a = self.a.mul_assignment(&inputs, &aux);
+        let mut b = self.b.mul_assignment(&inputs, &aux);
+        let mut c = self.c.mul_assignment(&inputs, &aux);
+
+        // x * 0 = 0
+        a.extend_from_slice(&inputs);
+        b.resize(a.len(), Scalar::zero());
+        c.resize(a.len(), Scalar::zero());
+
+        Ok(Witness {
+            a_aux_density: self.a_aux_density.clone(),
+            b_input_density: self.b_input_density.clone(),
+            b_aux_density: self.b_aux_density.clone(),
+            a,
+            b,
+            c,
+            input_assignment: inputs,
+            aux_assignment: aux,
+        })
+    }
+}
+
+/// The values of all variables of a circuit, which together with its [`R1csShape`] are all
+/// that is needed to create a proof.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment<Scalar: PrimeField> {
+    /// The assignment of the inputs, starting with the constant one.
+    pub inputs: Vec<Scalar>,
+    pub aux: Vec<Scalar>,
+}
+
+impl<Scalar: PrimeField> From<&Witness<Scalar>> for Assignment<Scalar> {
+    fn from(witness: &Witness<Scalar>) -> Self {
+        Assignment {
+            inputs: witness.input_assignment.clone(),
+            aux: witness.aux_assignment.clone(),
+        }
+    }
+}
+
+/// Replays the constraints of an [`R1csShape`], see [`R1csShape::circuit`].
+#[derive(Clone, Copy)]
+pub struct ShapeCircuit<'a, Scalar: PrimeField> {
+    shape: &'a R1csShape<Scalar>,
+    assignment: Option<&'a Assignment<Scalar>>,
+}
+
+impl<'a, Scalar: PrimeField> Circuit<Scalar> for ShapeCircuit<'a, Scalar> {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let shape = self.shape;
+        if let Some(assignment) = self.assignment {
+            shape.check_assignment(assignment)?;
+        }
+
+        let value = |get: fn(&Assignment<Scalar>, usize) -> Scalar, i: usize| {
+            move || {
+                self.assignment
+                    .map(|assignment| get(assignment, i))
+                    .ok_or(SynthesisError::AssignmentMissing)
+            }
+        };
+
+        // Variables are allocated in the order of their indices, so the constraint system
+        // is the same as the one of the original circuit.
+        let mut variables = Vec::with_capacity(shape.num_inputs + shape.num_aux);
+        variables.push(CS::one());
+        for i in 1..shape.num_inputs {
+            let get = |assignment: &Assignment<Scalar>, i: usize| assignment.inputs[i];
+            variables.push(cs.alloc_input(|| format!("input {}", i), value(get, i))?);
+        }
+        for i in 0..shape.num_aux {
+            let get = |assignment: &Assignment<Scalar>, i: usize| assignment.aux[i];
+            variables.push(cs.alloc(|| format!("aux {}", i), value(get, i))?);
+        }
+
+        let lc = |matrix: &SparseMatrix<Scalar>, row: usize, lc: LinearCombination<Scalar>| {
+            matrix
+                .row(row)
+                .fold(lc, |lc, (column, coeff)| lc + (*coeff, variables[column]))
+        };
+        for row in 0..shape.num_constraints() {
+            cs.enforce(
+                || format!("constraint {}", row),
+                |l| lc(&shape.a, row, l),
+                |l| lc(&shape.b, row, l),
+                |l| lc(&shape.c, row, l),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, create_random_proof_from_assignment, generate_random_parameters,
+        prepare_verifying_key, synthesize_witness, verify_proof,
+    };
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of the square roots of the public inputs, and that their sum is
+    // `factor` times the sum of the inputs. Inputs and auxiliary variables are allocated
+    // alternately.
+    #[derive(Clone)]
+    struct RootsCircuit {
+        roots: Vec<Option<Fr>>,
+        factor: u64,
+    }
+
+    impl Circuit<Fr> for RootsCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut sum_roots = LinearCombination::zero();
+            let mut sum_squares = LinearCombination::zero();
+            for (i, root) in self.roots.iter().enumerate() {
+                let x = cs.alloc(
+                    || format!("root {}", i),
+                    || root.ok_or(SynthesisError::AssignmentMissing),
+                )?;
+                let y = cs.alloc_input(
+                    || format!("square {}", i),
+                    || {
+                        root.map(|r| r.square())
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    },
+                )?;
+                cs.enforce(
+                    || format!("square {}", i),
+                    |lc| lc + x,
+                    |lc| lc + x,
+                    |lc| lc + y,
+                );
+                sum_roots = sum_roots + x;
+                sum_squares = sum_squares + (Fr::from(self.factor), y);
+            }
+
+            let sum = cs.alloc(
+                || "sum",
+                || {
+                    self.roots
+                        .iter()
+                        .try_fold(Fr::zero(), |acc, root| root.map(|r| acc + r))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(
+                || "sum",
+                |lc| lc + &sum_roots,
+                |lc| lc + CS::one(),
+                |lc| lc + sum,
+            );
+            // Only there to make the C matrix interesting.
+            cs.enforce(
+                || "scaled squares",
+                |lc| lc + &sum_squares,
+                |lc| lc,
+                |lc| lc,
+            );
+
+            Ok(())
+        }
+    }
+
+    fn circuit(roots: &[Fr], factor: u64) -> RootsCircuit {
+        RootsCircuit {
+            roots: roots.iter().cloned().map(Some).collect(),
+            factor,
+        }
+    }
+
+    fn blank(len: usize, factor: u64) -> RootsCircuit {
+        RootsCircuit {
+            roots: vec![None; len],
+            factor,
+        }
+    }
+
+    #[test]
+    fn test_r1cs_shape() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let roots = (0..5).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let shape = R1csShape::from_circuit(blank(5, 3)).unwrap();
+        assert_eq!(shape.num_inputs(), 6);
+        assert_eq!(shape.num_aux(), 6);
+        assert_eq!(shape.num_constraints(), 7);
+        assert_eq!(shape.a().num_entries(), 5 + 5 + 5);
+        assert_eq!(
+            shape.digest(),
+            CircuitDigest::of_circuit(blank(5, 3)).unwrap()
+        );
+
+        // Evaluating the matrices gives exactly the witness the prover synthesizes.
+        let witness = synthesize_witness(circuit(&roots, 3)).unwrap();
+        let assignment = Assignment::from(&witness);
+        assert!(shape.witness(assignment.clone()).unwrap() == witness);
+
+        // Replaying the shape results in the same circuit.
+        let replayed = R1csShape::from_circuit(shape.circuit()).unwrap();
+        assert_eq!(replayed.digest(), shape.digest());
+        assert_eq!(replayed.a(), shape.a());
+        assert_eq!(replayed.b(), shape.b());
+        assert_eq!(replayed.c(), shape.c());
+        let mut cs = TestConstraintSystem::new();
+        shape
+            .circuit_with_assignment(&assignment)
+            .synthesize(&mut cs)
+            .unwrap();
+        assert!(cs.is_satisfied());
+        assert_eq!(cs.num_inputs(), 6);
+
+        // Parameters for the shape and for the circuit are interchangeable.
+        let squares = roots.iter().map(|r| r.square()).collect::<Vec<_>>();
+        let shape_params = generate_random_parameters::<Bls12, _, _>(shape.circuit(), rng).unwrap();
+        assert_eq!(shape_params.circuit_digest, Some(shape.digest()));
+        let pvk = prepare_verifying_key(&shape_params.vk);
+        let proof =
+            create_random_proof_from_assignment(&shape, assignment.clone(), &shape_params, rng)
+                .unwrap();
+        assert!(verify_proof(&pvk, &proof, &squares).unwrap());
+        let proof = create_random_proof(circuit(&roots, 3), &shape_params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &squares).unwrap());
+
+        let params = generate_random_parameters::<Bls12, _, _>(blank(5, 3), rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        let proof =
+            create_random_proof_from_assignment(&shape, assignment.clone(), &params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &squares).unwrap());
+
+        // The shape of another circuit is rejected.
+        let other = R1csShape::from_circuit(blank(5, 2)).unwrap();
+        assert!(matches!(
+            create_random_proof_from_assignment(&other, assignment.clone(), &params, rng),
+            Err(SynthesisError::CircuitDigestMismatch { .. })
+        ));
+
+        // So are assignments which don't fit.
+        let mut short = assignment;
+        short.aux.pop();
+        assert!(shape.witness(short.clone()).is_err());
+        assert!(create_random_proof_from_assignment(&shape, short, &params, rng).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::ops::{AddAssign, Mul, MulAssign, SubAssign};
+
+use ff::Field;
+use group::{prime::PrimeCurveAffine, Curve, Group, WnafGroup};
+use pairing::{Engine, MultiMillerLoop};
+use rand_core::RngCore;
+
+use super::{generate_parameters, Parameters, Proof, VerifyingKey};
+use crate::gpu;
+use crate::{Circuit, SynthesisError};
+
+/// The toxic waste of a parameter setup.
+///
+/// Anyone knowing the trapdoor can create proofs which verify for arbitrary public inputs,
+/// without knowing a witness. It must only ever be kept for testing, see
+/// [`generate_parameters_with_trapdoor`] and [`simulate_proof`].
+#[derive(Clone, Debug)]
+pub struct Trapdoor<E: Engine> {
+    pub g1: E::G1,
+    pub g2: E::G2,
+    pub alpha: E::Fr,
+    pub beta: E::Fr,
+    pub gamma: E::Fr,
+    pub delta: E::Fr,
+    pub tau: E::Fr,
+}
+
+impl<E: Engine> Trapdoor<E> {
+    /// Samples a random trapdoor.
+    pub fn random<R: RngCore>(rng: &mut R) -> Self {
+        Trapdoor {
+            g1: E::G1::random(&mut *rng),
+            g2: E::G2::random(&mut *rng),
+            alpha: E::Fr::random(&mut *rng),
+            beta: E::Fr::random(&mut *rng),
+            gamma: E::Fr::random(&mut *rng),
+            delta: E::Fr::random(&mut *rng),
+            tau: E::Fr::random(&mut *rng),
+        }
+    }
+}
+
+/// Generates a random common reference string for a circuit, like
+/// [`generate_random_parameters`](super::generate_random_parameters), but also returns
+/// the toxic waste.
+///
+/// Only use this for testing: the returned trapdoor allows to create proofs of false
+/// statements with [`simulate_proof`].
+pub fn generate_parameters_with_trapdoor<E, C, R>(
+    circuit: C,
+    rng: &mut R,
+) -> Result<(Parameters<E>, Trapdoor<E>), SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    <E as Engine>::G1: WnafGroup,
+    <E as Engine>::G2: WnafGroup,
+    C: Circuit<E::Fr>,
+    R: RngCore,
+{
+    let trapdoor = Trapdoor::random(rng);
+    let params = generate_parameters::<E, C>(
+        circuit,
+        trapdoor.g1,
+        trapdoor.g2,
+        trapdoor.alpha,
+        trapdoor.beta,
+        trapdoor.gamma,
+        trapdoor.delta,
+        trapdoor.tau,
+    )?;
+
+    Ok((params, trapdoor))
+}
+
+/// Creates a proof for the given public inputs using the trapdoor of the parameters,
+/// without a witness. The proof verifies against `vk`, which must have been generated
+/// with `trapdoor`.
+///
+/// Simulated proofs are distributed identically to honestly created ones, which is what
+/// makes Groth16 zero-knowledge.
+pub fn simulate_proof<E, R>(
+    trapdoor: &Trapdoor<E>,
+    vk: &VerifyingKey<E>,
+    public_inputs: &[E::Fr],
+    rng: &mut R,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: MultiMillerLoop,
+    R: RngCore,
+{
+    let a = E::Fr::random(&mut *rng);
+    let b = E::Fr::random(&mut *rng);
+
+    create_simulated_proof(trapdoor, vk, public_inputs, a, b)
+}
+
+/// Creates a simulated proof with `A = a * G1` and `B = b * G2`, see [`simulate_proof`].
+///
+/// `C` is then the unique element for which the proof verifies:
+/// `C = ((a * b - alpha * beta) * G1 - gamma * sum(inputs * IC)) / delta`.
+pub fn create_simulated_proof<E>(
+    trapdoor: &Trapdoor<E>,
+    vk: &VerifyingKey<E>,
+    public_inputs: &[E::Fr],
+    a: E::Fr,
+    b: E::Fr,
+) -> Result<Proof<E>, SynthesisError>
+where
+    E: MultiMillerLoop,
+{
+    if (public_inputs.len() + 1) != vk.ic.len() {
+        return Err(SynthesisError::MalformedVerifyingKey);
+    }
+
+    let delta_inverse: Option<E::Fr> = trapdoor.delta.invert().into();
+    let delta_inverse = delta_inverse.ok_or(SynthesisError::UnexpectedIdentity)?;
+
+    let mut acc = vk.ic[0].to_curve();
+    for (input, ic) in public_inputs.iter().zip(vk.ic.iter().skip(1)) {
+        AddAssign::<&E::G1>::add_assign(&mut acc, &ic.mul(input));
+    }
+    acc.mul_assign(trapdoor.gamma);
+
+    let mut ab = a;
+    ab.mul_assign(&b);
+    let mut alpha_beta = trapdoor.alpha;
+    alpha_beta.mul_assign(&trapdoor.beta);
+    ab.sub_assign(&alpha_beta);
+
+    let mut c = trapdoor.g1.mul(ab);
+    c.sub_assign(&acc);
+    c.mul_assign(delta_inverse);
+
+    Ok(Proof {
+        a: trapdoor.g1.mul(a).to_affine(),
+        b: trapdoor.g2.mul(b).to_affine(),
+        c: c.to_affine(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{create_random_proof, prepare_verifying_key, verify_proof};
+    use crate::{ConstraintSystem, SynthesisError};
+    use blstrs::{Bls12, Scalar as Fr};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of a cube root of the public input.
+    #[derive(Clone)]
+    struct CubeRootCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for CubeRootCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x = cs.alloc(|| "x", || self.x.ok_or(SynthesisError::AssignmentMissing))?;
+            let x2 = cs.alloc(
+                || "x^2",
+                || {
+                    self.x
+                        .map(|x| x.square())
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    self.x
+                        .map(|x| x.square() * x)
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+            cs.enforce(|| "x * x = x^2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x^2 * x = y", |lc| lc + x2, |lc| lc + x, |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_simulate_proof() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let (params, trapdoor) =
+            generate_parameters_with_trapdoor::<Bls12, _, _>(CubeRootCircuit { x: None }, rng)
+                .unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        // Honest proofs still verify.
+        let x = Fr::random(&mut *rng);
+        let proof =
+            create_random_proof(CubeRootCircuit { x: Some(x) }, &params, &mut *rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &[x.square() * x]).unwrap());
+
+        // Simulated proofs verify for any input, whether there is a witness or not.
+        for _ in 0..5 {
+            let y = Fr::random(&mut *rng);
+            let proof = simulate_proof(&trapdoor, &params.vk, &[y], &mut *rng).unwrap();
+            assert!(verify_proof(&pvk, &proof, &[y]).unwrap());
+            assert!(!verify_proof(&pvk, &proof, &[y + Fr::one()]).unwrap());
+        }
+
+        // Wrong number of inputs
+        assert!(simulate_proof(&trapdoor, &params.vk, &[], &mut *rng).is_err());
+
+        // A different trapdoor doesn't produce valid proofs.
+        let other = Trapdoor::<Bls12>::random(rng);
+        let proof = simulate_proof(&other, &params.vk, &[Fr::one()], &mut *rng).unwrap();
+        assert!(!verify_proof(&pvk, &proof, &[Fr::one()]).unwrap());
+    }
+}
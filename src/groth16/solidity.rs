@@ -0,0 +1,344 @@
+//! Export of Groth16 verifying keys over BLS12-381 as Solidity verifier contracts.
+//!
+//! The generated contract verifies proofs on chain using the BLS12-381 precompiles of
+//! [EIP-2537], which are available on Ethereum since the Prague/Electra upgrade. It exposes
+//!
+//! ```solidity
+//! function verifyProof(bytes calldata proof, uint256[] calldata input) external view returns (bool);
+//! ```
+//!
+//! where `proof` is encoded by [`encode_proof`] and `input` holds the public inputs as
+//! integers. [`encode_calldata`] encodes a complete call of `verifyProof`.
+//!
+//! [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+
+use std::fmt::Write;
+
+use blstrs::{Bls12, G1Affine, G2Affine, Scalar as Fr};
+use ff::PrimeField;
+use group::prime::PrimeCurveAffine;
+
+use super::{Proof, VerifyingKey};
+
+/// Size of an encoded G1 point: two 64 byte base field elements.
+pub const G1_SIZE: usize = 128;
+
+/// Size of an encoded G2 point: two elements of the quadratic extension field.
+pub const G2_SIZE: usize = 256;
+
+/// Size of a proof encoded by [`encode_proof`].
+pub const PROOF_SIZE: usize = 2 * G1_SIZE + G2_SIZE;
+
+/// The function selector of `verifyProof(bytes,uint256[])`.
+const VERIFY_PROOF_SELECTOR: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+
+/// Generates the source of a Solidity contract named `contract_name` which verifies proofs
+/// for `vk`.
+///
+/// The verifying key is embedded into the contract, with beta, gamma and delta negated so
+/// that verification is a single call of the pairing precompile.
+pub fn verifier_contract(vk: &VerifyingKey<Bls12>, contract_name: &str) -> String {
+    let ic = vk.ic.iter().map(encode_g1).collect::<Vec<_>>();
+
+    let mut source = String::new();
+    writeln!(
+        source,
+        r#"// SPDX-License-Identifier: MIT OR Apache-2.0
+pragma solidity ^0.8.0;
+
+/// @notice Groth16 verifier over BLS12-381, generated by bellperson.
+/// @dev Requires the EIP-2537 precompiles.
+contract {name} {{
+    // Order of the scalar field of BLS12-381
+    uint256 constant R = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001;
+
+    // EIP-2537 precompiles
+    address constant BLS12_G1MSM = address(0x0c);
+    address constant BLS12_PAIRING_CHECK = address(0x0f);
+
+    uint256 constant NUM_INPUTS = {num_inputs};
+    uint256 constant PROOF_SIZE = {proof_size};
+
+    bytes constant ALPHA_G1 = hex"{alpha_g1}";
+    bytes constant NEG_BETA_G2 = hex"{neg_beta_g2}";
+    bytes constant NEG_GAMMA_G2 = hex"{neg_gamma_g2}";
+    bytes constant NEG_DELTA_G2 = hex"{neg_delta_g2}";
+    bytes constant IC = hex"{ic}";
+
+    /// @notice Verifies `proof` for the public inputs `input`.
+    /// @param proof The points A (G1), B (G2) and C (G1) in the EIP-2537 encoding.
+    /// @param input The public inputs, each less than the scalar field order.
+    function verifyProof(bytes calldata proof, uint256[] calldata input) external view returns (bool) {{
+        require(proof.length == PROOF_SIZE, "invalid proof length");
+        require(input.length == NUM_INPUTS, "invalid number of public inputs");
+
+        // vk_x = IC[0] + sum(input[i] * IC[i + 1]), computed as a multi-scalar
+        // multiplication over (point, scalar) pairs of 128 + 32 bytes.
+        bytes memory ic = IC;
+        bytes memory msmInput = new bytes(160 * (NUM_INPUTS + 1));
+        for (uint256 i = 0; i <= NUM_INPUTS; i++) {{
+            uint256 scalar = 1;
+            if (i > 0) {{
+                scalar = input[i - 1];
+                require(scalar < R, "public input is not a field element");
+            }}
+            assembly {{
+                let src := add(add(ic, 32), mul(i, 128))
+                let dst := add(add(msmInput, 32), mul(i, 160))
+                mstore(dst, mload(src))
+                mstore(add(dst, 32), mload(add(src, 32)))
+                mstore(add(dst, 64), mload(add(src, 64)))
+                mstore(add(dst, 96), mload(add(src, 96)))
+                mstore(add(dst, 128), scalar)
+            }}
+        }}
+        (bool ok, bytes memory vkX) = BLS12_G1MSM.staticcall(msmInput);
+        require(ok && vkX.length == 128, "G1 multi-scalar multiplication failed");
+
+        // e(A, B) * e(alpha, -beta) * e(vk_x, -gamma) * e(C, -delta) == 1
+        bytes memory pairingInput = abi.encodePacked(
+            proof[0:384],
+            ALPHA_G1,
+            NEG_BETA_G2,
+            vkX,
+            NEG_GAMMA_G2,
+            proof[384:512],
+            NEG_DELTA_G2
+        );
+        bytes memory result;
+        (ok, result) = BLS12_PAIRING_CHECK.staticcall(pairingInput);
+        // The precompile fails for points which are not on the curve or not in the
+        // prime order subgroup.
+        if (!ok || result.length != 32) {{
+            return false;
+        }}
+        return abi.decode(result, (uint256)) == 1;
+    }}
+}}"#,
+        name = contract_name,
+        num_inputs = vk.ic.len() - 1,
+        proof_size = PROOF_SIZE,
+        alpha_g1 = hex::encode(encode_g1(&vk.alpha_g1)),
+        neg_beta_g2 = hex::encode(encode_g2(&-vk.beta_g2)),
+        neg_gamma_g2 = hex::encode(encode_g2(&-vk.gamma_g2)),
+        neg_delta_g2 = hex::encode(encode_g2(&-vk.delta_g2)),
+        ic = hex::encode(ic.concat()),
+    )
+    .expect("writing to a string cannot fail");
+
+    source
+}
+
+/// Encodes a proof as the `proof` argument of `verifyProof`: the points A, B and C in the
+/// EIP-2537 encoding, [`PROOF_SIZE`] bytes in total.
+pub fn encode_proof(proof: &Proof<Bls12>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(PROOF_SIZE);
+    out.extend_from_slice(&encode_g1(&proof.a));
+    out.extend_from_slice(&encode_g2(&proof.b));
+    out.extend_from_slice(&encode_g1(&proof.c));
+    out
+}
+
+/// Encodes a public input as an `uint256`, i.e. a 32 byte big-endian integer.
+pub fn encode_input(input: &Fr) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(input.to_repr().as_ref());
+    out.reverse();
+    out
+}
+
+/// Encodes the ABI calldata of a call of `verifyProof(proof, public_inputs)`, including
+/// the function selector.
+pub fn encode_calldata(proof: &Proof<Bls12>, public_inputs: &[Fr]) -> Vec<u8> {
+    let proof = encode_proof(proof);
+
+    let mut out = Vec::with_capacity(4 + 32 * (5 + public_inputs.len()) + PROOF_SIZE);
+    out.extend_from_slice(&VERIFY_PROOF_SELECTOR);
+
+    // Head: offsets of the two dynamic arguments, relative to the start of the arguments.
+    // `PROOF_SIZE` is a multiple of 32, so the proof doesn't need any padding.
+    out.extend_from_slice(&encode_u256(64));
+    out.extend_from_slice(&encode_u256(64 + 32 + PROOF_SIZE as u64));
+
+    // Tail: the length followed by the contents of each argument.
+    out.extend_from_slice(&encode_u256(PROOF_SIZE as u64));
+    out.extend_from_slice(&proof);
+    out.extend_from_slice(&encode_u256(public_inputs.len() as u64));
+    for input in public_inputs {
+        out.extend_from_slice(&encode_input(input));
+    }
+
+    out
+}
+
+/// Encodes a G1 point as defined by EIP-2537: the big-endian coordinates x and y, each
+/// padded to 64 bytes. The point at infinity is encoded as all zeroes.
+fn encode_g1(p: &G1Affine) -> [u8; G1_SIZE] {
+    let mut out = [0u8; G1_SIZE];
+    if bool::from(p.is_identity()) {
+        return out;
+    }
+
+    // The uncompressed encoding is x || y, and no flags are set for affine points.
+    let uncompressed = p.to_uncompressed();
+    let bytes = uncompressed.as_ref();
+    out[16..64].copy_from_slice(&bytes[0..48]);
+    out[80..128].copy_from_slice(&bytes[48..96]);
+    out
+}
+
+/// Encodes a G2 point as defined by EIP-2537: the coordinates x and y, each encoded as
+/// `c0 || c1`, where both are padded to 64 bytes. The point at infinity is encoded as all
+/// zeroes.
+fn encode_g2(p: &G2Affine) -> [u8; G2_SIZE] {
+    let mut out = [0u8; G2_SIZE];
+    if bool::from(p.is_identity()) {
+        return out;
+    }
+
+    // The uncompressed encoding is x.c1 || x.c0 || y.c1 || y.c0.
+    let uncompressed = p.to_uncompressed();
+    let bytes = uncompressed.as_ref();
+    out[16..64].copy_from_slice(&bytes[48..96]);
+    out[80..128].copy_from_slice(&bytes[0..48]);
+    out[144..192].copy_from_slice(&bytes[144..192]);
+    out[208..256].copy_from_slice(&bytes[96..144]);
+    out
+}
+
+fn encode_u256(n: u64) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[24..].copy_from_slice(&n.to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{create_random_proof, generate_random_parameters};
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use ff::Field;
+    use group::{Curve, Group};
+    use pairing::{MillerLoopResult, MultiMillerLoop};
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of the factors of the public input.
+    #[derive(Clone)]
+    struct FactorCircuit {
+        a: Option<Fr>,
+        b: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for FactorCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || "c",
+                || {
+                    let mut c = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                    c *= self.b.ok_or(SynthesisError::AssignmentMissing)?;
+                    Ok(c)
+                },
+            )?;
+            cs.enforce(|| "a * b = c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+
+            Ok(())
+        }
+    }
+
+    fn padded(s: &str) -> Vec<u8> {
+        let mut out = vec![0u8; 16];
+        out.extend(hex::decode(s).unwrap());
+        out
+    }
+
+    #[test]
+    fn test_encode_generators() {
+        let g1 = padded("17f1d3a73197d7942695638c4fa9ac0fc3688c4f9774b905a14e3a3f171bac586c55e83ff97a1aeffb3af00adb22c6bb");
+        let g1 = [g1, padded("08b3f481e3aaa0f1a09e30ed741d8ae4fcf5e095d5d00af600db18cb2c04b3edd03cc744a2888ae40caa232946c5e7e1")].concat();
+        assert_eq!(&encode_g1(&G1Affine::generator())[..], &g1[..]);
+
+        let g2 = [
+            padded("024aa2b2f08f0a91260805272dc51051c6e47ad4fa403b02b4510b647ae3d1770bac0326a805bbefd48056c8c121bdb8"),
+            padded("13e02b6052719f607dacd3a088274f65596bd0d09920b61ab5da61bbdc7f5049334cf11213945d57e5ac7d055d042b7e"),
+            padded("0ce5d527727d6e118cc9cdc6da2e351aadfd9baa8cbdd3a76d429a695160d12c923ac9cc3baca289e193548608b82801"),
+            padded("0606c4a02ea734cc32acd2b02bc28b99cb3e287e85a763af267492ab572e99ab3f370d275cec1da1aaa9075ff05f79be"),
+        ]
+        .concat();
+        assert_eq!(&encode_g2(&G2Affine::generator())[..], &g2[..]);
+
+        assert_eq!(encode_g1(&G1Affine::identity()), [0u8; G1_SIZE]);
+        assert_eq!(encode_g2(&G2Affine::identity()), [0u8; G2_SIZE]);
+
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(encode_input(&Fr::one()), one);
+    }
+
+    #[test]
+    fn test_verifier_contract() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(FactorCircuit { a: None, b: None }, rng)
+                .unwrap();
+        let vk = &params.vk;
+
+        let a = Fr::random(&mut *rng);
+        let b = Fr::random(&mut *rng);
+        let c = a * b;
+        let proof = create_random_proof(
+            FactorCircuit {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            rng,
+        )
+        .unwrap();
+
+        let source = verifier_contract(vk, "FactorVerifier");
+        assert!(source.contains("contract FactorVerifier {"));
+        assert!(source.contains("uint256 constant NUM_INPUTS = 1;"));
+        assert!(source.contains(&format!(
+            "bytes constant NEG_DELTA_G2 = hex\"{}\";",
+            hex::encode(encode_g2(&-vk.delta_g2))
+        )));
+
+        // The equation checked by the contract holds for the proof.
+        let vk_x = (vk.ic[0].to_curve() + vk.ic[1] * c).to_affine();
+        let neg_beta = (-vk.beta_g2).into();
+        let neg_gamma = (-vk.gamma_g2).into();
+        let neg_delta = (-vk.delta_g2).into();
+        let result = Bls12::multi_miller_loop(&[
+            (&proof.a, &proof.b.into()),
+            (&vk.alpha_g1, &neg_beta),
+            (&vk_x, &neg_gamma),
+            (&proof.c, &neg_delta),
+        ])
+        .final_exponentiation();
+        assert!(bool::from(result.is_identity()));
+
+        let calldata = encode_calldata(&proof, &[c]);
+        assert_eq!(calldata.len(), 4 + 32 * 3 + PROOF_SIZE + 32 * 2);
+        assert_eq!(&calldata[..4], &VERIFY_PROOF_SELECTOR);
+        assert_eq!(calldata[4 + 31], 64);
+        assert_eq!(
+            &calldata[4 + 32..4 + 64],
+            &encode_u256(96 + PROOF_SIZE as u64)
+        );
+        assert_eq!(calldata[4 + 64 + 30..4 + 96], [2, 0]);
+        assert_eq!(
+            &calldata[4 + 96..4 + 96 + PROOF_SIZE],
+            &encode_proof(&proof)[..]
+        );
+        assert_eq!(calldata[4 + 96 + PROOF_SIZE + 31], 1);
+        assert_eq!(&calldata[calldata.len() - 32..], &encode_input(&c));
+    }
+}
@@ -435,6 +435,71 @@ fn test_create_batch_single() {
     }
 }
 
+#[test]
+fn test_simulated_proof() {
+    use crate::groth16::{create_simulated_proof, Trapdoor};
+
+    let trapdoor = Trapdoor::<DummyEngine> {
+        g1: Fr::one(),
+        g2: Fr::one(),
+        alpha: Fr::from(48577u64),
+        beta: Fr::from(22580u64),
+        gamma: Fr::from(53332u64),
+        delta: Fr::from(5481u64),
+        tau: Fr::from(3673u64),
+    };
+
+    let params = {
+        let c = XorDemo::<Fr> {
+            a: None,
+            b: None,
+            _marker: PhantomData,
+        };
+
+        generate_parameters::<DummyEngine, _>(
+            c,
+            trapdoor.g1,
+            trapdoor.g2,
+            trapdoor.alpha,
+            trapdoor.beta,
+            trapdoor.gamma,
+            trapdoor.delta,
+            trapdoor.tau,
+        )
+        .unwrap()
+    };
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    let r = Fr::from(27134u64);
+    let s = Fr::from(17146u64);
+
+    let c = XorDemo {
+        a: Some(true),
+        b: Some(false),
+        _marker: PhantomData,
+    };
+    let proof = create_proof(c, &params, r, s).unwrap();
+
+    // Given the same A and B, the simulator produces exactly the honest proof, without
+    // knowing the witness. As A and B are uniformly random in honest proofs, the proofs
+    // reveal nothing about the witness.
+    let simulated =
+        create_simulated_proof(&trapdoor, &params.vk, &[Fr::one()], proof.a, proof.b).unwrap();
+    assert_eq!(simulated, proof);
+
+    // Simulated proofs verify for statements which have no witness.
+    let simulated = create_simulated_proof(
+        &trapdoor,
+        &params.vk,
+        &[Fr::from(2u64)],
+        Fr::from(3u64),
+        Fr::from(5u64),
+    )
+    .unwrap();
+    assert!(verify_proof(&pvk, &simulated, &[Fr::from(2u64)]).unwrap());
+}
+
 #[test]
 fn test_verify_random_single() {
     use crate::groth16::{create_random_proof, generate_random_parameters, Proof};
@@ -1,4 +1,4 @@
-use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+use group::{prime::PrimeCurveAffine, GroupEncoding, UncompressedEncoding};
 use pairing::{Engine, MultiMillerLoop};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
@@ -53,6 +53,13 @@ fn read_uncompressed_point<C: UncompressedEncoding>(repr: &C::Uncompressed) -> i
     Option::from(opt).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
 }
 
+fn read_compressed_point<C: GroupEncoding, R: Read>(reader: &mut R) -> io::Result<C> {
+    let mut repr = C::Repr::default();
+    reader.read_exact(repr.as_mut())?;
+    let opt = C::from_bytes(&repr);
+    Option::from(opt).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
+}
+
 impl<E: Engine + MultiMillerLoop> VerifyingKey<E> {
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_all(self.alpha_g1.to_uncompressed().as_ref())?;
@@ -69,6 +76,57 @@ impl<E: Engine + MultiMillerLoop> VerifyingKey<E> {
         Ok(())
     }
 
+    /// Writes the verifying key like [`VerifyingKey::write`], but with compressed points.
+    pub fn write_compressed<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.alpha_g1.to_bytes().as_ref())?;
+        writer.write_all(self.beta_g1.to_bytes().as_ref())?;
+        writer.write_all(self.beta_g2.to_bytes().as_ref())?;
+        writer.write_all(self.gamma_g2.to_bytes().as_ref())?;
+        writer.write_all(self.delta_g1.to_bytes().as_ref())?;
+        writer.write_all(self.delta_g2.to_bytes().as_ref())?;
+        writer.write_u32::<BigEndian>(self.ic.len() as u32)?;
+        for ic in &self.ic {
+            writer.write_all(ic.to_bytes().as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a verifying key written by [`VerifyingKey::write_compressed`].
+    pub fn read_compressed<R: Read>(mut reader: R) -> io::Result<Self> {
+        let alpha_g1 = read_compressed_point(&mut reader)?;
+        let beta_g1 = read_compressed_point(&mut reader)?;
+        let beta_g2 = read_compressed_point(&mut reader)?;
+        let gamma_g2 = read_compressed_point(&mut reader)?;
+        let delta_g1 = read_compressed_point(&mut reader)?;
+        let delta_g2 = read_compressed_point(&mut reader)?;
+
+        let ic_len = reader.read_u32::<BigEndian>()? as usize;
+
+        let mut ic = vec![];
+
+        for _ in 0..ic_len {
+            let g1: E::G1Affine = read_compressed_point(&mut reader)?;
+            if g1.is_identity().into() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "point at infinity",
+                ));
+            }
+            ic.push(g1);
+        }
+
+        Ok(VerifyingKey {
+            alpha_g1,
+            beta_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g1,
+            delta_g2,
+            ic,
+        })
+    }
+
     pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
         let mut g1_repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
         let mut g2_repr = <E::G2Affine as UncompressedEncoding>::Uncompressed::default();
@@ -0,0 +1,327 @@
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use bitvec::prelude::*;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use ff::PrimeField;
+
+use crate::multiexp::DensityTracker;
+
+/// Version of the binary format written by [`Witness::write`].
+const WITNESS_VERSION: u32 = 1;
+
+/// Upper bound on the number of elements allocated for ahead of reading them.
+const READ_CHUNK_SIZE: usize = 1 << 16;
+
+/// A synthesized circuit: the assignment of all variables together with the evaluations of
+/// the constraints, as needed for creating a proof without synthesizing the circuit again.
+///
+/// Witnesses are created by [`synthesize_witness`](super::synthesize_witness) and consumed by
+/// [`create_proof_from_witness`](super::create_proof_from_witness). They can be persisted with
+/// [`Witness::write`], which allows synthesis and proving to happen on different machines.
+///
+/// # Binary format
+///
+/// All integers are big-endian `u32`, all field elements are encoded as their canonical
+/// [`PrimeField::Repr`].
+///
+/// - format version, currently `1`
+/// - number of constraints `n`, number of inputs and number of auxiliary variables
+/// - `n` evaluations each of the A, B and C polynomials
+/// - input assignment, followed by the auxiliary assignment
+/// - density of the A query over auxiliary variables, of the B query over inputs and of the
+///   B query over auxiliary variables, each packed into bytes with the first variable in the
+///   least significant bit
+///
+/// Note that a witness contains the full private assignment of the circuit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Witness<Scalar: PrimeField> {
+    // Density of queries
+    pub(crate) a_aux_density: DensityTracker,
+    pub(crate) b_input_density: DensityTracker,
+    pub(crate) b_aux_density: DensityTracker,
+
+    // Evaluations of A, B, C polynomials
+    pub(crate) a: Vec<Scalar>,
+    pub(crate) b: Vec<Scalar>,
+    pub(crate) c: Vec<Scalar>,
+
+    // Assignments of variables
+    pub(crate) input_assignment: Vec<Scalar>,
+    pub(crate) aux_assignment: Vec<Scalar>,
+}
+
+impl<Scalar: PrimeField> Witness<Scalar> {
+    /// The number of constraints, including the ones enforcing full density of the inputs.
+    pub fn num_constraints(&self) -> usize {
+        self.a.len()
+    }
+
+    /// The assignment of the inputs, starting with the constant one.
+    pub fn input_assignment(&self) -> &[Scalar] {
+        &self.input_assignment
+    }
+
+    /// The assignment of the auxiliary variables.
+    pub fn aux_assignment(&self) -> &[Scalar] {
+        &self.aux_assignment
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(WITNESS_VERSION)?;
+        writer.write_u32::<BigEndian>(self.a.len() as u32)?;
+        writer.write_u32::<BigEndian>(self.input_assignment.len() as u32)?;
+        writer.write_u32::<BigEndian>(self.aux_assignment.len() as u32)?;
+
+        for s in self
+            .a
+            .iter()
+            .chain(self.b.iter())
+            .chain(self.c.iter())
+            .chain(self.input_assignment.iter())
+            .chain(self.aux_assignment.iter())
+        {
+            writer.write_all(s.to_repr().as_ref())?;
+        }
+
+        write_density(&mut writer, &self.a_aux_density)?;
+        write_density(&mut writer, &self.b_input_density)?;
+        write_density(&mut writer, &self.b_aux_density)?;
+
+        Ok(())
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let version = reader.read_u32::<BigEndian>()?;
+        if version != WITNESS_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported witness version {}", version),
+            ));
+        }
+
+        let num_constraints = reader.read_u32::<BigEndian>()? as usize;
+        let num_inputs = reader.read_u32::<BigEndian>()? as usize;
+        let num_aux = reader.read_u32::<BigEndian>()? as usize;
+
+        let a = read_scalars(&mut reader, num_constraints)?;
+        let b = read_scalars(&mut reader, num_constraints)?;
+        let c = read_scalars(&mut reader, num_constraints)?;
+        let input_assignment = read_scalars(&mut reader, num_inputs)?;
+        let aux_assignment = read_scalars(&mut reader, num_aux)?;
+
+        let a_aux_density = read_density(&mut reader, num_aux)?;
+        let b_input_density = read_density(&mut reader, num_inputs)?;
+        let b_aux_density = read_density(&mut reader, num_aux)?;
+
+        Ok(Witness {
+            a_aux_density,
+            b_input_density,
+            b_aux_density,
+            a,
+            b,
+            c,
+            input_assignment,
+            aux_assignment,
+        })
+    }
+}
+
+/// A witness whose H polynomial has been computed, ready for the multiexp stage of the
+/// prover. Created by [`compute_h_batch_priority`](super::compute_h_batch_priority) and
+/// consumed by [`create_proof_from_prepared_batch_priority`](
+/// super::create_proof_from_prepared_batch_priority).
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreparedWitness<Scalar: PrimeField> {
+    // Coefficients of the H polynomial
+    pub(crate) h: Arc<Vec<Scalar::Repr>>,
+
+    // Density of queries
+    pub(crate) a_aux_density: DensityTracker,
+    pub(crate) b_input_density: DensityTracker,
+    pub(crate) b_aux_density: DensityTracker,
+
+    // Assignments of variables
+    pub(crate) input_assignment: Arc<Vec<Scalar::Repr>>,
+    pub(crate) aux_assignment: Arc<Vec<Scalar::Repr>>,
+}
+
+impl<Scalar: PrimeField> PreparedWitness<Scalar> {
+    /// The coefficients of the H polynomial, excluding the leading one which is always zero.
+    pub fn h_coefficients(&self) -> &[Scalar::Repr] {
+        &self.h
+    }
+}
+
+fn read_scalars<Scalar: PrimeField, R: Read>(
+    reader: &mut R,
+    len: usize,
+) -> io::Result<Vec<Scalar>> {
+    // The length isn't trusted for allocations before the scalars were actually read.
+    let mut scalars = Vec::with_capacity(len.min(READ_CHUNK_SIZE));
+    for _ in 0..len {
+        let mut repr = Scalar::Repr::default();
+        reader.read_exact(repr.as_mut())?;
+
+        let scalar = Option::from(Scalar::from_repr(repr))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid field element"))?;
+        scalars.push(scalar);
+    }
+
+    Ok(scalars)
+}
+
+fn write_density<W: Write>(writer: &mut W, density: &DensityTracker) -> io::Result<()> {
+    let bits = density.bv.iter().by_val().collect::<BitVec<Lsb0, u8>>();
+    writer.write_all(bits.as_raw_slice())
+}
+
+fn read_density<R: Read>(reader: &mut R, len: usize) -> io::Result<DensityTracker> {
+    let num_bytes = len / 8 + usize::from(len & 7 != 0);
+    let mut bytes = Vec::with_capacity(num_bytes.min(READ_CHUNK_SIZE));
+    reader.take(num_bytes as u64).read_to_end(&mut bytes)?;
+    if bytes.len() != num_bytes {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "witness is truncated",
+        ));
+    }
+    let bits = BitVec::<Lsb0, u8>::from_vec(bytes);
+
+    // Padding bits must be unset, so that the encoding is canonical.
+    if bits[len..].any() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid density padding",
+        ));
+    }
+
+    let mut density = DensityTracker::new();
+    for (i, bit) in bits[..len].iter().by_val().enumerate() {
+        density.add_element();
+        if bit {
+            density.inc(i);
+        }
+    }
+
+    Ok(density)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_proof, create_proof_from_witness, create_random_proof_from_witness,
+        generate_random_parameters, prepare_verifying_key, synthesize_witness, verify_proof,
+    };
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    #[derive(Clone)]
+    struct SquaresCircuit {
+        xs: Vec<Option<Fr>>,
+    }
+
+    // Proves knowledge of the square roots of the public inputs.
+    impl Circuit<Fr> for SquaresCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            for (i, x_val) in self.xs.into_iter().enumerate() {
+                let x = cs.alloc(
+                    || format!("x {}", i),
+                    || x_val.ok_or(SynthesisError::AssignmentMissing),
+                )?;
+                let y = cs.alloc_input(
+                    || format!("y {}", i),
+                    || {
+                        x_val
+                            .map(|x| x.square())
+                            .ok_or(SynthesisError::AssignmentMissing)
+                    },
+                )?;
+                cs.enforce(
+                    || format!("x^2 = y {}", i),
+                    |lc| lc + x,
+                    |lc| lc + x,
+                    |lc| lc + y,
+                );
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_witness_roundtrip_and_prove() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        // Enough variables for the densities to span multiple bytes.
+        let n = 11;
+        let params =
+            generate_random_parameters::<Bls12, _, _>(SquaresCircuit { xs: vec![None; n] }, rng)
+                .unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let xs = (0..n).map(|_| Fr::random(&mut *rng)).collect::<Vec<_>>();
+        let ys = xs.iter().map(|x| x.square()).collect::<Vec<_>>();
+        let circuit = SquaresCircuit {
+            xs: xs.iter().copied().map(Some).collect(),
+        };
+
+        let witness = synthesize_witness(circuit.clone()).unwrap();
+        assert_eq!(witness.num_constraints(), 2 * n + 1);
+        assert_eq!(witness.input_assignment()[0], Fr::one());
+        assert_eq!(&witness.input_assignment()[1..], &ys[..]);
+        assert_eq!(witness.aux_assignment(), &xs[..]);
+
+        let mut bytes = vec![];
+        witness.write(&mut bytes).unwrap();
+        let de_witness = Witness::<Fr>::read(&bytes[..]).unwrap();
+        assert_eq!(de_witness, witness);
+
+        // Proving from the witness is equivalent to proving from the circuit.
+        let r = Fr::random(&mut *rng);
+        let s = Fr::random(&mut *rng);
+        let proof = create_proof_from_witness(de_witness.clone(), &params, r, s).unwrap();
+        assert!(proof == create_proof(circuit, &params, r, s).unwrap());
+        assert!(verify_proof(&pvk, &proof, &ys).unwrap());
+
+        let proof = create_random_proof_from_witness(de_witness, &params, rng).unwrap();
+        assert!(verify_proof(&pvk, &proof, &ys).unwrap());
+    }
+
+    #[test]
+    fn test_witness_read_invalid() {
+        let witness = synthesize_witness(SquaresCircuit {
+            xs: vec![Some(Fr::one()); 3],
+        })
+        .unwrap();
+        let mut bytes = vec![];
+        witness.write(&mut bytes).unwrap();
+
+        // Unknown version
+        let mut invalid = bytes.clone();
+        invalid[3] = 2;
+        assert!(Witness::<Fr>::read(&invalid[..]).is_err());
+
+        // Padding bits of the last density set
+        let mut invalid = bytes.clone();
+        *invalid.last_mut().unwrap() |= 0x80;
+        assert!(Witness::<Fr>::read(&invalid[..]).is_err());
+
+        // Truncated
+        assert!(Witness::<Fr>::read(&bytes[..bytes.len() - 1]).is_err());
+
+        // Lengths far beyond the actual data
+        let mut invalid = bytes.clone();
+        invalid[4..16].copy_from_slice(&[0xff; 12]);
+        let err = Witness::<Fr>::read(&invalid[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -20,6 +20,18 @@ impl Variable {
     pub fn get_unchecked(&self) -> Index {
         self.0
     }
+
+    /// Moves the variable of a constraint system which another one is extended with, by the
+    /// number of inputs and auxiliary variables of the extended one, see
+    /// [`ConstraintSystem::extend`](crate::ConstraintSystem::extend). The input for the
+    /// constant one is shared, so it stays in place and isn't counted in `input_offset`.
+    pub(crate) fn shift(self, input_offset: usize, aux_offset: usize) -> Variable {
+        match self.0 {
+            Index::Input(0) => self,
+            Index::Input(i) => Variable(Index::Input(i + input_offset)),
+            Index::Aux(i) => Variable(Index::Aux(i + aux_offset)),
+        }
+    }
 }
 
 /// Represents the index of either an input variable or
@@ -180,6 +192,20 @@ impl<Scalar: PrimeField> LinearCombination<Scalar> {
             )
     }
 
+    /// Moves all variables like [`Variable::shift`]. The order of the terms doesn't change.
+    pub(crate) fn shift(mut self, input_offset: usize, aux_offset: usize) -> Self {
+        for (i, _) in self.inputs.values.iter_mut().filter(|(i, _)| *i != 0) {
+            *i += input_offset;
+        }
+        for (i, _) in self.aux.values.iter_mut() {
+            *i += aux_offset;
+        }
+        self.inputs.last_inserted = None;
+        self.aux.last_inserted = None;
+
+        self
+    }
+
     #[inline]
     fn add_assign_unsimplified_input(&mut self, new_var: usize, coeff: Scalar) {
         self.inputs
@@ -0,0 +1,305 @@
+//! Detection of unconstrained and under-constrained variables in a recorded constraint
+//! system, which are a common cause of soundness bugs: a prover can choose another value
+//! for such a variable and still satisfy all constraints.
+//!
+//! The analysis is structural, it only looks at which variables appear in which constraints
+//! and never at their assignment. A constraint `A * B = C` is considered linear if `A` or `B`
+//! is a constant, i.e. only contains the input for the constant one.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+use ff::PrimeField;
+
+use crate::{Index, LinearCombination};
+
+/// The kinds of findings of [`analyze`], from most to least severe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FindingKind {
+    /// An auxiliary variable which appears in no constraint, so it can take any value.
+    UnconstrainedAux,
+    /// An input other than the constant one which appears in no constraint, so the proof
+    /// doesn't depend on it.
+    UnusedInput,
+    /// An auxiliary variable which is packed as a bit, i.e. appears with a power of two as
+    /// coefficient next to other such variables, but only appears in linear constraints.
+    /// Nothing forces it to be `0` or `1`, e.g. it lacks a booleanity constraint.
+    UnconstrainedBit,
+    /// An auxiliary variable which only appears in linear constraints. This is often fine,
+    /// e.g. for a variable defined as the sum of others, but such a variable can't have any
+    /// non-linear property, like being a bit.
+    OnlyLinear,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            FindingKind::UnconstrainedAux => "unconstrained auxiliary variable",
+            FindingKind::UnusedInput => "unused input",
+            FindingKind::UnconstrainedBit => "bit without booleanity constraint",
+            FindingKind::OnlyLinear => "only linearly constrained variable",
+        };
+        f.write_str(s)
+    }
+}
+
+/// A suspicious variable, with its path in the constraint system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub variable: Index,
+    pub path: String,
+}
+
+/// The findings of [`analyze`], inputs first, then auxiliary variables, each in the order
+/// they were allocated.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnalysisReport {
+    pub findings: Vec<Finding>,
+}
+
+impl AnalysisReport {
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    pub fn of_kind(&self, kind: FindingKind) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.kind == kind)
+    }
+
+    /// The number of findings per kind.
+    pub fn summary(&self) -> BTreeMap<FindingKind, usize> {
+        let mut summary = BTreeMap::new();
+        for finding in &self.findings {
+            *summary.entry(finding.kind).or_insert(0) += 1;
+        }
+
+        summary
+    }
+}
+
+impl fmt::Display for AnalysisReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no findings");
+        }
+
+        for (kind, count) in self.summary() {
+            writeln!(f, "{}: {}", kind, count)?;
+        }
+        for finding in &self.findings {
+            writeln!(f, "  {}: `{}`", finding.kind, finding.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Usage {
+    Unused,
+    Linear,
+    NonLinear,
+}
+
+// Whether the linear combination only consists of the input for the constant one.
+fn is_constant<Scalar: PrimeField>(lc: &LinearCombination<Scalar>) -> bool {
+    lc.iter()
+        .all(|(var, coeff)| var.get_unchecked() == Index::Input(0) || coeff.is_zero().into())
+}
+
+// Whether the linear combination packs at least two variables as bits, i.e. their
+// coefficients are distinct powers of two, up to sign.
+fn is_packing<Scalar: PrimeField>(
+    lc: &LinearCombination<Scalar>,
+    powers_of_two: &HashMap<Vec<u8>, u32>,
+) -> bool {
+    let mut seen = HashSet::new();
+    for (var, coeff) in lc.iter() {
+        if var.get_unchecked() == Index::Input(0) || coeff.is_zero().into() {
+            continue;
+        }
+
+        let power = powers_of_two
+            .get(coeff.to_repr().as_ref())
+            .or_else(|| powers_of_two.get((-*coeff).to_repr().as_ref()));
+        match power {
+            Some(power) if seen.insert(power) => {}
+            _ => return false,
+        }
+    }
+
+    seen.len() >= 2
+}
+
+/// Analyzes the constraints `A * B = C` of a constraint system with the given paths of
+/// inputs and auxiliary variables, where the first input is the constant one.
+///
+/// Usually called through [`MetricCS::analyze`](super::metric_cs::MetricCS::analyze) or
+/// [`TestConstraintSystem::analyze`](super::test_cs::TestConstraintSystem::analyze).
+pub fn analyze<'a, Scalar, I>(
+    input_paths: &[&str],
+    aux_paths: &[&str],
+    constraints: I,
+) -> AnalysisReport
+where
+    Scalar: PrimeField,
+    I: IntoIterator<
+        Item = (
+            &'a LinearCombination<Scalar>,
+            &'a LinearCombination<Scalar>,
+            &'a LinearCombination<Scalar>,
+        ),
+    >,
+{
+    let mut power = Scalar::one();
+    let mut powers_of_two = HashMap::new();
+    for i in 0..Scalar::NUM_BITS {
+        powers_of_two.insert(power.to_repr().as_ref().to_vec(), i);
+        power = power.double();
+    }
+
+    let mut input_usage = vec![Usage::Unused; input_paths.len()];
+    let mut aux_usage = vec![Usage::Unused; aux_paths.len()];
+    let mut packed = vec![false; aux_paths.len()];
+
+    for (a, b, c) in constraints {
+        let usage = if is_constant(a) || is_constant(b) {
+            Usage::Linear
+        } else {
+            Usage::NonLinear
+        };
+
+        for lc in [a, b, c].iter() {
+            let is_packing = is_packing(lc, &powers_of_two);
+            for (var, coeff) in lc.iter() {
+                if coeff.is_zero().into() {
+                    continue;
+                }
+                match var.get_unchecked() {
+                    Index::Input(i) => input_usage[i] = input_usage[i].max(usage),
+                    Index::Aux(i) => {
+                        aux_usage[i] = aux_usage[i].max(usage);
+                        packed[i] |= is_packing;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut findings = vec![];
+    // The constant one doesn't need to be constrained.
+    for (i, usage) in input_usage.into_iter().enumerate().skip(1) {
+        if usage == Usage::Unused {
+            findings.push(Finding {
+                kind: FindingKind::UnusedInput,
+                variable: Index::Input(i),
+                path: input_paths[i].to_string(),
+            });
+        }
+    }
+    for (i, usage) in aux_usage.into_iter().enumerate() {
+        let kind = match usage {
+            Usage::Unused => FindingKind::UnconstrainedAux,
+            Usage::Linear if packed[i] => FindingKind::UnconstrainedBit,
+            Usage::Linear => FindingKind::OnlyLinear,
+            Usage::NonLinear => continue,
+        };
+        findings.push(Finding {
+            kind,
+            variable: Index::Aux(i),
+            path: aux_paths[i].to_string(),
+        });
+    }
+
+    AnalysisReport { findings }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gadgets::boolean::{AllocatedBit, Boolean};
+    use crate::gadgets::multipack::pack_into_inputs;
+    use crate::gadgets::num::AllocatedNum;
+    use crate::util_cs::metric_cs::MetricCS;
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use crate::{ConstraintSystem, SynthesisError};
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    fn synthesize<CS: ConstraintSystem<Fr>>(cs: &mut CS) -> Result<(), SynthesisError> {
+        // Properly constrained bits, packed into an input.
+        let bits = (0..4)
+            .map(|i| {
+                AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(i % 2 == 0))
+                    .map(Boolean::from)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        pack_into_inputs(cs.namespace(|| "pack bits"), &bits)?;
+
+        // Bits without booleanity constraints, packed into an input.
+        let mut cs = cs.namespace(|| "broken");
+        let raw = (0..3)
+            .map(|i| cs.alloc(|| format!("raw {}", i), || Ok(Fr::one())))
+            .collect::<Result<Vec<_>, _>>()?;
+        let packed = cs.alloc_input(|| "packed", || Ok(Fr::from(7u64)))?;
+        cs.enforce(
+            || "pack raw",
+            |lc| lc + raw[0] + (Fr::from(2u64), raw[1]) + (Fr::from(4u64), raw[2]),
+            |lc| lc + CS::one(),
+            |lc| lc + packed,
+        );
+
+        // A variable only defined linearly, and one never constrained.
+        let x = AllocatedNum::alloc(cs.namespace(|| "x"), || Ok(Fr::from(3u64)))?;
+        let y = cs.alloc(|| "y", || Ok(Fr::from(6u64)))?;
+        cs.enforce(
+            || "y = 2x",
+            |lc| lc + (Fr::from(2u64), x.get_variable()),
+            |lc| lc + CS::one(),
+            |lc| lc + y,
+        );
+        x.square(cs.namespace(|| "x2"))?;
+        cs.alloc(|| "free", || Ok(Fr::one()))?;
+        cs.alloc_input(|| "unused", || Ok(Fr::one()))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut cs = MetricCS::<Fr>::new();
+        synthesize(&mut cs).unwrap();
+        let report = cs.analyze();
+
+        let findings = report
+            .findings
+            .iter()
+            .map(|finding| (finding.kind, finding.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                (FindingKind::UnusedInput, "broken/unused"),
+                (FindingKind::UnconstrainedBit, "broken/raw 0"),
+                (FindingKind::UnconstrainedBit, "broken/raw 1"),
+                (FindingKind::UnconstrainedBit, "broken/raw 2"),
+                (FindingKind::OnlyLinear, "broken/y"),
+                (FindingKind::UnconstrainedAux, "broken/free"),
+            ]
+        );
+        assert_eq!(report.summary()[&FindingKind::UnconstrainedBit], 3);
+        assert_eq!(report.of_kind(FindingKind::UnconstrainedAux).count(), 1);
+        assert!(report
+            .to_string()
+            .contains("  bit without booleanity constraint: `broken/raw 0`\n"));
+
+        let mut test_cs = TestConstraintSystem::<Fr>::new();
+        synthesize(&mut test_cs).unwrap();
+        assert!(test_cs.is_satisfied());
+        assert_eq!(test_cs.analyze(), report);
+    }
+}
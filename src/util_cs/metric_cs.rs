@@ -3,6 +3,9 @@ use std::collections::{BTreeMap, HashMap};
 
 use ff::PrimeField;
 
+use crate::util_cs::analysis::{analyze, AnalysisReport};
+use crate::util_cs::snapshot::CircuitSnapshot;
+use crate::util_cs::test_cs::hash_constraints;
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 
 #[derive(Clone, Copy)]
@@ -178,6 +181,46 @@ impl<Scalar: PrimeField> MetricCS<Scalar> {
         s
     }
 
+    /// The hash of the constraints, as computed by
+    /// [`TestConstraintSystem::hash`](super::test_cs::TestConstraintSystem::hash).
+    pub fn hash(&self) -> String {
+        hash_constraints(self.inputs.len(), self.aux.len(), &self.constraints)
+    }
+
+    /// A snapshot of the size of the circuit, with namespaces nested deeper than `depth`
+    /// counted in their ancestor at that depth, see
+    /// [`assert_snapshot`](crate::util_cs::snapshot::assert_snapshot).
+    pub fn snapshot(&self, depth: usize) -> CircuitSnapshot {
+        CircuitSnapshot::new(
+            self.hash(),
+            self.inputs.len(),
+            self.aux.len(),
+            self.constraints.iter().map(|c| c.3.as_str()),
+            depth,
+        )
+    }
+
+    /// Finds unconstrained and under-constrained variables, see
+    /// [`analyze`](crate::util_cs::analysis::analyze).
+    pub fn analyze(&self) -> AnalysisReport {
+        let input_paths = self
+            .inputs
+            .iter()
+            .map(|path| path.as_str())
+            .collect::<Vec<_>>();
+        let aux_paths = self
+            .aux
+            .iter()
+            .map(|path| path.as_str())
+            .collect::<Vec<_>>();
+
+        analyze(
+            &input_paths,
+            &aux_paths,
+            self.constraints.iter().map(|(a, b, c, _)| (a, b, c)),
+        )
+    }
+
     fn set_named_obj(&mut self, path: String, to: NamedObject) {
         if self.named_objects.contains_key(&path) {
             panic!("tried to create object at existing path: {}", path);
@@ -204,6 +247,10 @@ impl<Scalar: PrimeField> Default for MetricCS<Scalar> {
 impl<Scalar: PrimeField> ConstraintSystem<Scalar> for MetricCS<Scalar> {
     type Root = Self;
 
+    fn new() -> Self {
+        Self::default()
+    }
+
     fn alloc<F, A, AR>(&mut self, annotation: A, _f: F) -> Result<Variable, SynthesisError>
     where
         F: FnOnce() -> Result<Scalar, SynthesisError>,
@@ -265,6 +312,66 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for MetricCS<Scalar> {
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
+
+    // `new` already allocates the input for the constant one.
+    fn new_for_extend(&self) -> Self {
+        Self::default()
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        let input_offset = self.inputs.len() - 1;
+        let aux_offset = self.aux.len();
+        let constraint_offset = self.constraints.len();
+        let prefix = |path: String| {
+            if self.current_namespace.is_empty() {
+                path
+            } else {
+                format!("{}/{}", self.current_namespace.join("/"), path)
+            }
+        };
+
+        let mut named_objects = vec![];
+        for (path, object) in other.named_objects {
+            let object = match object {
+                // The constant one is shared.
+                NamedObject::Var(var) if var == Self::one() => continue,
+                NamedObject::Var(var) => NamedObject::Var(var.shift(input_offset, aux_offset)),
+                NamedObject::Constraint(i) => NamedObject::Constraint(i + constraint_offset),
+                NamedObject::Namespace => NamedObject::Namespace,
+            };
+            named_objects.push((prefix(path), object));
+        }
+        let inputs = other
+            .inputs
+            .into_iter()
+            .skip(1)
+            .map(prefix)
+            .collect::<Vec<_>>();
+        let aux = other.aux.into_iter().map(prefix).collect::<Vec<_>>();
+        let constraints = other
+            .constraints
+            .into_iter()
+            .map(|(a, b, c, path)| {
+                (
+                    a.shift(input_offset, aux_offset),
+                    b.shift(input_offset, aux_offset),
+                    c.shift(input_offset, aux_offset),
+                    prefix(path),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (path, object) in named_objects {
+            self.set_named_obj(path, object);
+        }
+        self.inputs.extend(inputs);
+        self.aux.extend(aux);
+        self.constraints.extend(constraints);
+    }
 }
 
 fn compute_path(ns: &[String], this: &str) -> String {
@@ -1,3 +1,10 @@
+pub mod analysis;
 pub mod bench_cs;
 pub mod metric_cs;
+pub mod profile_cs;
+pub mod r1cs;
+pub mod sat_cs;
+pub mod shape_cs;
+pub mod snapshot;
 pub mod test_cs;
+pub mod witness_cs;
@@ -0,0 +1,445 @@
+//! A constraint system which profiles the size of a circuit per namespace.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use ff::PrimeField;
+use serde::{Deserialize, Serialize};
+
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// What to count in the folded stacks of a profile, see [`ProfileNode::folded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileMetric {
+    Constraints,
+    Aux,
+    Inputs,
+}
+
+/// The size of a namespace of a circuit. The counts only include what was allocated or
+/// enforced directly in the namespace, the totals include all nested namespaces.
+///
+/// Nodes can be serialized with any serde format, e.g. as JSON to track the size of a
+/// circuit over time.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileNode {
+    pub name: String,
+    pub constraints: usize,
+    pub aux: usize,
+    pub inputs: usize,
+    pub total_constraints: usize,
+    pub total_aux: usize,
+    pub total_inputs: usize,
+    /// The nested namespaces, in the order they were first entered.
+    pub children: Vec<ProfileNode>,
+}
+
+impl ProfileNode {
+    fn count(&self, metric: ProfileMetric) -> usize {
+        match metric {
+            ProfileMetric::Constraints => self.constraints,
+            ProfileMetric::Aux => self.aux,
+            ProfileMetric::Inputs => self.inputs,
+        }
+    }
+
+    /// The nested namespace at `path`, relative to this node, with namespaces separated by
+    /// `/`.
+    pub fn get(&self, path: &str) -> Option<&ProfileNode> {
+        path.split('/').try_fold(self, |node, name| {
+            node.children.iter().find(|child| child.name == name)
+        })
+    }
+
+    /// The profile in the folded stack format of flamegraph tools: one line per namespace
+    /// with a non-zero count, its path separated by `;`, a space and the count of `metric`.
+    ///
+    /// Semicolons in names are replaced by colons, as they would split frames.
+    pub fn folded(&self, metric: ProfileMetric) -> String {
+        let mut out = String::new();
+        let mut stack = vec![];
+        self.write_folded(metric, &mut stack, &mut out);
+
+        out
+    }
+
+    fn write_folded(&self, metric: ProfileMetric, stack: &mut Vec<String>, out: &mut String) {
+        stack.push(self.name.replace(';', ":"));
+
+        let count = self.count(metric);
+        if count > 0 {
+            writeln!(out, "{} {}", stack.join(";"), count).expect("writing to a string failed");
+        }
+        for child in &self.children {
+            child.write_folded(metric, stack, out);
+        }
+
+        stack.pop();
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    constraints: usize,
+    aux: usize,
+    inputs: usize,
+    children: Vec<usize>,
+    children_by_name: HashMap<String, usize>,
+}
+
+impl Node {
+    fn new(name: String) -> Self {
+        Node {
+            name,
+            constraints: 0,
+            aux: 0,
+            inputs: 0,
+            children: vec![],
+            children_by_name: HashMap::new(),
+        }
+    }
+}
+
+/// A constraint system which counts constraints, auxiliary variables and inputs per
+/// namespace, without keeping the constraints or calling the closures computing
+/// assignments. Unlike [`MetricCS`](super::metric_cs::MetricCS), it doesn't keep a node
+/// per constraint or variable, but one per distinct namespace path.
+///
+/// Entering a namespace with the same path again adds to the counts of that namespace, so
+/// gadgets used in a loop under the same name are aggregated. Gadgets usually number their
+/// namespaces though, e.g. `bit {}` or `round {}`, so the number of distinct paths still
+/// grows with the size of the circuit. [`ProfileCS::with_merged_numbers`] merges those.
+///
+/// Like [`MetricCS`](super::metric_cs::MetricCS), the input for the constant one is
+/// allocated on creation and counted in the root namespace.
+#[derive(Debug)]
+pub struct ProfileCS<Scalar: PrimeField> {
+    // The root is the first node.
+    nodes: Vec<Node>,
+    current_namespace: Vec<usize>,
+    num_inputs: usize,
+    num_aux: usize,
+    merge_numbers: bool,
+    _scalar: std::marker::PhantomData<Scalar>,
+}
+
+impl<Scalar: PrimeField> Default for ProfileCS<Scalar> {
+    fn default() -> Self {
+        let mut root = Node::new("circuit".into());
+        root.inputs = 1;
+
+        ProfileCS {
+            nodes: vec![root],
+            current_namespace: vec![0],
+            num_inputs: 1,
+            num_aux: 0,
+            merge_numbers: false,
+            _scalar: Default::default(),
+        }
+    }
+}
+
+impl<Scalar: PrimeField> ProfileCS<Scalar> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// A constraint system which merges namespaces whose names only differ in numbers
+    /// separated by spaces, e.g. `bit 3` and `bit 4` are both counted in `bit *`. Its size
+    /// then only depends on the gadgets a circuit uses, not on how often it uses them.
+    pub fn with_merged_numbers() -> Self {
+        ProfileCS {
+            merge_numbers: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.nodes.iter().map(|node| node.constraints).sum()
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    fn current(&mut self) -> &mut Node {
+        let index = *self
+            .current_namespace
+            .last()
+            .expect("root namespace was popped");
+        &mut self.nodes[index]
+    }
+
+    /// The tree of namespaces with their counts, rooted at a node named `circuit`.
+    pub fn profile(&self) -> ProfileNode {
+        self.profile_node(0)
+    }
+
+    fn profile_node(&self, index: usize) -> ProfileNode {
+        let node = &self.nodes[index];
+        let children = node
+            .children
+            .iter()
+            .map(|&child| self.profile_node(child))
+            .collect::<Vec<_>>();
+
+        ProfileNode {
+            name: node.name.clone(),
+            constraints: node.constraints,
+            aux: node.aux,
+            inputs: node.inputs,
+            total_constraints: node.constraints
+                + children.iter().map(|c| c.total_constraints).sum::<usize>(),
+            total_aux: node.aux + children.iter().map(|c| c.total_aux).sum::<usize>(),
+            total_inputs: node.inputs + children.iter().map(|c| c.total_inputs).sum::<usize>(),
+            children,
+        }
+    }
+}
+
+/// Synthesizes `circuit` without a witness and returns its profile.
+pub fn profile_circuit<Scalar, C>(circuit: C) -> Result<ProfileNode, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar>,
+{
+    let mut cs = ProfileCS::new();
+    circuit.synthesize(&mut cs)?;
+
+    Ok(cs.profile())
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ProfileCS<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.current().aux += 1;
+        self.num_aux += 1;
+
+        Ok(Variable::new_unchecked(Index::Aux(self.num_aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.current().inputs += 1;
+        self.num_inputs += 1;
+
+        Ok(Variable::new_unchecked(Index::Input(self.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, _a: LA, _b: LB, _c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        self.current().constraints += 1;
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        let mut name = name_fn().into();
+        if self.merge_numbers {
+            name = merge_numbers(&name);
+        }
+        let next = self.nodes.len();
+        let current = self.current();
+        let index = match current.children_by_name.get(&name) {
+            Some(&index) => index,
+            None => {
+                current.children.push(next);
+                current.children_by_name.insert(name.clone(), next);
+                self.nodes.push(Node::new(name));
+                next
+            }
+        };
+        self.current_namespace.push(index);
+    }
+
+    fn pop_namespace(&mut self) {
+        assert!(
+            self.current_namespace.len() > 1,
+            "can't pop the root namespace"
+        );
+        self.current_namespace.pop();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+/// Replaces the numbers separated by spaces in `name` by `*`.
+fn merge_numbers(name: &str) -> String {
+    name.split(' ')
+        .map(|word| {
+            if !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit()) {
+                "*"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::gadgets::boolean::{AllocatedBit, Boolean};
+    use crate::gadgets::num::AllocatedNum;
+    use crate::gadgets::sha256::sha256;
+    use blstrs::Scalar as Fr;
+
+    struct HashCircuit;
+
+    impl Circuit<Fr> for HashCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let bits = (0..512)
+                .map(|i| {
+                    AllocatedBit::alloc(cs.namespace(|| format!("bit {}", i)), Some(i % 3 == 0))
+                        .map(Boolean::from)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for i in 0..2 {
+                sha256(cs.namespace(|| "sha256"), &bits)?;
+                let num = AllocatedNum::alloc(cs.namespace(|| format!("num {}", i)), || {
+                    Ok(Fr::from(3u64))
+                })?;
+                num.inputize(cs.namespace(|| format!("input {}", i)))?;
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_profile_cs() {
+        let mut cs = ProfileCS::<Fr>::new();
+        // The namespace of the hash is reused, which `TestConstraintSystem` doesn't allow.
+        HashCircuit.synthesize(&mut cs).unwrap();
+
+        let profile = cs.profile();
+        assert_eq!(profile.name, "circuit");
+        assert_eq!(profile.total_constraints, cs.num_constraints());
+        assert_eq!(profile.total_inputs, cs.num_inputs());
+        assert_eq!(profile.total_aux, cs.num_aux());
+        assert_eq!(profile.inputs, 1);
+        assert_eq!(cs.num_inputs(), 3);
+
+        let sha = profile.get("sha256").unwrap();
+        assert_eq!(sha.constraints, 0);
+        assert_eq!(sha.total_constraints, 2 * 44874);
+        assert_eq!(sha.total_inputs, 0);
+        assert!(!sha.children.is_empty());
+        assert_eq!(profile.get("bit 7").unwrap().total_constraints, 1);
+        assert_eq!(profile.get("input 1").unwrap().inputs, 1);
+        assert!(profile.get("sha256/nonexistent").is_none());
+        assert_eq!(
+            profile.children.len(),
+            512 + 1 + 2 + 2,
+            "repeated namespaces are merged"
+        );
+
+        let folded = profile.folded(ProfileMetric::Constraints);
+        assert!(folded.contains("\ncircuit;bit 7 1\n"));
+        let total = folded
+            .lines()
+            .map(|line| line.rsplit(' ').next().unwrap().parse::<usize>().unwrap())
+            .sum::<usize>();
+        assert_eq!(total, cs.num_constraints());
+        assert!(profile
+            .folded(ProfileMetric::Inputs)
+            .starts_with("circuit 1\n"));
+
+        let json = serde_json::to_string(&profile).unwrap();
+        let decoded: ProfileNode = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, profile);
+    }
+
+    struct Sha256Circuit {
+        bytes: usize,
+    }
+
+    impl Circuit<Fr> for Sha256Circuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let bits = (0..8 * self.bytes)
+                .map(|i| {
+                    AllocatedBit::alloc(cs.namespace(|| format!("input bit {}", i)), Some(false))
+                        .map(Boolean::from)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            sha256(cs.namespace(|| "sha256"), &bits)?;
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_profile_cs_merged_numbers() {
+        assert_eq!(merge_numbers("input bit 3 12"), "input bit * *");
+        assert_eq!(merge_numbers("sha256"), "sha256");
+        assert_eq!(merge_numbers("h[0] ^ v[8]"), "h[0] ^ v[8]");
+
+        let profile = |bytes, merged| {
+            let mut cs = if merged {
+                ProfileCS::<Fr>::with_merged_numbers()
+            } else {
+                ProfileCS::<Fr>::new()
+            };
+            Sha256Circuit { bytes }.synthesize(&mut cs).unwrap();
+            (cs.nodes.len(), cs.profile())
+        };
+
+        // Without merging, every bit and block of the input adds namespaces.
+        let (small_nodes, _) = profile(32, false);
+        let (large_nodes, _) = profile(128, false);
+        assert!(large_nodes > small_nodes + 8 * 96);
+
+        // Merged, the size of the tree only depends on the gadgets.
+        let (small_nodes, small) = profile(32, true);
+        let (large_nodes, large) = profile(128, true);
+        assert_eq!(small_nodes, large_nodes);
+        assert!(large_nodes < 100, "{} nodes", large_nodes);
+        assert_eq!(large.children.len(), 2);
+        assert_eq!(large.get("input bit *").unwrap().aux, 8 * 128);
+        let (_, unmerged) = profile(128, false);
+        assert_eq!(large.total_constraints, unmerged.total_constraints);
+        assert_eq!(
+            large.get("sha256/block *").unwrap().total_constraints,
+            (0..3)
+                .map(|i| {
+                    let block = unmerged.get(&format!("sha256/block {}", i)).unwrap();
+                    block.total_constraints
+                })
+                .sum::<usize>()
+        );
+        assert_eq!(
+            small.get("sha256").unwrap().children.len(),
+            large.get("sha256").unwrap().children.len()
+        );
+        assert!(large
+            .folded(ProfileMetric::Constraints)
+            .contains("circuit;sha256;block *;"));
+    }
+}
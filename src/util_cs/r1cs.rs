@@ -0,0 +1,764 @@
+//! Export of circuits to the binary `.r1cs` and `.wtns` formats of iden3/circom, and import
+//! of such files as a [`Circuit`].
+//!
+//! Wire `0` is the constant one, followed by the public wires and then the private ones.
+//! Exported circuits map the inputs of the constraint system to wires `0..num_inputs`, in
+//! order, and the auxiliary variables to the wires after them. All inputs other than the
+//! constant one are declared as public inputs; bellperson has no notion of public outputs or
+//! private inputs, they are simply public inputs and auxiliary variables.
+//!
+//! Field elements are encoded in little-endian, in their canonical (non-Montgomery) form,
+//! padded to a multiple of 8 bytes.
+
+use std::cmp::Ordering;
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::{PrimeField, PrimeFieldBits};
+
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+const R1CS_MAGIC: [u8; 4] = *b"r1cs";
+const R1CS_VERSION: u32 = 1;
+const R1CS_SECTION_HEADER: u32 = 1;
+const R1CS_SECTION_CONSTRAINTS: u32 = 2;
+const R1CS_SECTION_WIRE_TO_LABEL: u32 = 3;
+// Sections of circuits with PLONK custom gates, which can't be expressed as R1CS.
+const R1CS_SECTION_CUSTOM_GATES_LIST: u32 = 4;
+const R1CS_SECTION_CUSTOM_GATES_APPLICATION: u32 = 5;
+
+const WTNS_MAGIC: [u8; 4] = *b"wtns";
+const WTNS_VERSION: u32 = 2;
+const WTNS_SECTION_HEADER: u32 = 1;
+const WTNS_SECTION_DATA: u32 = 2;
+
+/// The terms of a linear combination as pairs of wire and coefficient.
+pub type R1csTerms<Scalar> = Vec<(usize, Scalar)>;
+
+/// A constraint `A * B = C`.
+pub type R1csConstraint<Scalar> = (R1csTerms<Scalar>, R1csTerms<Scalar>, R1csTerms<Scalar>);
+
+/// The contents of an `.r1cs` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct R1cs<Scalar: PrimeField> {
+    /// The number of wires, including the constant one.
+    pub num_wires: usize,
+    pub num_pub_out: usize,
+    pub num_pub_in: usize,
+    pub num_prv_in: usize,
+    pub constraints: Vec<R1csConstraint<Scalar>>,
+}
+
+impl<Scalar: PrimeField> R1cs<Scalar> {
+    /// The number of public wires, excluding the constant one.
+    pub fn num_public(&self) -> usize {
+        self.num_pub_out + self.num_pub_in
+    }
+
+    /// The circuit described by the constraints, with the assignment of all wires from
+    /// `witness` if it is given, e.g. as read by [`read_wtns`].
+    ///
+    /// The public wires become the inputs of the circuit, in order, the private ones its
+    /// auxiliary variables.
+    pub fn circuit<'a>(&'a self, witness: Option<&'a [Scalar]>) -> R1csCircuit<'a, Scalar> {
+        R1csCircuit {
+            r1cs: self,
+            witness,
+        }
+    }
+}
+
+impl<Scalar: PrimeFieldBits> R1cs<Scalar> {
+    /// Writes the header, constraint and wire-to-label sections. Labels are the wire
+    /// indices.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let n8 = field_size::<Scalar>();
+        let num_wires = to_u32(self.num_wires, "number of wires")?;
+
+        writer.write_all(&R1CS_MAGIC)?;
+        writer.write_u32::<LittleEndian>(R1CS_VERSION)?;
+        writer.write_u32::<LittleEndian>(3)?;
+
+        writer.write_u32::<LittleEndian>(R1CS_SECTION_HEADER)?;
+        writer.write_u64::<LittleEndian>((4 + n8 + 4 * 4 + 8 + 4) as u64)?;
+        writer.write_u32::<LittleEndian>(n8 as u32)?;
+        writer.write_all(&modulus::<Scalar>())?;
+        writer.write_u32::<LittleEndian>(num_wires)?;
+        writer.write_u32::<LittleEndian>(to_u32(self.num_pub_out, "number of outputs")?)?;
+        writer.write_u32::<LittleEndian>(to_u32(self.num_pub_in, "number of inputs")?)?;
+        writer.write_u32::<LittleEndian>(to_u32(self.num_prv_in, "number of inputs")?)?;
+        writer.write_u64::<LittleEndian>(self.num_wires as u64)?;
+        writer
+            .write_u32::<LittleEndian>(to_u32(self.constraints.len(), "number of constraints")?)?;
+
+        let constraints_size: usize = self
+            .constraints
+            .iter()
+            .map(|(a, b, c)| 3 * 4 + (a.len() + b.len() + c.len()) * (4 + n8))
+            .sum();
+        writer.write_u32::<LittleEndian>(R1CS_SECTION_CONSTRAINTS)?;
+        writer.write_u64::<LittleEndian>(constraints_size as u64)?;
+        let mut buf = vec![0u8; n8];
+        for (a, b, c) in &self.constraints {
+            for terms in &[a, b, c] {
+                writer.write_u32::<LittleEndian>(terms.len() as u32)?;
+                for (wire, coeff) in terms.iter() {
+                    writer.write_u32::<LittleEndian>(to_u32(*wire, "wire")?)?;
+                    to_le_bytes(coeff, &mut buf);
+                    writer.write_all(&buf)?;
+                }
+            }
+        }
+
+        writer.write_u32::<LittleEndian>(R1CS_SECTION_WIRE_TO_LABEL)?;
+        writer.write_u64::<LittleEndian>(self.num_wires as u64 * 8)?;
+        for wire in 0..self.num_wires {
+            writer.write_u64::<LittleEndian>(wire as u64)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads an `.r1cs` file over the scalar field `Scalar`. Labels are ignored, and files
+    /// with custom gates are rejected.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let sections = read_sections(&mut reader, R1CS_MAGIC, R1CS_VERSION)?;
+
+        let mut header = None;
+        let mut constraints = None;
+        for (kind, section) in &sections {
+            match *kind {
+                R1CS_SECTION_HEADER if header.is_none() => header = Some(&section[..]),
+                R1CS_SECTION_CONSTRAINTS if constraints.is_none() => {
+                    constraints = Some(&section[..])
+                }
+                R1CS_SECTION_HEADER | R1CS_SECTION_CONSTRAINTS => {
+                    return Err(invalid_data("duplicate r1cs section"))
+                }
+                R1CS_SECTION_CUSTOM_GATES_LIST | R1CS_SECTION_CUSTOM_GATES_APPLICATION => {
+                    return Err(invalid_data("r1cs custom gates are not supported"))
+                }
+                _ => {}
+            }
+        }
+        let mut header = header.ok_or_else(|| invalid_data("missing r1cs header"))?;
+        let mut constraints =
+            constraints.ok_or_else(|| invalid_data("missing r1cs constraints"))?;
+
+        read_field::<Scalar>(&mut header)?;
+        let num_wires = header.read_u32::<LittleEndian>()? as usize;
+        let num_pub_out = header.read_u32::<LittleEndian>()? as usize;
+        let num_pub_in = header.read_u32::<LittleEndian>()? as usize;
+        let num_prv_in = header.read_u32::<LittleEndian>()? as usize;
+        let _num_labels = header.read_u64::<LittleEndian>()?;
+        let num_constraints = header.read_u32::<LittleEndian>()? as usize;
+        if num_wires < 1 + num_pub_out + num_pub_in + num_prv_in {
+            return Err(invalid_data("invalid number of r1cs wires"));
+        }
+
+        let modulus = modulus::<Scalar>();
+        let read_terms = |reader: &mut &[u8]| -> io::Result<R1csTerms<Scalar>> {
+            let len = reader.read_u32::<LittleEndian>()? as usize;
+            let mut terms = Vec::with_capacity(len.min(reader.len() / (4 + modulus.len())));
+            for _ in 0..len {
+                let wire = reader.read_u32::<LittleEndian>()? as usize;
+                if wire >= num_wires {
+                    return Err(invalid_data("r1cs wire out of range"));
+                }
+                let coeff = read_scalar(reader, &modulus)?;
+                terms.push((wire, coeff));
+            }
+            Ok(terms)
+        };
+
+        let mut r1cs_constraints = Vec::with_capacity(num_constraints.min(constraints.len() / 12));
+        for _ in 0..num_constraints {
+            let a = read_terms(&mut constraints)?;
+            let b = read_terms(&mut constraints)?;
+            let c = read_terms(&mut constraints)?;
+            r1cs_constraints.push((a, b, c));
+        }
+        if !constraints.is_empty() {
+            return Err(invalid_data("trailing data in r1cs constraints"));
+        }
+
+        Ok(R1cs {
+            num_wires,
+            num_pub_out,
+            num_pub_in,
+            num_prv_in,
+            constraints: r1cs_constraints,
+        })
+    }
+}
+
+/// Writes the assignment of all wires as a `.wtns` file.
+pub fn write_wtns<Scalar, W>(witness: &[Scalar], mut writer: W) -> io::Result<()>
+where
+    Scalar: PrimeFieldBits,
+    W: Write,
+{
+    let n8 = field_size::<Scalar>();
+
+    writer.write_all(&WTNS_MAGIC)?;
+    writer.write_u32::<LittleEndian>(WTNS_VERSION)?;
+    writer.write_u32::<LittleEndian>(2)?;
+
+    writer.write_u32::<LittleEndian>(WTNS_SECTION_HEADER)?;
+    writer.write_u64::<LittleEndian>((4 + n8 + 4) as u64)?;
+    writer.write_u32::<LittleEndian>(n8 as u32)?;
+    writer.write_all(&modulus::<Scalar>())?;
+    writer.write_u32::<LittleEndian>(to_u32(witness.len(), "number of wires")?)?;
+
+    writer.write_u32::<LittleEndian>(WTNS_SECTION_DATA)?;
+    writer.write_u64::<LittleEndian>((witness.len() * n8) as u64)?;
+    let mut buf = vec![0u8; n8];
+    for value in witness {
+        to_le_bytes(value, &mut buf);
+        writer.write_all(&buf)?;
+    }
+
+    Ok(())
+}
+
+/// Reads the assignment of all wires from a `.wtns` file over the scalar field `Scalar`.
+pub fn read_wtns<Scalar, R>(mut reader: R) -> io::Result<Vec<Scalar>>
+where
+    Scalar: PrimeFieldBits,
+    R: Read,
+{
+    let sections = read_sections(&mut reader, WTNS_MAGIC, WTNS_VERSION)?;
+    let section = |kind| {
+        sections
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, section)| &section[..])
+            .ok_or_else(|| invalid_data("missing wtns section"))
+    };
+
+    let mut header = section(WTNS_SECTION_HEADER)?;
+    read_field::<Scalar>(&mut header)?;
+    let len = header.read_u32::<LittleEndian>()? as usize;
+
+    let mut data = section(WTNS_SECTION_DATA)?;
+    let modulus = modulus::<Scalar>();
+    if data.len() != len * modulus.len() {
+        return Err(invalid_data("wtns data doesn't match the number of wires"));
+    }
+
+    let mut witness = Vec::with_capacity(len);
+    for _ in 0..len {
+        witness.push(read_scalar(&mut data, &modulus)?);
+    }
+
+    Ok(witness)
+}
+
+/// A circuit read from an `.r1cs` file, optionally with an assignment, see
+/// [`R1cs::circuit`].
+#[derive(Clone, Copy)]
+pub struct R1csCircuit<'a, Scalar: PrimeField> {
+    r1cs: &'a R1cs<Scalar>,
+    witness: Option<&'a [Scalar]>,
+}
+
+impl<'a, Scalar: PrimeField> Circuit<Scalar> for R1csCircuit<'a, Scalar> {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let r1cs = self.r1cs;
+        if let Some(witness) = self.witness {
+            if witness.len() != r1cs.num_wires {
+                return Err(SynthesisError::IncompatibleLengthVector(format!(
+                    "witness has {} wires, r1cs has {}",
+                    witness.len(),
+                    r1cs.num_wires
+                )));
+            }
+        }
+
+        let value = |wire: usize| {
+            move || {
+                self.witness
+                    .map(|witness| witness[wire])
+                    .ok_or(SynthesisError::AssignmentMissing)
+            }
+        };
+
+        let mut wires = Vec::with_capacity(r1cs.num_wires);
+        wires.push(CS::one());
+        for wire in 1..r1cs.num_wires {
+            let var = if wire <= r1cs.num_public() {
+                cs.alloc_input(|| format!("wire {}", wire), value(wire))?
+            } else {
+                cs.alloc(|| format!("wire {}", wire), value(wire))?
+            };
+            wires.push(var);
+        }
+
+        let add_terms = |lc: LinearCombination<Scalar>, terms: &R1csTerms<Scalar>| {
+            terms
+                .iter()
+                .fold(lc, |lc, &(wire, coeff)| lc + (coeff, wires[wire]))
+        };
+        for (i, (a, b, c)) in r1cs.constraints.iter().enumerate() {
+            cs.enforce(
+                || format!("constraint {}", i),
+                |lc| add_terms(lc, a),
+                |lc| add_terms(lc, b),
+                |lc| add_terms(lc, c),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// A constraint system which records the constraints and, if available, the assignment of
+/// a circuit, to export them in the `.r1cs` and `.wtns` formats.
+///
+/// Like [`TestConstraintSystem`](super::test_cs::TestConstraintSystem) it starts with the
+/// input for the constant one, so circuits can be synthesized into it directly.
+pub struct R1csCS<Scalar: PrimeField> {
+    inputs: Vec<Option<Scalar>>,
+    aux: Vec<Option<Scalar>>,
+    #[allow(clippy::type_complexity)]
+    constraints: Vec<(
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+    )>,
+}
+
+impl<Scalar: PrimeField> Default for R1csCS<Scalar> {
+    fn default() -> Self {
+        R1csCS {
+            inputs: vec![Some(Scalar::one())],
+            aux: vec![],
+            constraints: vec![],
+        }
+    }
+}
+
+impl<Scalar: PrimeField> R1csCS<Scalar> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.aux.len()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    fn wire(&self, var: Variable) -> usize {
+        match var.get_unchecked() {
+            Index::Input(i) => i,
+            Index::Aux(i) => self.inputs.len() + i,
+        }
+    }
+
+    /// The constraints over wires, see the [module documentation](self) for how variables
+    /// are mapped to wires.
+    pub fn to_r1cs(&self) -> R1cs<Scalar> {
+        let terms = |lc: &LinearCombination<Scalar>| -> R1csTerms<Scalar> {
+            lc.iter()
+                .filter(|(_, coeff)| !bool::from(coeff.is_zero()))
+                .map(|(var, coeff)| (self.wire(var), *coeff))
+                .collect()
+        };
+
+        R1cs {
+            num_wires: self.inputs.len() + self.aux.len(),
+            num_pub_out: 0,
+            num_pub_in: self.inputs.len() - 1,
+            num_prv_in: 0,
+            constraints: self
+                .constraints
+                .iter()
+                .map(|(a, b, c)| (terms(a), terms(b), terms(c)))
+                .collect(),
+        }
+    }
+
+    /// The assignment of all wires, if every variable was assigned.
+    pub fn witness(&self) -> Option<Vec<Scalar>> {
+        self.inputs.iter().chain(&self.aux).cloned().collect()
+    }
+}
+
+impl<Scalar: PrimeFieldBits> R1csCS<Scalar> {
+    pub fn write_r1cs<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.to_r1cs().write(writer)
+    }
+
+    /// Writes the assignment, which fails if the circuit was synthesized without one.
+    pub fn write_wtns<W: Write>(&self, writer: W) -> io::Result<()> {
+        let witness = self.witness().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "missing variable assignment")
+        })?;
+        write_wtns(&witness, writer)
+    }
+}
+
+/// Assignments are recorded if they can be computed, so that circuits can also be exported
+/// without a witness.
+fn assignment<Scalar, F>(f: F) -> Result<Option<Scalar>, SynthesisError>
+where
+    F: FnOnce() -> Result<Scalar, SynthesisError>,
+{
+    match f() {
+        Ok(value) => Ok(Some(value)),
+        Err(SynthesisError::AssignmentMissing) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for R1csCS<Scalar> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Default::default()
+    }
+
+    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux.push(assignment(f)?);
+
+        Ok(Variable::new_unchecked(Index::Aux(self.aux.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.inputs.push(assignment(f)?);
+
+        Ok(Variable::new_unchecked(Index::Input(self.inputs.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.constraints.push((a, b, c));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn to_u32(value: usize, what: &str) -> io::Result<u32> {
+    if value > u32::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} doesn't fit into the r1cs format", what),
+        ));
+    }
+    Ok(value as u32)
+}
+
+/// Reads the magic bytes, the version and all sections of an iden3 binary file.
+fn read_sections<R: Read>(
+    mut reader: R,
+    magic: [u8; 4],
+    version: u32,
+) -> io::Result<Vec<(u32, Vec<u8>)>> {
+    let mut file_magic = [0u8; 4];
+    reader.read_exact(&mut file_magic)?;
+    if file_magic != magic {
+        return Err(invalid_data("invalid magic bytes"));
+    }
+    if reader.read_u32::<LittleEndian>()? != version {
+        return Err(invalid_data("unsupported file version"));
+    }
+
+    let num_sections = reader.read_u32::<LittleEndian>()?;
+    let mut sections = vec![];
+    for _ in 0..num_sections {
+        let kind = reader.read_u32::<LittleEndian>()?;
+        let size = reader.read_u64::<LittleEndian>()?;
+
+        // The size isn't trusted for allocations before the data was actually read.
+        let mut section = vec![];
+        (&mut reader).take(size).read_to_end(&mut section)?;
+        if section.len() as u64 != size {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated section",
+            ));
+        }
+        sections.push((kind, section));
+    }
+
+    Ok(sections)
+}
+
+/// Reads the size and the modulus of the field, which must be the scalar field.
+fn read_field<Scalar: PrimeFieldBits>(reader: &mut &[u8]) -> io::Result<()> {
+    let n8 = reader.read_u32::<LittleEndian>()? as usize;
+    if n8 != field_size::<Scalar>() {
+        return Err(invalid_data("field size doesn't match the scalar field"));
+    }
+    let mut prime = vec![0u8; n8];
+    reader.read_exact(&mut prime)?;
+    if prime != modulus::<Scalar>() {
+        return Err(invalid_data("prime doesn't match the scalar field"));
+    }
+
+    Ok(())
+}
+
+/// The size of an encoded field element in bytes.
+fn field_size<Scalar: PrimeField>() -> usize {
+    let limbs = (Scalar::NUM_BITS as usize - 1) / 64 + 1;
+    limbs * 8
+}
+
+fn modulus<Scalar: PrimeFieldBits>() -> Vec<u8> {
+    let mut bytes = vec![0u8; field_size::<Scalar>()];
+    bits_to_le_bytes(Scalar::char_le_bits(), &mut bytes);
+    bytes
+}
+
+fn to_le_bytes<Scalar: PrimeFieldBits>(value: &Scalar, out: &mut [u8]) {
+    for byte in out.iter_mut() {
+        *byte = 0;
+    }
+    bits_to_le_bytes(value.to_le_bits(), out);
+}
+
+// Bits beyond `out` are zero, as the values are less than the modulus.
+fn bits_to_le_bytes<I: IntoIterator<Item = bool>>(bits: I, out: &mut [u8]) {
+    for (i, bit) in bits.into_iter().take(out.len() * 8).enumerate() {
+        if bit {
+            out[i / 8] |= 1 << (i % 8);
+        }
+    }
+}
+
+/// Reads a field element, rejecting non-canonical encodings.
+fn read_scalar<Scalar: PrimeField>(reader: &mut &[u8], modulus: &[u8]) -> io::Result<Scalar> {
+    if reader.len() < modulus.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated field element",
+        ));
+    }
+    let (bytes, rest) = reader.split_at(modulus.len());
+    *reader = rest;
+
+    if bytes.iter().rev().cmp(modulus.iter().rev()) != Ordering::Less {
+        return Err(invalid_data("field element isn't canonical"));
+    }
+
+    // Horner's method over 64-bit limbs, starting with the most significant one.
+    let shift = Scalar::from(u64::MAX) + Scalar::one();
+    let mut value = Scalar::zero();
+    for limb in bytes.chunks(8).rev() {
+        let mut limb_bytes = [0u8; 8];
+        limb_bytes.copy_from_slice(limb);
+        value = value * shift + Scalar::from(u64::from_le_bytes(limb_bytes));
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of `x` with `x^3 + x + 5 = y` for the public input `y`.
+    struct CubicCircuit {
+        x: Option<Fr>,
+    }
+
+    impl Circuit<Fr> for CubicCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let x_val = self.x;
+            let x = cs.alloc(|| "x", || x_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let x2_val = x_val.map(|x| x.square());
+            let x2 = cs.alloc(|| "x^2", || x2_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let x3_val = x2_val.and_then(|x2| x_val.map(|x| x2 * x));
+            let x3 = cs.alloc(|| "x^3", || x3_val.ok_or(SynthesisError::AssignmentMissing))?;
+            let y = cs.alloc_input(
+                || "y",
+                || {
+                    x3_val
+                        .and_then(|x3| x_val.map(|x| x3 + x + Fr::from(5u64)))
+                        .ok_or(SynthesisError::AssignmentMissing)
+                },
+            )?;
+
+            cs.enforce(|| "x * x = x^2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+            cs.enforce(|| "x^2 * x = x^3", |lc| lc + x2, |lc| lc + x, |lc| lc + x3);
+            cs.enforce(
+                || "(x^3 + x + 5) * 1 = y",
+                |lc| lc + x3 + x + (Fr::from(5u64), CS::one()),
+                |lc| lc + CS::one(),
+                |lc| lc + y,
+            );
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_r1cs_roundtrip() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let x = Fr::random(&mut *rng);
+        let y = x.square() * x + x + Fr::from(5u64);
+        let mut cs = R1csCS::<Fr>::new();
+        CubicCircuit { x: Some(x) }.synthesize(&mut cs).unwrap();
+        assert_eq!(cs.num_inputs(), 2);
+        assert_eq!(cs.num_aux(), 3);
+
+        let r1cs = cs.to_r1cs();
+        assert_eq!(r1cs.num_wires, 5);
+        assert_eq!(r1cs.num_public(), 1);
+        // y is wire 1, x is wire 2.
+        assert_eq!(
+            r1cs.constraints[2].0,
+            vec![(0, Fr::from(5u64)), (2, Fr::one()), (4, Fr::one())]
+        );
+        let witness = cs.witness().unwrap();
+        assert_eq!(witness, vec![Fr::one(), y, x, x.square(), x.square() * x]);
+
+        let mut r1cs_bytes = vec![];
+        cs.write_r1cs(&mut r1cs_bytes).unwrap();
+        let mut wtns_bytes = vec![];
+        cs.write_wtns(&mut wtns_bytes).unwrap();
+
+        // The modulus of the BLS12-381 scalar field follows the magic, version, number of
+        // sections, type and size of the header section, and the field size.
+        let modulus =
+            hex::decode("73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001")
+                .unwrap();
+        let le_modulus = modulus.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(&r1cs_bytes[..4], b"r1cs");
+        assert_eq!(&r1cs_bytes[24..28], &32u32.to_le_bytes());
+        assert_eq!(&r1cs_bytes[28..60], &le_modulus[..]);
+        assert_eq!(&wtns_bytes[..4], b"wtns");
+        assert_eq!(&wtns_bytes[28..60], &le_modulus[..]);
+        // The first wire is the constant one, in canonical form.
+        let data = wtns_bytes.len() - 5 * 32;
+        assert_eq!(wtns_bytes[data], 1);
+        assert!(wtns_bytes[data + 1..data + 32].iter().all(|b| *b == 0));
+
+        let read = R1cs::<Fr>::read(&r1cs_bytes[..]).unwrap();
+        assert_eq!(read, r1cs);
+        let read_witness = read_wtns::<Fr, _>(&wtns_bytes[..]).unwrap();
+        assert_eq!(read_witness, witness);
+
+        // The imported circuit has the same constraints, and can be proven.
+        let mut test_cs = TestConstraintSystem::<Fr>::new();
+        read.circuit(Some(&read_witness))
+            .synthesize(&mut test_cs)
+            .unwrap();
+        assert!(test_cs.is_satisfied());
+        assert_eq!(test_cs.num_constraints(), 3);
+        assert_eq!(test_cs.num_inputs(), 2);
+
+        let mut reexported = R1csCS::<Fr>::new();
+        read.circuit(None).synthesize(&mut reexported).unwrap();
+        assert_eq!(reexported.to_r1cs(), r1cs);
+        assert!(reexported.witness().is_none());
+        assert!(reexported.write_wtns(&mut vec![]).is_err());
+
+        let params = generate_random_parameters::<Bls12, _, _>(read.circuit(None), rng).unwrap();
+        let proof = create_random_proof(read.circuit(Some(&read_witness)), &params, rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        assert!(verify_proof(&pvk, &proof, &[y]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[y + Fr::one()]).unwrap());
+
+        let short_witness = &read_witness[..4];
+        assert!(read
+            .circuit(Some(short_witness))
+            .synthesize(&mut TestConstraintSystem::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_r1cs_invalid() {
+        let mut cs = R1csCS::<Fr>::new();
+        CubicCircuit {
+            x: Some(Fr::from(3u64)),
+        }
+        .synthesize(&mut cs)
+        .unwrap();
+        let mut r1cs_bytes = vec![];
+        cs.write_r1cs(&mut r1cs_bytes).unwrap();
+        let mut wtns_bytes = vec![];
+        cs.write_wtns(&mut wtns_bytes).unwrap();
+
+        // Another field
+        let mut other_field = r1cs_bytes.clone();
+        other_field[40] ^= 1;
+        assert!(R1cs::<Fr>::read(&other_field[..]).is_err());
+        let mut other_field = wtns_bytes.clone();
+        other_field[40] ^= 1;
+        assert!(read_wtns::<Fr, _>(&other_field[..]).is_err());
+
+        // Truncated files
+        assert!(R1cs::<Fr>::read(&r1cs_bytes[..r1cs_bytes.len() - 1]).is_err());
+        assert!(read_wtns::<Fr, _>(&wtns_bytes[..wtns_bytes.len() - 1]).is_err());
+
+        // A wire beyond the number of wires, in the first term of the first constraint
+        let mut wire_out_of_range = r1cs_bytes.clone();
+        let constraints = 12 + 12 + 64 + 12;
+        wire_out_of_range[constraints + 4] = 5;
+        assert!(R1cs::<Fr>::read(&wire_out_of_range[..]).is_err());
+
+        // A non-canonical value, the modulus itself
+        let mut non_canonical = wtns_bytes.clone();
+        let data = non_canonical.len() - 5 * 32;
+        let modulus = modulus::<Fr>();
+        non_canonical[data..data + 32].copy_from_slice(&modulus);
+        assert!(read_wtns::<Fr, _>(&non_canonical[..]).is_err());
+    }
+}
@@ -0,0 +1,498 @@
+//! A constraint system which checks every constraint of a circuit as it is enforced, and
+//! reports all unsatisfied ones.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use ff::PrimeField;
+
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// A variable of an unsatisfied constraint, with its assignment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableAssignment<Scalar: PrimeField> {
+    pub index: Index,
+    /// The path of the variable, its annotation prefixed by the namespaces it was allocated in.
+    pub path: String,
+    pub value: Scalar,
+}
+
+/// A constraint `A * B = C` which isn't satisfied by the assignment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnsatisfiedConstraint<Scalar: PrimeField> {
+    /// The position of the constraint, in the order constraints were enforced.
+    pub index: usize,
+    /// The path of the namespace the constraint was enforced in.
+    pub namespace: String,
+    /// The path of the constraint, its annotation prefixed by `namespace`.
+    pub path: String,
+    /// The evaluations of the linear combinations `A`, `B` and `C`.
+    pub a: Scalar,
+    pub b: Scalar,
+    pub c: Scalar,
+    /// The variables appearing in any of the linear combinations, inputs first.
+    pub variables: Vec<VariableAssignment<Scalar>>,
+}
+
+/// The result of checking a circuit, see [`check_satisfiability`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct SatisfiabilityReport<Scalar: PrimeField> {
+    pub num_constraints: usize,
+    pub unsatisfied: Vec<UnsatisfiedConstraint<Scalar>>,
+}
+
+impl<Scalar: PrimeField> SatisfiabilityReport<Scalar> {
+    pub fn is_satisfied(&self) -> bool {
+        self.unsatisfied.is_empty()
+    }
+
+    /// The number of unsatisfied constraints per namespace. Constraints are only counted for
+    /// the namespace they were enforced in, not for its parents.
+    pub fn by_namespace(&self) -> BTreeMap<&str, usize> {
+        let mut summary = BTreeMap::new();
+        for constraint in &self.unsatisfied {
+            *summary.entry(constraint.namespace.as_str()).or_insert(0) += 1;
+        }
+
+        summary
+    }
+}
+
+impl<Scalar: PrimeField> fmt::Display for SatisfiabilityReport<Scalar> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_satisfied() {
+            return write!(f, "all {} constraints are satisfied", self.num_constraints);
+        }
+
+        writeln!(
+            f,
+            "{} of {} constraints are unsatisfied",
+            self.unsatisfied.len(),
+            self.num_constraints
+        )?;
+        for (namespace, count) in self.by_namespace() {
+            let namespace = if namespace.is_empty() { "/" } else { namespace };
+            writeln!(f, "  {}: {}", namespace, count)?;
+        }
+
+        for constraint in &self.unsatisfied {
+            writeln!(f, "constraint {} `{}`:", constraint.index, constraint.path)?;
+            writeln!(f, "  A = {:?}", constraint.a)?;
+            writeln!(f, "  B = {:?}", constraint.b)?;
+            writeln!(f, "  C = {:?}", constraint.c)?;
+            for var in &constraint.variables {
+                writeln!(f, "  `{}` = {:?}", var.path, var.value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks constraints while a circuit is synthesized, keeping track of namespaces and the
+/// paths of all variables so that unsatisfied constraints can be reported with them.
+///
+/// The assignment is owned by the constraint system using the checker, which passes it in.
+#[derive(Clone, Debug)]
+pub(crate) struct SatisfiabilityChecker<Scalar: PrimeField> {
+    current_namespace: Vec<String>,
+    input_paths: Vec<String>,
+    aux_paths: Vec<String>,
+    num_constraints: usize,
+    unsatisfied: Vec<UnsatisfiedConstraint<Scalar>>,
+}
+
+impl<Scalar: PrimeField> SatisfiabilityChecker<Scalar> {
+    pub(crate) fn new() -> Self {
+        SatisfiabilityChecker {
+            current_namespace: vec![],
+            input_paths: vec![],
+            aux_paths: vec![],
+            num_constraints: 0,
+            unsatisfied: vec![],
+        }
+    }
+
+    fn path(&self, name: &str) -> String {
+        if self.current_namespace.is_empty() {
+            return name.to_string();
+        }
+
+        format!("{}/{}", self.current_namespace.join("/"), name)
+    }
+
+    pub(crate) fn alloc_input(&mut self, name: &str) {
+        let path = self.path(name);
+        self.input_paths.push(path);
+    }
+
+    pub(crate) fn alloc_aux(&mut self, name: &str) {
+        let path = self.path(name);
+        self.aux_paths.push(path);
+    }
+
+    pub(crate) fn push_namespace(&mut self, name: String) {
+        self.current_namespace.push(name);
+    }
+
+    pub(crate) fn pop_namespace(&mut self) {
+        assert!(self.current_namespace.pop().is_some());
+    }
+
+    /// Records the constraint if the evaluations `a * b` and `c` differ. The annotation is
+    /// only computed in that case.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn enforce<A, AR>(
+        &mut self,
+        annotation: A,
+        lcs: [&LinearCombination<Scalar>; 3],
+        a: Scalar,
+        b: Scalar,
+        c: Scalar,
+        input_assignment: &[Scalar],
+        aux_assignment: &[Scalar],
+    ) where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let index = self.num_constraints;
+        self.num_constraints += 1;
+        if a * b == c {
+            return;
+        }
+
+        let mut inputs = BTreeSet::new();
+        let mut aux = BTreeSet::new();
+        for lc in lcs.iter() {
+            for (i, _) in lc.iter_inputs() {
+                inputs.insert(*i);
+            }
+            for (i, _) in lc.iter_aux() {
+                aux.insert(*i);
+            }
+        }
+        let variables = inputs
+            .into_iter()
+            .map(|i| VariableAssignment {
+                index: Index::Input(i),
+                path: self.input_paths[i].clone(),
+                value: input_assignment[i],
+            })
+            .chain(aux.into_iter().map(|i| VariableAssignment {
+                index: Index::Aux(i),
+                path: self.aux_paths[i].clone(),
+                value: aux_assignment[i],
+            }))
+            .collect();
+
+        self.unsatisfied.push(UnsatisfiedConstraint {
+            index,
+            namespace: self.current_namespace.join("/"),
+            path: self.path(&annotation().into()),
+            a,
+            b,
+            c,
+            variables,
+        });
+    }
+
+    /// Merges the checker of a constraint system which the constraint system using this
+    /// checker is extended with, see [`ConstraintSystem::extend`]. The paths of `other` are
+    /// moved into the current namespace.
+    pub(crate) fn extend(&mut self, other: Self) {
+        let input_offset = self.input_paths.len() - 1;
+        let aux_offset = self.aux_paths.len();
+        let constraint_offset = self.num_constraints;
+
+        for mut constraint in other.unsatisfied {
+            constraint.index += constraint_offset;
+            constraint.namespace = if constraint.namespace.is_empty() {
+                self.current_namespace.join("/")
+            } else {
+                self.path(&constraint.namespace)
+            };
+            constraint.path = self.path(&constraint.path);
+            for var in &mut constraint.variables {
+                if var.index == Index::Input(0) {
+                    // The constant one is shared.
+                    var.path = self.input_paths[0].clone();
+                } else {
+                    var.index = Variable::new_unchecked(var.index)
+                        .shift(input_offset, aux_offset)
+                        .get_unchecked();
+                    var.path = self.path(&var.path);
+                }
+            }
+            self.unsatisfied.push(constraint);
+        }
+
+        let input_paths = other.input_paths[1..]
+            .iter()
+            .map(|path| self.path(path))
+            .collect::<Vec<_>>();
+        let aux_paths = other
+            .aux_paths
+            .iter()
+            .map(|path| self.path(path))
+            .collect::<Vec<_>>();
+        self.input_paths.extend(input_paths);
+        self.aux_paths.extend(aux_paths);
+        self.num_constraints += other.num_constraints;
+    }
+
+    /// Like [`extend`](Self::extend), for a constraint system which didn't check its
+    /// constraints. Only the evaluations `a * b = c` of its constraints are checked, and its
+    /// variables and constraints are reported by their index instead of their path.
+    pub(crate) fn extend_unnamed(
+        &mut self,
+        num_inputs: usize,
+        num_aux: usize,
+        evaluations: (&[Scalar], &[Scalar], &[Scalar]),
+    ) {
+        let (a, b, c) = evaluations;
+        for (i, ((a, b), c)) in a.iter().zip(b).zip(c).enumerate() {
+            if *a * b != *c {
+                self.unsatisfied.push(UnsatisfiedConstraint {
+                    index: self.num_constraints + i,
+                    namespace: self.current_namespace.join("/"),
+                    path: self.path(&format!("extended constraint {}", i)),
+                    a: *a,
+                    b: *b,
+                    c: *c,
+                    variables: vec![],
+                });
+            }
+        }
+
+        for i in 1..num_inputs {
+            let path = self.path(&format!("extended input {}", i));
+            self.input_paths.push(path);
+        }
+        for i in 0..num_aux {
+            let path = self.path(&format!("extended aux {}", i));
+            self.aux_paths.push(path);
+        }
+        self.num_constraints += a.len();
+    }
+
+    pub(crate) fn into_report(self) -> SatisfiabilityReport<Scalar> {
+        SatisfiabilityReport {
+            num_constraints: self.num_constraints,
+            unsatisfied: self.unsatisfied,
+        }
+    }
+}
+
+/// A constraint system which evaluates every constraint as it is enforced and records the
+/// unsatisfied ones, together with the paths and assignments of their variables.
+///
+/// Unlike [`TestConstraintSystem`](super::test_cs::TestConstraintSystem), constraints aren't
+/// kept around and names aren't required to be unique, so it can check circuits of
+/// production size.
+pub struct SatisfiabilityCS<Scalar: PrimeField> {
+    input_assignment: Vec<Scalar>,
+    aux_assignment: Vec<Scalar>,
+    checker: SatisfiabilityChecker<Scalar>,
+}
+
+impl<Scalar: PrimeField> Default for SatisfiabilityCS<Scalar> {
+    fn default() -> Self {
+        let mut checker = SatisfiabilityChecker::new();
+        checker.alloc_input("ONE");
+
+        SatisfiabilityCS {
+            input_assignment: vec![Scalar::one()],
+            aux_assignment: vec![],
+            checker,
+        }
+    }
+}
+
+impl<Scalar: PrimeField> SatisfiabilityCS<Scalar> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.checker.num_constraints
+    }
+
+    pub fn is_satisfied(&self) -> bool {
+        self.checker.unsatisfied.is_empty()
+    }
+
+    pub fn unsatisfied(&self) -> &[UnsatisfiedConstraint<Scalar>] {
+        &self.checker.unsatisfied
+    }
+
+    pub fn into_report(self) -> SatisfiabilityReport<Scalar> {
+        self.checker.into_report()
+    }
+}
+
+/// Synthesizes `circuit` with its witness and checks all of its constraints.
+pub fn check_satisfiability<Scalar, C>(
+    circuit: C,
+) -> Result<SatisfiabilityReport<Scalar>, SynthesisError>
+where
+    Scalar: PrimeField,
+    C: Circuit<Scalar>,
+{
+    let mut cs = SatisfiabilityCS::new();
+    circuit.synthesize(&mut cs)?;
+
+    Ok(cs.into_report())
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for SatisfiabilityCS<Scalar> {
+    type Root = Self;
+
+    fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux_assignment.push(f()?);
+        self.checker.alloc_aux(&annotation().into());
+
+        Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.input_assignment.push(f()?);
+        self.checker.alloc_input(&annotation().into());
+
+        Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        let inputs = &self.input_assignment;
+        let aux = &self.aux_assignment;
+        let a_res = a.eval(None, None, inputs, aux);
+        let b_res = b.eval(None, None, inputs, aux);
+        let c_res = c.eval(None, None, inputs, aux);
+
+        self.checker
+            .enforce(annotation, [&a, &b, &c], a_res, b_res, c_res, inputs, aux);
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.checker.push_namespace(name_fn().into());
+    }
+
+    fn pop_namespace(&mut self) {
+        self.checker.pop_namespace();
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    // Proves knowledge of the factors of `c`, once in each of the namespaces.
+    struct FactorCircuit {
+        namespaces: Vec<(&'static str, u64, u64, u64)>,
+    }
+
+    impl Circuit<Fr> for FactorCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            for (name, a, b, c) in self.namespaces {
+                let mut cs = cs.namespace(|| name);
+                let a = cs.alloc(|| "a", || Ok(Fr::from(a)))?;
+                let b = cs.alloc(|| "b", || Ok(Fr::from(b)))?;
+                let c = cs.alloc_input(|| "c", || Ok(Fr::from(c)))?;
+                cs.enforce(|| "a * b = c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+                cs.enforce(
+                    || "a * 1 = a",
+                    |lc| lc + a,
+                    |lc| lc + CS::one(),
+                    |lc| lc + a,
+                );
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sat_cs() {
+        let report = check_satisfiability(FactorCircuit {
+            namespaces: vec![("first", 3, 5, 15), ("second", 2, 7, 14)],
+        })
+        .unwrap();
+        assert!(report.is_satisfied());
+        assert_eq!(report.num_constraints, 4);
+
+        let report = check_satisfiability(FactorCircuit {
+            namespaces: vec![
+                ("first", 3, 5, 16),
+                ("second", 2, 7, 14),
+                ("third", 1, 1, 2),
+            ],
+        })
+        .unwrap();
+        assert!(!report.is_satisfied());
+        assert_eq!(report.num_constraints, 6);
+        assert_eq!(report.unsatisfied.len(), 2);
+
+        let first = &report.unsatisfied[0];
+        assert_eq!(first.index, 0);
+        assert_eq!(first.namespace, "first");
+        assert_eq!(first.path, "first/a * b = c");
+        assert_eq!(first.a, Fr::from(3u64));
+        assert_eq!(first.b, Fr::from(5u64));
+        assert_eq!(first.c, Fr::from(16u64));
+        let paths = first
+            .variables
+            .iter()
+            .map(|var| var.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["first/c", "first/a", "first/b"]);
+        assert_eq!(first.variables[0].index, Index::Input(1));
+        assert_eq!(first.variables[0].value, Fr::from(16u64));
+
+        assert_eq!(report.unsatisfied[1].path, "third/a * b = c");
+        let summary = report.by_namespace();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary["first"], 1);
+        assert_eq!(summary["third"], 1);
+
+        let printed = report.to_string();
+        assert!(printed.starts_with("2 of 6 constraints are unsatisfied"));
+        assert!(printed.contains("constraint 4 `third/a * b = c`"));
+
+        // Names don't need to be unique.
+        let mut cs = SatisfiabilityCS::<Fr>::new();
+        cs.alloc(|| "x", || Ok(Fr::one())).unwrap();
+        cs.alloc(|| "x", || Ok(Fr::one())).unwrap();
+        assert!(cs.is_satisfied());
+    }
+}
@@ -0,0 +1,447 @@
+//! A constraint system which only records the shape of a circuit, and the [`CircuitDigest`]
+//! which identifies it.
+
+use std::fmt;
+
+use blake2s_simd::State as Blake2s;
+use ff::PrimeField;
+
+use crate::{Circuit, ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// Domain separator of circuit digests, which also versions the digest.
+const CIRCUIT_DIGEST_DOMAIN: &[u8] = b"bellperson circuit digest v1";
+
+/// Identifies the constraint system of a circuit: the BLAKE2s-256 digest of all constraints
+/// and the number of variables.
+///
+/// Unlike [`TestConstraintSystem::hash`](super::test_cs::TestConstraintSystem::hash), the
+/// digest doesn't depend on names of variables, constraints or namespaces, so renaming
+/// doesn't change it, and it can be computed by constraint systems which ignore names.
+/// Assignments of variables don't affect the digest either.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitDigest(pub [u8; 32]);
+
+impl CircuitDigest {
+    /// Synthesizes `circuit` without a witness, like the parameter generator does, and
+    /// returns its digest.
+    pub fn of_circuit<Scalar, C>(circuit: C) -> Result<Self, SynthesisError>
+    where
+        Scalar: PrimeField,
+        C: Circuit<Scalar>,
+    {
+        let mut cs = ShapeCS::new();
+        cs.alloc_input(|| "", || Ok(Scalar::one()))?;
+        circuit.synthesize(&mut cs)?;
+
+        Ok(cs.digest())
+    }
+}
+
+impl fmt::Display for CircuitDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CircuitDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CircuitDigest({})", self)
+    }
+}
+
+/// Computes a [`CircuitDigest`] incrementally, while a circuit is synthesized.
+#[derive(Clone)]
+pub(crate) struct CircuitHasher {
+    state: Blake2s,
+    num_inputs: u64,
+    num_aux: u64,
+    num_constraints: u64,
+}
+
+impl CircuitHasher {
+    pub(crate) fn new() -> Self {
+        let mut state = Blake2s::new();
+        state.update(CIRCUIT_DIGEST_DOMAIN);
+
+        CircuitHasher {
+            state,
+            num_inputs: 0,
+            num_aux: 0,
+            num_constraints: 0,
+        }
+    }
+
+    pub(crate) fn alloc_input(&mut self) {
+        self.num_inputs += 1;
+    }
+
+    pub(crate) fn alloc_aux(&mut self) {
+        self.num_aux += 1;
+    }
+
+    pub(crate) fn enforce<Scalar: PrimeField>(
+        &mut self,
+        a: &LinearCombination<Scalar>,
+        b: &LinearCombination<Scalar>,
+        c: &LinearCombination<Scalar>,
+    ) {
+        self.num_constraints += 1;
+        self.hash_lc(a);
+        self.hash_lc(b);
+        self.hash_lc(c);
+    }
+
+    // Terms with a zero coefficient are skipped, they don't change the constraint.
+    fn hash_lc<Scalar: PrimeField>(&mut self, lc: &LinearCombination<Scalar>) {
+        let terms = || lc.iter().filter(|(_, coeff)| !bool::from(coeff.is_zero()));
+        self.state.update(&(terms().count() as u64).to_be_bytes());
+
+        for (var, coeff) in terms() {
+            let (tag, index) = match var.get_unchecked() {
+                Index::Input(i) => (b'I', i),
+                Index::Aux(i) => (b'A', i),
+            };
+            self.state.update(&[tag]);
+            self.state.update(&(index as u64).to_be_bytes());
+            self.state.update(coeff.to_repr().as_ref());
+        }
+    }
+
+    /// Continues with the constraints of a part of the circuit which was synthesized on its
+    /// own, as if they were synthesized here, see [`ConstraintSystem::extend`]. The first
+    /// input of the part is the constant one, which is shared.
+    pub(crate) fn extend<Scalar: PrimeField>(&mut self, part: ShapeCS<Scalar>) {
+        let input_offset = self.num_inputs as usize - 1;
+        let aux_offset = self.num_aux as usize;
+        for (a, b, c) in part.constraints {
+            self.enforce(
+                &a.shift(input_offset, aux_offset),
+                &b.shift(input_offset, aux_offset),
+                &c.shift(input_offset, aux_offset),
+            );
+        }
+        self.num_inputs += part.num_inputs as u64 - 1;
+        self.num_aux += part.num_aux as u64;
+    }
+
+    /// The digest of everything synthesized so far.
+    pub(crate) fn digest(&self) -> CircuitDigest {
+        let mut state = self.state.clone();
+        state.update(&self.num_inputs.to_be_bytes());
+        state.update(&self.num_aux.to_be_bytes());
+        state.update(&self.num_constraints.to_be_bytes());
+
+        let mut out = [0u8; 32];
+        out.copy_from_slice(state.finalize().as_bytes());
+        CircuitDigest(out)
+    }
+}
+
+/// Computes a [`CircuitDigest`] in a constraint system which can be extended with parts of
+/// the circuit synthesized on their own, see [`ConstraintSystem::extend`]. The indices of
+/// the variables of a part are only known once a constraint system is extended with it, so
+/// the constraints of parts are recorded and only hashed then.
+pub(crate) enum ExtensibleHasher<Scalar: PrimeField> {
+    Circuit(CircuitHasher),
+    Part(ShapeCS<Scalar>),
+}
+
+impl<Scalar: PrimeField> ExtensibleHasher<Scalar> {
+    pub(crate) fn new() -> Self {
+        ExtensibleHasher::Circuit(CircuitHasher::new())
+    }
+
+    /// A hasher for a part of the circuit, for a constraint system created by
+    /// [`ConstraintSystem::new_for_extend`].
+    pub(crate) fn for_part(&self) -> Self {
+        ExtensibleHasher::Part(ShapeCS::new())
+    }
+
+    pub(crate) fn alloc_input(&mut self) {
+        match self {
+            ExtensibleHasher::Circuit(hasher) => hasher.alloc_input(),
+            ExtensibleHasher::Part(shape) => shape.num_inputs += 1,
+        }
+    }
+
+    pub(crate) fn alloc_aux(&mut self) {
+        match self {
+            ExtensibleHasher::Circuit(hasher) => hasher.alloc_aux(),
+            ExtensibleHasher::Part(shape) => shape.num_aux += 1,
+        }
+    }
+
+    pub(crate) fn enforce(
+        &mut self,
+        a: &LinearCombination<Scalar>,
+        b: &LinearCombination<Scalar>,
+        c: &LinearCombination<Scalar>,
+    ) {
+        match self {
+            ExtensibleHasher::Circuit(hasher) => hasher.enforce(a, b, c),
+            ExtensibleHasher::Part(shape) => {
+                shape.constraints.push((a.clone(), b.clone(), c.clone()))
+            }
+        }
+    }
+
+    /// Continues with a part of the circuit, recorded by a hasher from
+    /// [`for_part`](Self::for_part). Returns `None` if the part wasn't recorded, in which
+    /// case the digest can't be computed anymore.
+    pub(crate) fn extend(self, part: Option<Self>) -> Option<Self> {
+        match (self, part) {
+            (ExtensibleHasher::Circuit(mut hasher), Some(ExtensibleHasher::Part(part))) => {
+                hasher.extend(part);
+                Some(ExtensibleHasher::Circuit(hasher))
+            }
+            (ExtensibleHasher::Part(mut shape), Some(ExtensibleHasher::Part(part))) => {
+                shape.extend(part);
+                Some(ExtensibleHasher::Part(shape))
+            }
+            _ => None,
+        }
+    }
+
+    /// The digest of everything synthesized so far, which is only known for the whole
+    /// circuit, not for its parts.
+    pub(crate) fn digest(&self) -> Option<CircuitDigest> {
+        match self {
+            ExtensibleHasher::Circuit(hasher) => Some(hasher.digest()),
+            ExtensibleHasher::Part(_) => None,
+        }
+    }
+}
+
+/// A constraint system which records the constraints of a circuit, but neither names nor
+/// assignments. Closures computing assignments are never called, so circuits can be
+/// synthesized without a witness.
+pub struct ShapeCS<Scalar: PrimeField> {
+    num_inputs: usize,
+    num_aux: usize,
+    #[allow(clippy::type_complexity)]
+    constraints: Vec<(
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+    )>,
+}
+
+impl<Scalar: PrimeField> ShapeCS<Scalar> {
+    pub fn new() -> Self {
+        ShapeCS::default()
+    }
+
+    pub fn num_inputs(&self) -> usize {
+        self.num_inputs
+    }
+
+    pub fn num_aux(&self) -> usize {
+        self.num_aux
+    }
+
+    pub fn num_constraints(&self) -> usize {
+        self.constraints.len()
+    }
+
+    /// The constraints `A * B = C`, in the order they were enforced.
+    #[allow(clippy::type_complexity)]
+    pub fn constraints(
+        &self,
+    ) -> &[(
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+    )] {
+        &self.constraints
+    }
+
+    pub fn digest(&self) -> CircuitDigest {
+        let mut hasher = CircuitHasher::new();
+        hasher.num_inputs = self.num_inputs as u64;
+        hasher.num_aux = self.num_aux as u64;
+        for (a, b, c) in &self.constraints {
+            hasher.enforce(a, b, c);
+        }
+
+        hasher.digest()
+    }
+}
+
+impl<Scalar: PrimeField> Default for ShapeCS<Scalar> {
+    fn default() -> Self {
+        ShapeCS {
+            num_inputs: 0,
+            num_aux: 0,
+            constraints: vec![],
+        }
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for ShapeCS<Scalar> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn alloc<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.num_aux += 1;
+
+        Ok(Variable::new_unchecked(Index::Aux(self.num_aux - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _annotation: A, _f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.num_inputs += 1;
+
+        Ok(Variable::new_unchecked(Index::Input(self.num_inputs - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _annotation: A, a: LA, b: LB, c: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        let a = a(LinearCombination::zero());
+        let b = b(LinearCombination::zero());
+        let c = c(LinearCombination::zero());
+
+        self.constraints.push((a, b, c));
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self) {}
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        let input_offset = self.num_inputs - 1;
+        let aux_offset = self.num_aux;
+        let shift = |lc: LinearCombination<Scalar>| lc.shift(input_offset, aux_offset);
+        self.constraints.extend(
+            other
+                .constraints
+                .into_iter()
+                .map(|(a, b, c)| (shift(a), shift(b), shift(c))),
+        );
+
+        // The first input of `other` is the constant one, which is shared.
+        self.num_inputs += other.num_inputs - 1;
+        self.num_aux += other.num_aux;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::util_cs::test_cs::TestConstraintSystem;
+    use blstrs::Scalar as Fr;
+    use ff::Field;
+
+    // Proves knowledge of the factors of the public input. The names and the coefficient
+    // of `a` can be changed.
+    struct FactorCircuit {
+        a: Option<Fr>,
+        b: Option<Fr>,
+        name: &'static str,
+        coeff: u64,
+    }
+
+    impl Circuit<Fr> for FactorCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut cs = cs.namespace(|| self.name);
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || "c",
+                || Ok(self.a.unwrap_or_default() * self.b.unwrap_or_default()),
+            )?;
+            cs.enforce(
+                || format!("{} a * b = c", self.name),
+                |lc| lc + (Fr::from(self.coeff), a),
+                |lc| lc + b,
+                |lc| lc + c,
+            );
+
+            Ok(())
+        }
+    }
+
+    fn digest(name: &'static str, coeff: u64, a: Option<Fr>) -> CircuitDigest {
+        CircuitDigest::of_circuit(FactorCircuit {
+            a,
+            b: a.map(|a| a.double()),
+            name,
+            coeff,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_circuit_digest() {
+        let base = digest("factor", 1, None);
+
+        // Names and assignments don't matter.
+        assert_eq!(digest("renamed", 1, None), base);
+        assert_eq!(digest("factor", 1, Some(Fr::from(3u64))), base);
+
+        // The constraints do.
+        assert_ne!(digest("factor", 2, None), base);
+
+        // The digest is the same whether or not names are recorded.
+        let mut cs = TestConstraintSystem::<Fr>::new();
+        FactorCircuit {
+            a: Some(Fr::from(3u64)),
+            b: Some(Fr::from(5u64)),
+            name: "factor",
+            coeff: 1,
+        }
+        .synthesize(&mut cs)
+        .unwrap();
+        assert!(cs.is_satisfied());
+
+        let mut shape = ShapeCS::<Fr>::new();
+        shape.alloc_input(|| "", || Ok(Fr::one())).unwrap();
+        FactorCircuit {
+            a: None,
+            b: None,
+            name: "factor",
+            coeff: 1,
+        }
+        .synthesize(&mut shape)
+        .unwrap();
+        assert_eq!(shape.num_inputs(), cs.num_inputs());
+        assert_eq!(shape.num_constraints(), cs.num_constraints());
+        assert_eq!(shape.num_aux(), 2);
+        assert_eq!(shape.digest(), base);
+        assert_eq!(base.to_string().len(), 64);
+    }
+}
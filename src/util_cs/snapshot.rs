@@ -0,0 +1,355 @@
+//! Snapshots of the size of a circuit, to catch regressions in tests.
+//!
+//! A snapshot records the hash of the constraints, as computed by
+//! [`TestConstraintSystem::hash`](super::test_cs::TestConstraintSystem::hash), the number of
+//! variables and constraints, and the number of constraints per namespace. It is stored as
+//! text, so that changes show up in diffs:
+//!
+//! ```text
+//! hash 5a0c96e8...
+//! inputs 2
+//! aux 3
+//! constraints 3
+//! namespace 3 cube
+//! namespace 2 cube/square
+//! ```
+//!
+//! [`assert_snapshot`] compares a circuit against a snapshot file and reports which
+//! namespaces grew or shrank.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+/// The size of a circuit, see the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitSnapshot {
+    pub hash: String,
+    pub num_inputs: usize,
+    pub num_aux: usize,
+    pub num_constraints: usize,
+    /// The number of constraints per namespace path, including nested namespaces.
+    pub namespaces: BTreeMap<String, usize>,
+}
+
+impl CircuitSnapshot {
+    /// Creates a snapshot from the paths of all constraints. Namespaces nested deeper than
+    /// `depth` are counted in their ancestor at that depth.
+    pub fn new<'a, I>(
+        hash: String,
+        num_inputs: usize,
+        num_aux: usize,
+        constraint_paths: I,
+        depth: usize,
+    ) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut num_constraints = 0;
+        let mut namespaces = BTreeMap::new();
+        for path in constraint_paths {
+            num_constraints += 1;
+
+            // The last component is the name of the constraint.
+            let components = path.split('/').collect::<Vec<_>>();
+            let nesting = (components.len() - 1).min(depth);
+            for i in 1..=nesting {
+                *namespaces.entry(components[..i].join("/")).or_insert(0) += 1;
+            }
+        }
+
+        CircuitSnapshot {
+            hash,
+            num_inputs,
+            num_aux,
+            num_constraints,
+            namespaces,
+        }
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "hash {}", self.hash)?;
+        writeln!(writer, "inputs {}", self.num_inputs)?;
+        writeln!(writer, "aux {}", self.num_aux)?;
+        writeln!(writer, "constraints {}", self.num_constraints)?;
+        for (path, count) in &self.namespaces {
+            writeln!(writer, "namespace {} {}", count, path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid snapshot line: {}", line),
+            )
+        };
+
+        let mut snapshot = CircuitSnapshot {
+            hash: String::new(),
+            num_inputs: 0,
+            num_aux: 0,
+            num_constraints: 0,
+            namespaces: BTreeMap::new(),
+        };
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, ' ');
+            let key = parts.next().unwrap_or_default();
+            let value = parts.next().ok_or_else(|| invalid(&line))?;
+            let parse = |value: &str| value.parse::<usize>().map_err(|_| invalid(&line));
+            match key {
+                "hash" => snapshot.hash = value.to_string(),
+                "inputs" => snapshot.num_inputs = parse(value)?,
+                "aux" => snapshot.num_aux = parse(value)?,
+                "constraints" => snapshot.num_constraints = parse(value)?,
+                "namespace" => {
+                    let mut parts = value.splitn(2, ' ');
+                    let count = parse(parts.next().unwrap_or_default())?;
+                    let path = parts.next().ok_or_else(|| invalid(&line))?;
+                    snapshot.namespaces.insert(path.to_string(), count);
+                }
+                _ => return Err(invalid(&line)),
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// The differences from `expected` to this snapshot.
+    pub fn diff(&self, expected: &CircuitSnapshot) -> SnapshotDiff {
+        let mut namespaces = BTreeMap::new();
+        for (path, &count) in &expected.namespaces {
+            let actual = self.namespaces.get(path).copied().unwrap_or(0);
+            if actual != count {
+                namespaces.insert(path.clone(), (count, actual));
+            }
+        }
+        for (path, &count) in &self.namespaces {
+            if !expected.namespaces.contains_key(path) {
+                namespaces.insert(path.clone(), (0, count));
+            }
+        }
+
+        SnapshotDiff {
+            hash: (expected.hash.clone(), self.hash.clone()),
+            num_inputs: (expected.num_inputs, self.num_inputs),
+            num_aux: (expected.num_aux, self.num_aux),
+            num_constraints: (expected.num_constraints, self.num_constraints),
+            namespaces,
+        }
+    }
+}
+
+/// The differences between two snapshots, as pairs of expected and actual values. Only
+/// namespaces whose number of constraints changed are included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    pub hash: (String, String),
+    pub num_inputs: (usize, usize),
+    pub num_aux: (usize, usize),
+    pub num_constraints: (usize, usize),
+    pub namespaces: BTreeMap<String, (usize, usize)>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.hash.0 == self.hash.1
+            && self.num_inputs.0 == self.num_inputs.1
+            && self.num_aux.0 == self.num_aux.1
+            && self.num_constraints.0 == self.num_constraints.1
+            && self.namespaces.is_empty()
+    }
+}
+
+fn write_change(f: &mut fmt::Formatter, name: &str, (from, to): (usize, usize)) -> fmt::Result {
+    let (sign, delta) = match to.cmp(&from) {
+        Ordering::Equal => return Ok(()),
+        Ordering::Greater => ('+', to - from),
+        Ordering::Less => ('-', from - to),
+    };
+    writeln!(f, "  {}: {} -> {} ({}{})", name, from, to, sign, delta)
+}
+
+impl fmt::Display for SnapshotDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "circuit matches the snapshot");
+        }
+
+        writeln!(f, "circuit differs from the snapshot:")?;
+        if self.hash.0 != self.hash.1 {
+            writeln!(f, "  hash: {} -> {}", self.hash.0, self.hash.1)?;
+        }
+        write_change(f, "inputs", self.num_inputs)?;
+        write_change(f, "aux", self.num_aux)?;
+        write_change(f, "constraints", self.num_constraints)?;
+
+        if !self.namespaces.is_empty() {
+            writeln!(f, "constraints per namespace:")?;
+            for (path, &change) in &self.namespaces {
+                write_change(f, path, change)?;
+            }
+        } else if self.num_constraints.0 == self.num_constraints.1 {
+            writeln!(f, "the constraints changed, but not their number")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares `snapshot` against the snapshot stored at `path`, and panics with a report of
+/// the differences if they don't match.
+///
+/// A missing snapshot is an error too, so that a misspelled path can't pass silently. The
+/// snapshot is written instead if `BELLMAN_UPDATE_SNAPSHOTS` is set, which is how snapshots
+/// are created, and updated after an intended change to a circuit.
+pub fn assert_snapshot<P: AsRef<Path>>(path: P, snapshot: &CircuitSnapshot) {
+    let path = path.as_ref();
+    if env::var("BELLMAN_UPDATE_SNAPSHOTS").is_ok() {
+        let file = fs::File::create(path)
+            .unwrap_or_else(|e| panic!("failed to create {}: {}", path.display(), e));
+        snapshot
+            .write(io::BufWriter::new(file))
+            .unwrap_or_else(|e| panic!("failed to write {}: {}", path.display(), e));
+        return;
+    }
+
+    if !path.exists() {
+        panic!(
+            "{}: snapshot doesn't exist, set BELLMAN_UPDATE_SNAPSHOTS to create it",
+            path.display()
+        );
+    }
+
+    let file =
+        fs::File::open(path).unwrap_or_else(|e| panic!("failed to open {}: {}", path.display(), e));
+    let expected = CircuitSnapshot::read(io::BufReader::new(file))
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", path.display(), e));
+
+    let diff = snapshot.diff(&expected);
+    if !diff.is_empty() {
+        panic!(
+            "{}: {}set BELLMAN_UPDATE_SNAPSHOTS to update the snapshot",
+            path.display(),
+            diff
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(hash: &str, paths: &[&str]) -> CircuitSnapshot {
+        CircuitSnapshot::new(hash.to_string(), 2, 5, paths.iter().copied(), 2)
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let base = snapshot("00", &["a/b/c/x", "a/b/y", "a/z", "w", "d/e/v"]);
+        assert_eq!(base.num_constraints, 5);
+        let namespaces = base
+            .namespaces
+            .iter()
+            .map(|(path, &count)| (path.as_str(), count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            namespaces,
+            [("a", 3), ("a/b", 2), ("d", 1), ("d/e", 1)],
+            "namespaces are truncated to the depth"
+        );
+
+        let mut bytes = vec![];
+        base.write(&mut bytes).unwrap();
+        assert_eq!(CircuitSnapshot::read(&bytes[..]).unwrap(), base);
+        assert!(CircuitSnapshot::read(&b"inputs two\n"[..]).is_err());
+        assert!(base.diff(&base).is_empty());
+
+        let grown = snapshot("01", &["a/b/c/x", "a/b/y", "a/b/y2", "w", "f/u", "d/e/v"]);
+        let diff = grown.diff(&base);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.num_constraints, (5, 6));
+        let namespaces = diff
+            .namespaces
+            .iter()
+            .map(|(path, &change)| (path.as_str(), change))
+            .collect::<Vec<_>>();
+        assert_eq!(namespaces, [("a/b", (2, 3)), ("f", (0, 1))]);
+        let report = diff.to_string();
+        assert!(report.contains("  constraints: 5 -> 6 (+1)\n"));
+        assert!(report.contains("  a/b: 2 -> 3 (+1)\n"));
+        assert!(!report.contains("  a:"));
+
+        let changed = snapshot("02", &["a/b/c/x", "a/b/y", "a/z", "w", "d/e/v"]);
+        let report = changed.diff(&base).to_string();
+        assert!(report.contains("the constraints changed, but not their number"));
+    }
+
+    #[test]
+    fn test_cs_snapshots() {
+        use crate::util_cs::metric_cs::MetricCS;
+        use crate::util_cs::test_cs::TestConstraintSystem;
+        use crate::ConstraintSystem;
+        use blstrs::Scalar as Fr;
+
+        fn synthesize<CS: ConstraintSystem<Fr>>(cs: &mut CS) {
+            let mut cs = cs.namespace(|| "cube");
+            let x = cs.alloc(|| "x", || Ok(Fr::from(2u64))).unwrap();
+            let x2 = {
+                let mut cs = cs.namespace(|| "square");
+                let x2 = cs.alloc(|| "x2", || Ok(Fr::from(4u64))).unwrap();
+                cs.enforce(|| "x * x = x2", |lc| lc + x, |lc| lc + x, |lc| lc + x2);
+                x2
+            };
+            let x3 = cs.alloc_input(|| "x3", || Ok(Fr::from(8u64))).unwrap();
+            cs.enforce(|| "x2 * x = x3", |lc| lc + x2, |lc| lc + x, |lc| lc + x3);
+        }
+
+        let mut test_cs = TestConstraintSystem::<Fr>::new();
+        synthesize(&mut test_cs);
+        let mut metric_cs = MetricCS::<Fr>::new();
+        synthesize(&mut metric_cs);
+
+        let snapshot = test_cs.snapshot(2);
+        assert_eq!(metric_cs.snapshot(2), snapshot);
+        assert_eq!(snapshot.num_inputs, 2);
+        assert_eq!(snapshot.num_aux, 2);
+        assert_eq!(snapshot.num_constraints, 2);
+        assert_eq!(snapshot.namespaces["cube"], 2);
+        assert_eq!(snapshot.namespaces["cube/square"], 1);
+        assert_eq!(test_cs.snapshot(1).namespaces.len(), 1);
+    }
+
+    #[test]
+    fn test_missing_snapshot() {
+        use crate::test_utils::with_env_vars;
+        use std::panic;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.snap");
+        let base = snapshot("00", &["a/x", "b/y"]);
+
+        with_env_vars(vec![("BELLMAN_UPDATE_SNAPSHOTS", None)], || {
+            assert!(panic::catch_unwind(|| assert_snapshot(&path, &base)).is_err());
+            assert!(!path.exists());
+        });
+
+        with_env_vars(vec![("BELLMAN_UPDATE_SNAPSHOTS", Some("1"))], || {
+            assert_snapshot(&path, &base);
+        });
+        with_env_vars(vec![("BELLMAN_UPDATE_SNAPSHOTS", None)], || {
+            assert_snapshot(&path, &base);
+        });
+    }
+}
@@ -2,6 +2,8 @@ use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 
+use crate::util_cs::analysis::{analyze, AnalysisReport};
+use crate::util_cs::snapshot::CircuitSnapshot;
 use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
 use blake2s_simd::State as Blake2s;
 use byteorder::{BigEndian, ByteOrder};
@@ -83,7 +85,7 @@ fn proc_lc<Scalar: PrimeField>(
     map
 }
 
-fn hash_lc<Scalar: PrimeField>(terms: &LinearCombination<Scalar>, h: &mut Blake2s) {
+pub(crate) fn hash_lc<Scalar: PrimeField>(terms: &LinearCombination<Scalar>, h: &mut Blake2s) {
     let map = proc_lc::<Scalar>(terms);
 
     let mut buf = [0u8; 9 + 32];
@@ -111,6 +113,44 @@ fn hash_lc<Scalar: PrimeField>(terms: &LinearCombination<Scalar>, h: &mut Blake2
     }
 }
 
+/// Hashes the number of variables and the constraints of a circuit, shared by
+/// [`TestConstraintSystem::hash`] and
+/// [`MetricCS::hash`](super::metric_cs::MetricCS::hash).
+#[allow(clippy::type_complexity)]
+pub(crate) fn hash_constraints<Scalar: PrimeField>(
+    num_inputs: usize,
+    num_aux: usize,
+    constraints: &[(
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+        LinearCombination<Scalar>,
+        String,
+    )],
+) -> String {
+    let mut h = Blake2s::new();
+    {
+        let mut buf = [0u8; 24];
+
+        BigEndian::write_u64(&mut buf[0..8], num_inputs as u64);
+        BigEndian::write_u64(&mut buf[8..16], num_aux as u64);
+        BigEndian::write_u64(&mut buf[16..24], constraints.len() as u64);
+        h.update(&buf);
+    }
+
+    for constraint in constraints {
+        hash_lc::<Scalar>(&constraint.0, &mut h);
+        hash_lc::<Scalar>(&constraint.1, &mut h);
+        hash_lc::<Scalar>(&constraint.2, &mut h);
+    }
+
+    let mut s = String::new();
+    for b in h.finalize().as_ref() {
+        s += &format!("{:02x}", b);
+    }
+
+    s
+}
+
 fn _eval_lc2<Scalar: PrimeField>(
     terms: &LinearCombination<Scalar>,
     inputs: &[Scalar],
@@ -198,28 +238,41 @@ impl<Scalar: PrimeField> TestConstraintSystem<Scalar> {
     }
 
     pub fn hash(&self) -> String {
-        let mut h = Blake2s::new();
-        {
-            let mut buf = [0u8; 24];
-
-            BigEndian::write_u64(&mut buf[0..8], self.inputs.len() as u64);
-            BigEndian::write_u64(&mut buf[8..16], self.aux.len() as u64);
-            BigEndian::write_u64(&mut buf[16..24], self.constraints.len() as u64);
-            h.update(&buf);
-        }
-
-        for constraint in &self.constraints {
-            hash_lc::<Scalar>(&constraint.0, &mut h);
-            hash_lc::<Scalar>(&constraint.1, &mut h);
-            hash_lc::<Scalar>(&constraint.2, &mut h);
-        }
-
-        let mut s = String::new();
-        for b in h.finalize().as_ref() {
-            s += &format!("{:02x}", b);
-        }
-
-        s
+        hash_constraints(self.inputs.len(), self.aux.len(), &self.constraints)
+    }
+
+    /// A snapshot of the size of the circuit, with namespaces nested deeper than `depth`
+    /// counted in their ancestor at that depth, see
+    /// [`assert_snapshot`](crate::util_cs::snapshot::assert_snapshot).
+    pub fn snapshot(&self, depth: usize) -> CircuitSnapshot {
+        CircuitSnapshot::new(
+            self.hash(),
+            self.inputs.len(),
+            self.aux.len(),
+            self.constraints.iter().map(|c| c.3.as_str()),
+            depth,
+        )
+    }
+
+    /// Finds unconstrained and under-constrained variables, see
+    /// [`analyze`](crate::util_cs::analysis::analyze).
+    pub fn analyze(&self) -> AnalysisReport {
+        let input_paths = self
+            .inputs
+            .iter()
+            .map(|(_, path)| path.as_str())
+            .collect::<Vec<_>>();
+        let aux_paths = self
+            .aux
+            .iter()
+            .map(|(_, path)| path.as_str())
+            .collect::<Vec<_>>();
+
+        analyze(
+            &input_paths,
+            &aux_paths,
+            self.constraints.iter().map(|(a, b, c, _)| (a, b, c)),
+        )
     }
 
     pub fn which_is_unsatisfied(&self) -> Option<&str> {
@@ -334,6 +387,10 @@ fn compute_path(ns: &[String], this: &str) -> String {
 impl<Scalar: PrimeField> ConstraintSystem<Scalar> for TestConstraintSystem<Scalar> {
     type Root = Self;
 
+    fn new() -> Self {
+        Self::default()
+    }
+
     fn alloc<F, A, AR>(&mut self, annotation: A, f: F) -> Result<Variable, SynthesisError>
     where
         F: FnOnce() -> Result<Scalar, SynthesisError>,
@@ -401,6 +458,70 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for TestConstraintSystem<Scala
     fn get_root(&mut self) -> &mut Self::Root {
         self
     }
+
+    // `new` already allocates the input for the constant one.
+    fn new_for_extend(&self) -> Self {
+        Self::default()
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        let input_offset = self.inputs.len() - 1;
+        let aux_offset = self.aux.len();
+        let constraint_offset = self.constraints.len();
+        let prefix = |path: String| {
+            if self.current_namespace.is_empty() {
+                path
+            } else {
+                format!("{}/{}", self.current_namespace.join("/"), path)
+            }
+        };
+
+        let mut named_objects = vec![];
+        for (path, object) in other.named_objects {
+            let object = match object {
+                // The constant one is shared.
+                NamedObject::Var(var) if var == Self::one() => continue,
+                NamedObject::Var(var) => NamedObject::Var(var.shift(input_offset, aux_offset)),
+                NamedObject::Constraint(i) => NamedObject::Constraint(i + constraint_offset),
+                NamedObject::Namespace => NamedObject::Namespace,
+            };
+            named_objects.push((prefix(path), object));
+        }
+        let inputs = other
+            .inputs
+            .into_iter()
+            .skip(1)
+            .map(|(value, path)| (value, prefix(path)))
+            .collect::<Vec<_>>();
+        let aux = other
+            .aux
+            .into_iter()
+            .map(|(value, path)| (value, prefix(path)))
+            .collect::<Vec<_>>();
+        let constraints = other
+            .constraints
+            .into_iter()
+            .map(|(a, b, c, path)| {
+                (
+                    a.shift(input_offset, aux_offset),
+                    b.shift(input_offset, aux_offset),
+                    c.shift(input_offset, aux_offset),
+                    prefix(path),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        for (path, object) in named_objects {
+            self.set_named_obj(path, object);
+        }
+        self.inputs.extend(inputs);
+        self.aux.extend(aux);
+        self.constraints.extend(constraints);
+    }
 }
 
 #[cfg(test)]
@@ -465,4 +586,71 @@ mod tests {
 
         assert!(cs.get("test1/test2/hehe") == Fr::one());
     }
+
+    #[test]
+    fn test_cs_extend() {
+        use crate::gadgets::test::TestConstraintSystem as GadgetTestConstraintSystem;
+        use crate::util_cs::metric_cs::MetricCS;
+        use crate::{synthesize_parallel, Circuit};
+
+        // Proves knowledge of `x` with `x^3` as public input.
+        struct Cube(u64);
+
+        impl Circuit<Fr> for Cube {
+            fn synthesize<CS: ConstraintSystem<Fr>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                let x = Fr::from(self.0);
+                let x_var = cs.alloc(|| "x", || Ok(x))?;
+                let x2 = cs.alloc(|| "x2", || Ok(x.square()))?;
+                cs.enforce(|| "square", |lc| lc + x_var, |lc| lc + x_var, |lc| lc + x2);
+                let x3 = cs.alloc_input(|| "x3", || Ok(x.square() * x))?;
+                cs.enforce(|| "cube", |lc| lc + x2, |lc| lc + x_var, |lc| lc + x3);
+
+                Ok(())
+            }
+        }
+
+        fn synthesize<CS: ConstraintSystem<Fr>>(cs: &mut CS, parallel: bool) {
+            cs.alloc_input(|| "before", || Ok(Fr::one())).unwrap();
+            let mut cs = cs.namespace(|| "cubes");
+            let parts = (2..6).map(Cube).collect::<Vec<_>>();
+            if parallel {
+                synthesize_parallel(&mut cs, parts).unwrap();
+            } else {
+                for (i, part) in parts.into_iter().enumerate() {
+                    part.synthesize(&mut cs.namespace(|| format!("part {}", i)))
+                        .unwrap();
+                }
+            }
+            cs.alloc(|| "after", || Ok(Fr::one())).unwrap();
+        }
+
+        let mut parallel = TestConstraintSystem::<Fr>::new();
+        synthesize(&mut parallel, true);
+        let mut sequential = TestConstraintSystem::<Fr>::new();
+        synthesize(&mut sequential, false);
+        assert!(parallel.is_satisfied());
+        assert_eq!(parallel.num_constraints(), 8);
+        assert_eq!(parallel.hash(), sequential.hash());
+        assert_eq!(parallel.pretty_print(), sequential.pretty_print());
+        assert_eq!(parallel.get("cubes/part 3/x2"), Fr::from(25u64));
+        assert_eq!(parallel.get_input(5, "cubes/part 3/x3"), Fr::from(125u64));
+
+        let mut parallel = GadgetTestConstraintSystem::<Fr>::new();
+        synthesize(&mut parallel, true);
+        let mut sequential = GadgetTestConstraintSystem::<Fr>::new();
+        synthesize(&mut sequential, false);
+        assert!(parallel.is_satisfied());
+        assert_eq!(parallel.hash(), sequential.hash());
+        assert_eq!(parallel.get("cubes/part 1/x"), Fr::from(3u64));
+
+        let mut parallel = MetricCS::<Fr>::new();
+        synthesize(&mut parallel, true);
+        let mut sequential = MetricCS::<Fr>::new();
+        synthesize(&mut sequential, false);
+        assert_eq!(parallel.hash(), sequential.hash());
+        assert_eq!(parallel.pretty_print(), sequential.pretty_print());
+    }
 }
@@ -0,0 +1,234 @@
+//! A constraint system which only records the assignment of a circuit.
+
+use ff::PrimeField;
+
+use crate::groth16::Assignment;
+use crate::{ConstraintSystem, Index, LinearCombination, SynthesisError, Variable};
+
+/// A constraint system which records the values of all variables and ignores constraints;
+/// the closures building linear combinations are never called.
+///
+/// Together with the [`R1csShape`](crate::groth16::R1csShape) of a circuit, the resulting
+/// [`Assignment`] is all the prover needs, which avoids evaluating every constraint during
+/// synthesis, see [`create_proof_batch_with_shape_priority`](
+/// crate::groth16::create_proof_batch_with_shape_priority).
+///
+/// Like the prover, it starts out empty, so the input for the constant one has to be
+/// allocated first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WitnessCS<Scalar: PrimeField> {
+    input_assignment: Vec<Scalar>,
+    aux_assignment: Vec<Scalar>,
+}
+
+impl<Scalar: PrimeField> Default for WitnessCS<Scalar> {
+    fn default() -> Self {
+        WitnessCS {
+            input_assignment: vec![],
+            aux_assignment: vec![],
+        }
+    }
+}
+
+impl<Scalar: PrimeField> WitnessCS<Scalar> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// The assignment of the inputs, starting with the constant one.
+    pub fn input_assignment(&self) -> &[Scalar] {
+        &self.input_assignment
+    }
+
+    pub fn aux_assignment(&self) -> &[Scalar] {
+        &self.aux_assignment
+    }
+
+    pub fn into_assignment(self) -> Assignment<Scalar> {
+        Assignment {
+            inputs: self.input_assignment,
+            aux: self.aux_assignment,
+        }
+    }
+}
+
+impl<Scalar: PrimeField> ConstraintSystem<Scalar> for WitnessCS<Scalar> {
+    type Root = Self;
+
+    fn new() -> Self {
+        Default::default()
+    }
+
+    fn alloc<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.aux_assignment.push(f()?);
+
+        Ok(Variable(Index::Aux(self.aux_assignment.len() - 1)))
+    }
+
+    fn alloc_input<F, A, AR>(&mut self, _: A, f: F) -> Result<Variable, SynthesisError>
+    where
+        F: FnOnce() -> Result<Scalar, SynthesisError>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.input_assignment.push(f()?);
+
+        Ok(Variable(Index::Input(self.input_assignment.len() - 1)))
+    }
+
+    fn enforce<A, AR, LA, LB, LC>(&mut self, _: A, _: LA, _: LB, _: LC)
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+        LA: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LB: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+        LC: FnOnce(LinearCombination<Scalar>) -> LinearCombination<Scalar>,
+    {
+        // Do nothing; the constraints are known from the shape of the circuit.
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn pop_namespace(&mut self) {
+        // Do nothing; we don't care about namespaces in this context.
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn is_extensible() -> bool {
+        true
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.input_assignment
+            // Skip first input, which must have been a temporarily allocated one variable.
+            .extend(&other.input_assignment[1..]);
+        self.aux_assignment.extend(other.aux_assignment);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::groth16::{
+        create_random_proof_with_shape, generate_random_parameters, prepare_verifying_key,
+        synthesize_assignment, synthesize_witness, verify_proof, R1csShape,
+    };
+    use crate::Circuit;
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use rand_xorshift::XorShiftRng;
+
+    // Proves knowledge of `x` with `x^(2^n) = y` for the public input `y`.
+    #[derive(Clone)]
+    struct PowerCircuit {
+        x: Option<Fr>,
+        n: usize,
+    }
+
+    impl Circuit<Fr> for PowerCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let mut value = self.x;
+            let mut var = cs.alloc(|| "x", || value.ok_or(SynthesisError::AssignmentMissing))?;
+            for i in 0..self.n {
+                value = value.map(|v| v.square());
+                let next = cs.alloc(
+                    || format!("x^(2^{})", i + 1),
+                    || value.ok_or(SynthesisError::AssignmentMissing),
+                )?;
+                cs.enforce(
+                    || format!("square {}", i),
+                    |lc| lc + var,
+                    |lc| lc + var,
+                    |lc| lc + next,
+                );
+                var = next;
+            }
+
+            let y = cs.alloc_input(|| "y", || value.ok_or(SynthesisError::AssignmentMissing))?;
+            cs.enforce(|| "y", |lc| lc + var, |lc| lc + CS::one(), |lc| lc + y);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_witness_cs() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let x = Fr::random(&mut *rng);
+        let circuit = PowerCircuit { x: Some(x), n: 6 };
+        let witness = synthesize_witness(circuit.clone()).unwrap();
+        let assignment = synthesize_assignment(circuit.clone()).unwrap();
+        assert_eq!(assignment.inputs, witness.input_assignment());
+        assert_eq!(assignment.aux, witness.aux_assignment());
+
+        // Assignments are required.
+        assert!(synthesize_assignment(PowerCircuit { x: None, n: 6 }).is_err());
+
+        let shape = R1csShape::from_circuit(PowerCircuit { x: None, n: 6 }).unwrap();
+        let params = generate_random_parameters::<Bls12, _, _>(shape.circuit(), rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+        let proof = create_random_proof_with_shape(&shape, circuit, &params, rng).unwrap();
+        let mut y = x;
+        for _ in 0..6 {
+            y = y.square();
+        }
+        assert!(verify_proof(&pvk, &proof, &[y]).unwrap());
+        assert!(!verify_proof(&pvk, &proof, &[x]).unwrap());
+    }
+
+    #[test]
+    fn test_witness_cs_extend() {
+        let rng = &mut XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+
+        let mut full = WitnessCS::<Fr>::new();
+        full.alloc_input(|| "one", || Ok(Fr::one())).unwrap();
+        let mut parts = vec![];
+        for i in 0..10 {
+            if i % 3 == 0 {
+                let mut part = WitnessCS::new();
+                part.alloc_input(|| "one", || Ok(Fr::one())).unwrap();
+                parts.push(part);
+            }
+            let part = parts.last_mut().unwrap();
+
+            let aux = Fr::random(&mut *rng);
+            full.alloc(|| "aux", || Ok(aux)).unwrap();
+            part.alloc(|| "aux", || Ok(aux)).unwrap();
+            let input = Fr::random(&mut *rng);
+            full.alloc_input(|| "input", || Ok(input)).unwrap();
+            part.alloc_input(|| "input", || Ok(input)).unwrap();
+        }
+
+        let mut extended = WitnessCS::new();
+        extended.alloc_input(|| "one", || Ok(Fr::one())).unwrap();
+        for part in parts {
+            extended.extend(part);
+        }
+        assert_eq!(extended, full);
+        assert_eq!(full.input_assignment().len(), 11);
+        assert_eq!(full.aux_assignment().len(), 10);
+    }
+}
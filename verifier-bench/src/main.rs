@@ -130,6 +130,7 @@ where
     let hlen = (1 << (((count + public + 1) as f64).log2().ceil() as usize)) - 1;
     Parameters {
         vk: dummy_vk(public, &mut rng),
+        circuit_digest: None,
         h: Arc::new(random_points::<E::G1, _>(hlen, &mut rng)),
         l: Arc::new(random_points::<E::G1, _>(private, &mut rng)),
         a: Arc::new(random_points::<E::G1, _>(count, &mut rng)),